@@ -0,0 +1,9 @@
+mod movement;
+mod patterns;
+mod resnap;
+mod snapping;
+
+pub use movement::*;
+pub use patterns::*;
+pub use resnap::*;
+pub use snapping::*;
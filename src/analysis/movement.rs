@@ -0,0 +1,273 @@
+use crate::chart::Chart;
+use crate::collections::HitObject;
+use crate::difficulty::Difficulty;
+use crate::utility::ratio_to_f64;
+
+/// How the cursor moves to an osu!standard circle or slider from the object before it.
+///
+/// Values that don't mean anything are `None`: there's no movement into the first object
+/// or the first one after a spinner, and no velocity when the previous object wasn't on
+/// screen yet or the two overlap in time.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Movement {
+    /// Index into the chart's hit objects.
+    pub index: usize,
+    pub time: f64,
+    /// Where the object is, after stacking.
+    pub position: (f64, f64),
+    /// Distance in osu!pixels from where the previous object ended, slider ends included.
+    pub spacing: Option<f64>,
+    /// `spacing` over the time since the previous object ended, in osu!pixels per millisecond.
+    pub velocity: Option<f64>,
+    /// The angle in degrees at the previous object, between the object before it and this one.
+    /// 180 is a straight line, and 0 goes straight back.
+    pub angle: Option<f64>,
+    /// The point of the circle closest to where the optimal path was,
+    /// so the cursor only moves as far as it has to.
+    pub optimal_position: (f64, f64),
+    /// Like `spacing`, along the optimal path.
+    pub optimal_spacing: Option<f64>,
+    /// Like `velocity`, along the optimal path.
+    pub optimal_velocity: Option<f64>,
+}
+
+/// The cursor movements into each osu!standard circle and slider, in the chart's order.
+///
+/// Spinners and osu!mania holds are left out. `file_format` decides how objects stack,
+/// see [`Chart::stacking`].
+pub fn movements(chart: &Chart, difficulty: &Difficulty, file_format: u8) -> Vec<Movement> {
+    let stacks = chart.stacking(difficulty, file_format);
+    let radius = difficulty.circle_radius();
+    let preempt = difficulty.preempt();
+    let timing = chart.timing_map();
+    let slider_multiplier = ratio_to_f64(chart.slider_multiplier);
+
+    let mut movements: Vec<Movement> = Vec::new();
+    let mut previous: Option<Previous> = None;
+    // Where the cursor came from into the previous object.
+    let mut before_previous: Option<(f64, f64)> = None;
+    for (index, (object, stack)) in chart.hit_objects.iter().zip(stacks).enumerate() {
+        if !matches!(object, HitObject::Circle(_) | HitObject::Slider(_)) {
+            previous = None;
+            before_previous = None;
+            continue;
+        }
+        let time = object.start_time();
+        let position = stack.position;
+        let movement = match previous {
+            None => Movement {
+                index,
+                time,
+                position,
+                spacing: None,
+                velocity: None,
+                angle: None,
+                optimal_position: position,
+                optimal_spacing: None,
+                optimal_velocity: None,
+            },
+            Some(Previous {
+                end_time,
+                end_position,
+                mut optimal_end,
+                starts_run,
+            }) => {
+                // A circle starting a run is aimed at its edge closest to this object.
+                if starts_run {
+                    let last = movements
+                        .last_mut()
+                        .expect("previous object has a movement");
+                    last.optimal_position = optimal_point(position, end_position, radius);
+                    optimal_end = last.optimal_position;
+                }
+                let delta_time = time - end_time;
+                let velocity = |spacing: f64| {
+                    (delta_time > 0.0 && delta_time <= preempt).then(|| spacing / delta_time)
+                };
+                let spacing = distance(end_position, position);
+                let optimal_position = optimal_point(optimal_end, position, radius);
+                let optimal_spacing = distance(optimal_end, optimal_position);
+                Movement {
+                    index,
+                    time,
+                    position,
+                    spacing: Some(spacing),
+                    velocity: velocity(spacing),
+                    angle: before_previous.and_then(|from| angle(from, end_position, position)),
+                    optimal_position,
+                    optimal_spacing: Some(optimal_spacing),
+                    optimal_velocity: velocity(optimal_spacing),
+                }
+            }
+        };
+        before_previous = previous.map(|previous| previous.end_position);
+        previous = Some(match object {
+            // The cursor follows the ball to the slider's end, wherever it came in.
+            HitObject::Slider(_) => Previous {
+                end_time: object.end_time(&timing, slider_multiplier),
+                end_position: stack.end_position,
+                optimal_end: stack.end_position,
+                starts_run: false,
+            },
+            _ => Previous {
+                end_time: time,
+                end_position: position,
+                optimal_end: movement.optimal_position,
+                starts_run: previous.is_none(),
+            },
+        });
+        movements.push(movement);
+    }
+    movements
+}
+
+// Where the previous object left the cursor.
+#[derive(Copy, Clone)]
+struct Previous {
+    end_time: f64,
+    end_position: (f64, f64),
+    optimal_end: (f64, f64),
+    // Whether it's a circle with no object before it.
+    starts_run: bool,
+}
+
+// The point of the circle at `centre` closest to `from`, which is `from` itself when it's
+// already inside.
+fn optimal_point(from: (f64, f64), centre: (f64, f64), radius: f64) -> (f64, f64) {
+    let distance = distance(from, centre);
+    if distance <= radius {
+        return from;
+    }
+    let scale = radius / distance;
+    (
+        centre.0 + (from.0 - centre.0) * scale,
+        centre.1 + (from.1 - centre.1) * scale,
+    )
+}
+
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+}
+
+// The angle at `vertex` in degrees, or `None` when it touches either end.
+fn angle(from: (f64, f64), vertex: (f64, f64), to: (f64, f64)) -> Option<f64> {
+    let a = (from.0 - vertex.0, from.1 - vertex.1);
+    let b = (to.0 - vertex.0, to.1 - vertex.1);
+    if a == (0.0, 0.0) || b == (0.0, 0.0) {
+        return None;
+    }
+    let dot = a.0 * b.0 + a.1 * b.1;
+    let cross = a.0 * b.1 - a.1 * b.0;
+    Some(cross.abs().atan2(dot).to_degrees())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::{beatmap, DIFFICULTY};
+
+    // Preempt is 600 milliseconds at AR 9.
+    fn movements_of(hit_objects: &str) -> Vec<Movement> {
+        let fixture = beatmap(0, DIFFICULTY, "0,500,4,2,0,100,1,0", hit_objects);
+        movements(&fixture.chart, &fixture.difficulty, 14)
+    }
+
+    fn circles(circles: &[(f64, f64, i64)]) -> String {
+        circles
+            .iter()
+            .map(|(x, y, time)| format!("{x},{y},{time},1,0,0:0:0:0:"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn spinners_start_over() {
+        let movements = movements_of(
+            "100,100,0,1,0,0:0:0:0:\n256,192,500,12,0,1500\n300,100,2000,1,0,0:0:0:0:",
+        );
+        assert_eq!(movements.len(), 2);
+        let after = movements[1];
+        assert_eq!(after.index, 2);
+        assert_eq!(
+            (after.spacing, after.velocity, after.angle),
+            (None, None, None)
+        );
+        assert_eq!(after.optimal_position, after.position);
+    }
+
+    #[test]
+    fn velocity_needs_the_previous_object_on_screen() {
+        let velocity = |time| movements_of(&circles(&[(100.0, 100.0, 0), (400.0, 100.0, time)]))[1];
+        assert_eq!(velocity(600).velocity, Some(300.0 / 600.0));
+        let late = velocity(601);
+        assert_eq!((late.spacing, late.velocity), (Some(300.0), None));
+        assert_eq!(late.optimal_velocity, None);
+        assert_eq!(velocity(0).velocity, None);
+    }
+
+    #[test]
+    fn angles_are_180_in_a_line_and_0_going_back() {
+        let line = movements_of(&circles(&[
+            (100.0, 100.0, 0),
+            (200.0, 100.0, 200),
+            (300.0, 100.0, 400),
+        ]));
+        assert_eq!(line[1].angle, None);
+        assert_eq!(line[2].angle, Some(180.0));
+        // The last circle is 20 pixels off the first, so it doesn't stack on it.
+        let back = movements_of(&circles(&[
+            (100.0, 100.0, 0),
+            (300.0, 100.0, 200),
+            (120.0, 100.0, 400),
+        ]));
+        assert_eq!(back[2].angle, Some(0.0));
+        let square = movements_of(&circles(&[
+            (100.0, 100.0, 0),
+            (300.0, 100.0, 200),
+            (300.0, 300.0, 400),
+        ]));
+        assert!((square[2].angle.unwrap() - 90.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn optimal_points_stay_inside_the_circles() {
+        let fixture = beatmap(0, DIFFICULTY, "0,500,4,2,0,100,1,0", "");
+        let radius = fixture.difficulty.circle_radius();
+        let movements = movements_of(&circles(&[
+            (100.0, 100.0, 0),
+            (300.0, 100.0, 200),
+            (120.0, 150.0, 400),
+            (400.0, 300.0, 600),
+            (410.0, 310.0, 800),
+        ]));
+        for movement in movements.iter() {
+            let offset = distance(movement.position, movement.optimal_position);
+            assert!(offset <= radius + 1e-9, "{movement:?}");
+        }
+        // The first circle is aimed at its edge facing the second.
+        assert_eq!(movements[0].optimal_position, (100.0 + radius, 100.0));
+        let optimal = movements[1].optimal_spacing.unwrap();
+        assert!((optimal - (200.0 - 2.0 * radius)).abs() < 1e-9);
+        // A circle that overlaps where the cursor already is doesn't need any movement.
+        let overlapping = movements_of(&circles(&[(100.0, 100.0, 0), (130.0, 100.0, 200)]));
+        assert_eq!(overlapping[1].optimal_spacing, Some(0.0));
+    }
+
+    #[test]
+    fn stacked_positions_are_used() {
+        let fixture = beatmap(
+            0,
+            DIFFICULTY,
+            "0,500,4,2,0,100,1,0",
+            &circles(&[(256.0, 192.0, 0), (256.0, 192.0, 100)]),
+        );
+        let stacks = fixture.chart.stacking(&fixture.difficulty, 14);
+        let movements = movements(&fixture.chart, &fixture.difficulty, 14);
+        assert_ne!(stacks[0].position, (256.0, 192.0));
+        assert_eq!(movements[0].position, stacks[0].position);
+        assert_eq!(movements[1].position, stacks[1].position);
+        let spacing = distance(stacks[0].position, stacks[1].position);
+        assert!(spacing > 0.0);
+        assert_eq!(movements[1].spacing, Some(spacing));
+    }
+}
@@ -0,0 +1,309 @@
+use super::movements;
+use super::Movement;
+use crate::chart::Chart;
+use crate::collections::HitObject;
+use crate::difficulty::Difficulty;
+use crate::utility::ratio_to_f64;
+
+// Objects this many milliseconds apart or less still share a rhythm.
+const RHYTHM_TOLERANCE: f64 = 2.0;
+// Objects at most this far apart in beats are dense enough for streams. A little over
+// 1/4 so rounded timing still counts.
+const STREAM_FRACTION: f64 = 0.26;
+// Dense runs up to this many objects are bursts, longer ones are streams.
+const MAX_BURST_LENGTH: usize = 8;
+// Streams of at least this many objects are deathstreams.
+const DEATHSTREAM_LENGTH: usize = 33;
+// Spacing in circle diameters from which slower objects are jumps.
+const JUMP_SPACING: f64 = 1.5;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PatternKind {
+    /// Three objects in quick succession.
+    Triple,
+    /// A short run of dense objects, up to 8.
+    Burst,
+    Stream,
+    /// A stream of 33 objects or more.
+    Deathstream,
+    /// A burst or stream whose objects don't overlap.
+    SpacedStream,
+    /// Slower objects with at least one and a half circles between them.
+    Jumps,
+    /// Objects alternating between two places.
+    BackAndForth,
+}
+
+/// A run of osu!standard objects forming a pattern.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    pub start_time: f64,
+    /// When the last object ends, so slider ends are included.
+    pub end_time: f64,
+    /// Index into the chart's hit objects of the first object.
+    pub first_index: usize,
+    /// Index into the chart's hit objects of the last object.
+    pub last_index: usize,
+    pub object_count: usize,
+    /// The beatmap's BPM at the start of the pattern.
+    pub bpm: f64,
+    /// How much of a beat is between objects, like 0.25 for 1/4 notes.
+    pub beat_fraction: f64,
+    /// The BPM the objects would be 1/4 notes at, which is how streams are usually described.
+    pub stream_bpm: f64,
+    /// Average distance in osu!pixels between objects.
+    pub mean_spacing: f64,
+    pub max_spacing: f64,
+    /// Average speed between objects in osu!pixels per millisecond.
+    pub mean_velocity: f64,
+}
+
+/// Splits a chart's osu!standard objects into runs of the same rhythm and tags the ones
+/// that form a pattern, in time order.
+///
+/// Runs are broken by a change of rhythm, a spinner, or a slider anywhere but at the end.
+/// Dense runs are triples, bursts or streams depending on their length, and spaced streams
+/// when their objects don't overlap. Slower runs tag their stretches of jumps. Either one
+/// alternating between two places is back-and-forth instead.
+///
+/// `file_format` decides how objects stack, see [`Chart::stacking`].
+pub fn patterns(chart: &Chart, difficulty: &Difficulty, file_format: u8) -> Vec<Pattern> {
+    let timing = chart.timing_map();
+    let slider_multiplier = ratio_to_f64(chart.slider_multiplier);
+    let movements = movements(chart, difficulty, file_format);
+    let radius = difficulty.circle_radius();
+    let diameter = radius * 2.0;
+    let gap = |index: usize| movements[index].time - movements[index - 1].time;
+    // Whether the object at `index` carries on the run starting at `start`.
+    let continues = |start: usize, index: usize| {
+        let previous = &movements[index - 1];
+        movements[index].spacing.is_some()
+            && gap(index) > 0.0
+            && matches!(chart.hit_objects[previous.index], HitObject::Circle(_))
+            && (index == start + 1 || (gap(index) - gap(start + 1)).abs() <= RHYTHM_TOLERANCE)
+    };
+
+    let mut patterns = Vec::new();
+    let mut start = 0;
+    for end in 1..=movements.len() {
+        if end < movements.len() && continues(start, end) {
+            continue;
+        }
+        let run = &movements[start..end];
+        // When only the rhythm changed, the last object also starts the next run.
+        start = match end < movements.len() && continues(end - 1, end) {
+            true => end - 1,
+            false => end,
+        };
+        if run.len() < 3 {
+            continue;
+        }
+        let beat_length = timing.beat_length_at(run[0].time);
+        let beat_fraction = (run[1].time - run[0].time) / beat_length;
+        let pattern = |kind, objects: &[Movement]| {
+            let spacings = objects[1..].iter().filter_map(|movement| movement.spacing);
+            let gaps = objects.len() as f64 - 1.0;
+            let mean_spacing = spacings.clone().sum::<f64>() / gaps;
+            let duration = objects[objects.len() - 1].time - objects[0].time;
+            let last = &objects[objects.len() - 1];
+            Pattern {
+                kind,
+                start_time: objects[0].time,
+                end_time: chart.hit_objects[last.index].end_time(&timing, slider_multiplier),
+                first_index: objects[0].index,
+                last_index: last.index,
+                object_count: objects.len(),
+                bpm: 60000.0 / beat_length,
+                beat_fraction,
+                stream_bpm: 60000.0 / beat_length * 0.25 / beat_fraction,
+                mean_spacing,
+                max_spacing: spacings.fold(0.0, f64::max),
+                mean_velocity: mean_spacing * gaps / duration,
+            }
+        };
+
+        if beat_fraction <= STREAM_FRACTION {
+            let mean_spacing = run[1..]
+                .iter()
+                .filter_map(|movement| movement.spacing)
+                .sum::<f64>()
+                / (run.len() - 1) as f64;
+            let kind = match run.len() {
+                _ if is_back_and_forth(run, radius) => PatternKind::BackAndForth,
+                3 => PatternKind::Triple,
+                _ if mean_spacing >= diameter => PatternKind::SpacedStream,
+                length if length <= MAX_BURST_LENGTH => PatternKind::Burst,
+                length if length < DEATHSTREAM_LENGTH => PatternKind::Stream,
+                _ => PatternKind::Deathstream,
+            };
+            patterns.push(pattern(kind, run));
+            continue;
+        }
+
+        // Stretches of jumps, starting from the object before the first jump.
+        let is_jump = |movement: &Movement| {
+            movement
+                .spacing
+                .is_some_and(|spacing| spacing >= JUMP_SPACING * diameter)
+        };
+        let mut first = None;
+        for index in 1..=run.len() {
+            if index < run.len() && is_jump(&run[index]) {
+                first.get_or_insert(index - 1);
+                continue;
+            }
+            if let Some(first) = first.take() {
+                let jumps = &run[first..index];
+                if jumps.len() >= 3 {
+                    let kind = match is_back_and_forth(jumps, radius) {
+                        true => PatternKind::BackAndForth,
+                        false => PatternKind::Jumps,
+                    };
+                    patterns.push(pattern(kind, jumps));
+                }
+            }
+        }
+    }
+    patterns
+}
+
+// At least four objects that move a whole circle each time, landing where they were
+// two objects before.
+fn is_back_and_forth(objects: &[Movement], radius: f64) -> bool {
+    objects.len() >= 4
+        && objects[1..].iter().all(|movement| {
+            movement
+                .spacing
+                .is_some_and(|spacing| spacing >= radius * 2.0)
+        })
+        && objects.windows(3).all(|window| {
+            let (a, b) = (window[0].position, window[2].position);
+            ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt() <= radius
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::{beatmap, DIFFICULTY};
+
+    // Circles are 73 osu!pixels across and a beat is 500 milliseconds.
+    fn patterns_of(circles: &[(f64, f64, i64)]) -> Vec<Pattern> {
+        let hit_objects: Vec<String> = circles
+            .iter()
+            .map(|(x, y, time)| format!("{x},{y},{time},1,0,0:0:0:0:"))
+            .collect();
+        let fixture = beatmap(
+            0,
+            DIFFICULTY,
+            "0,500,4,2,0,100,1,0",
+            &hit_objects.join("\n"),
+        );
+        patterns(&fixture.chart, &fixture.difficulty, 14)
+    }
+
+    // `count` circles `gap` milliseconds apart, each at `position(i)`.
+    fn run(count: usize, gap: i64, position: impl Fn(usize) -> (f64, f64)) -> Vec<(f64, f64, i64)> {
+        (0..count)
+            .map(|i| {
+                let (x, y) = position(i);
+                (x, y, 1000 + i as i64 * gap)
+            })
+            .collect()
+    }
+
+    // Overlapping circles 10 osu!pixels apart in a line.
+    fn stream(count: usize) -> Vec<(f64, f64, i64)> {
+        run(count, 125, |i| (50.0 + i as f64 * 10.0, 192.0))
+    }
+
+    fn kinds(patterns: &[Pattern]) -> Vec<(PatternKind, usize)> {
+        patterns.iter().map(|p| (p.kind, p.object_count)).collect()
+    }
+
+    #[test]
+    fn dense_runs_are_tagged_by_length() {
+        assert_eq!(kinds(&patterns_of(&stream(2))), []);
+        assert_eq!(kinds(&patterns_of(&stream(3))), [(PatternKind::Triple, 3)]);
+        assert_eq!(kinds(&patterns_of(&stream(8))), [(PatternKind::Burst, 8)]);
+        assert_eq!(kinds(&patterns_of(&stream(9))), [(PatternKind::Stream, 9)]);
+        assert_eq!(
+            kinds(&patterns_of(&stream(32))),
+            [(PatternKind::Stream, 32)]
+        );
+        assert_eq!(
+            kinds(&patterns_of(&stream(33))),
+            [(PatternKind::Deathstream, 33)]
+        );
+
+        let burst = patterns_of(&stream(6))[0];
+        assert_eq!((burst.first_index, burst.last_index), (0, 5));
+        assert_eq!((burst.start_time, burst.end_time), (1000.0, 1625.0));
+        assert_eq!(
+            (burst.bpm, burst.beat_fraction, burst.stream_bpm),
+            (120.0, 0.25, 120.0)
+        );
+        assert!((burst.mean_spacing - 10.0).abs() < 1e-9);
+        assert!((burst.mean_velocity - 10.0 / 125.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn streams_that_dont_overlap_are_spaced() {
+        // About 92 osu!pixels apart, zigzagging so no circle comes back near another.
+        let zigzag = run(10, 125, |i| (50.0 + i as f64 * 45.0, [100.0, 180.0][i % 2]));
+        let patterns = patterns_of(&zigzag);
+        assert_eq!(kinds(&patterns), [(PatternKind::SpacedStream, 10)]);
+        assert!(patterns[0].mean_spacing > 73.0);
+    }
+
+    #[test]
+    fn slower_runs_tag_jumps() {
+        let corners = [
+            (50.0, 50.0),
+            (200.0, 50.0),
+            (200.0, 200.0),
+            (350.0, 200.0),
+            (350.0, 350.0),
+        ];
+        let jumps = run(5, 250, |i| corners[i]);
+        let patterns = patterns_of(&jumps);
+        assert_eq!(kinds(&patterns), [(PatternKind::Jumps, 5)]);
+        assert_eq!(patterns[0].beat_fraction, 0.5);
+        assert_eq!(patterns[0].max_spacing, 150.0);
+
+        // Jumps that don't go far enough aren't tagged.
+        let close = run(5, 250, |i| (50.0 + i as f64 * 100.0, 192.0));
+        assert_eq!(kinds(&patterns_of(&close)), []);
+    }
+
+    #[test]
+    fn alternating_between_two_places_is_back_and_forth() {
+        let alternating = run(6, 250, |i| [(100.0, 192.0), (300.0, 192.0)][i % 2]);
+        assert_eq!(
+            kinds(&patterns_of(&alternating)),
+            [(PatternKind::BackAndForth, 6)]
+        );
+        // Three objects don't alternate yet.
+        assert_eq!(
+            kinds(&patterns_of(&alternating[..3])),
+            [(PatternKind::Jumps, 3)]
+        );
+    }
+
+    #[test]
+    fn rhythm_changes_split_runs() {
+        // Four 1/4 gaps, then six 1/5 gaps continuing from the last 1/4 circle.
+        let mut circles = stream(5);
+        let (x, _, time) = circles[4];
+        circles.extend((1..=6).map(|i| (x + i as f64 * 10.0, 192.0, time + i * 100)));
+        let patterns = patterns_of(&circles);
+        assert_eq!(
+            kinds(&patterns),
+            [(PatternKind::Burst, 5), (PatternKind::Burst, 7)]
+        );
+        assert_eq!(patterns[0].last_index, 4);
+        assert_eq!(patterns[1].first_index, 4);
+        assert_eq!(patterns[1].beat_fraction, 0.2);
+    }
+}
@@ -0,0 +1,248 @@
+use super::snapping::closest_snap;
+use super::snapping::divisors;
+use super::SnapPart;
+use crate::chart::Chart;
+use crate::collections::HitObject;
+use crate::editor::Editor;
+use crate::utility::ratio_to_f64;
+
+// Smaller shifts in milliseconds are floating point noise, not moves.
+const TIME_EPSILON: f64 = 1e-6;
+
+/// What a resnap moved.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ResnapTarget {
+    /// Index into the chart's hit objects, and which part of the object moved.
+    Object { index: usize, part: SnapPart },
+    /// Index into the editor's bookmarks.
+    Bookmark { index: usize },
+    /// Index into the chart's timing points. Only inherited ones are moved.
+    TimingPoint { index: usize },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ResnapChange {
+    pub target: ResnapTarget,
+    pub old_time: f64,
+    pub new_time: f64,
+}
+
+impl ResnapChange {
+    /// How far it moved in milliseconds, negative when moved earlier.
+    pub fn shift(&self) -> f64 {
+        self.new_time - self.old_time
+    }
+}
+
+/// A chart and editor settings with everything moved onto the beat.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resnapped {
+    pub chart: Chart,
+    pub editor: Editor,
+    /// Everything that moved, with timing points first, then objects and bookmarks.
+    pub changes: Vec<ResnapChange>,
+}
+
+/// Moves objects, slider and spinner ends, bookmarks and inherited timing points
+/// to the closest snap of any allowed divisor, see [`snaps`](super::snaps).
+///
+/// Times stay whole milliseconds, so a 1/3 snap at 766.67 leaves an object at 767 alone.
+/// Slider repeats and ends are moved exactly by changing the slider's length,
+/// and red lines never move.
+/// The editor's `beat_divisor` is allowed along with the usual divisors.
+pub fn resnap(chart: &Chart, editor: &Editor) -> Resnapped {
+    let timing = chart.timing_map();
+    let slider_multiplier = ratio_to_f64(chart.slider_multiplier);
+    let divisors = divisors(editor.beat_divisor);
+    let snap = |time: f64| closest_snap(&timing, time, &divisors).1;
+    let mut changes = Vec::new();
+    let mut change = |target, old_time: f64, new_time: f64| {
+        if (new_time - old_time).abs() > TIME_EPSILON {
+            changes.push(ResnapChange {
+                target,
+                old_time,
+                new_time,
+            });
+        }
+    };
+
+    let mut resnapped = chart.clone();
+    for (index, point) in resnapped.timing_points.iter_mut().enumerate() {
+        if point.uninherited {
+            continue;
+        }
+        let time = snap(point.time as f64).round() as i64;
+        change(
+            ResnapTarget::TimingPoint { index },
+            point.time as f64,
+            time as f64,
+        );
+        point.time = time;
+    }
+    // Slider velocities come from the moved inherited points.
+    let moved_timing = resnapped.timing_map();
+
+    for (index, object) in resnapped.hit_objects.iter_mut().enumerate() {
+        let start = object.start_time();
+        let end = object.end_time(&timing, slider_multiplier);
+        let new_start = snap(start).round() as i64;
+        let start_target = ResnapTarget::Object {
+            index,
+            part: SnapPart::Start,
+        };
+        let end_target = ResnapTarget::Object {
+            index,
+            part: SnapPart::End,
+        };
+        change(start_target, start, new_start as f64);
+        match object {
+            HitObject::Circle(circle) => circle.time = new_start,
+            HitObject::Slider(slider) => {
+                slider.time = new_start;
+                // The first span keeps its own snap wherever the head went, so the
+                // repeats and the end snap too. Slider ends aren't stored,
+                // so they can stay between milliseconds.
+                let span_count = slider.slides.max(1) as f64;
+                let span_end = start + (end - start) / span_count;
+                let new_span_end = snap(span_end);
+                let span_duration = new_span_end - new_start as f64;
+                let moved =
+                    new_start as f64 != start || (new_span_end - span_end).abs() > TIME_EPSILON;
+                let velocity = slider.velocity(&moved_timing, slider_multiplier);
+                if moved && span_duration > 0.0 && velocity > 0.0 {
+                    slider.length = velocity * span_duration;
+                }
+                let moved_end = slider.end_time(&moved_timing, slider_multiplier);
+                change(end_target, end, moved_end);
+            }
+            HitObject::Spinner(spinner) => {
+                spinner.time = new_start;
+                spinner.end_time = (snap(end).round() as i64).max(new_start);
+                change(end_target, end, spinner.end_time as f64);
+            }
+            HitObject::ManiaHold(hold) => {
+                hold.time = new_start;
+                hold.end_time = (snap(end).round() as i64).max(new_start);
+                change(end_target, end, hold.end_time as f64);
+            }
+        }
+    }
+
+    let mut resnapped_editor = editor.clone();
+    for (index, bookmark) in resnapped_editor.bookmarks.iter_mut().enumerate() {
+        let time = snap(*bookmark as f64).round() as i64;
+        change(
+            ResnapTarget::Bookmark { index },
+            *bookmark as f64,
+            time as f64,
+        );
+        *bookmark = time;
+    }
+    Resnapped {
+        chart: resnapped,
+        editor: resnapped_editor,
+        changes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::snaps;
+    use crate::utility::{beatmap, DIFFICULTY};
+
+    const TIMING_POINTS: &str = "0,500,4,2,0,100,1,0
+1003,-100,4,2,0,100,0,0
+2003,400,4,2,0,100,1,0";
+
+    // A circle already on the beat, one off it, a slider with a repeat and a spinner.
+    const HIT_OBJECTS: &str = "256,192,500,1,0,0:0:0:0:
+256,192,1002,1,0,0:0:0:0:
+0,0,1248,2,0,L|68:0,2,68
+256,192,2997,12,0,3390";
+
+    fn editor(bookmarks: Vec<i64>, beat_divisor: Option<i64>) -> Editor {
+        Editor {
+            bookmarks,
+            distance_spacing: None,
+            beat_divisor,
+            grid_size: None,
+            timeline_zoom: None,
+        }
+    }
+
+    fn resnapped() -> (Chart, Resnapped) {
+        let chart = beatmap(0, DIFFICULTY, TIMING_POINTS, HIT_OBJECTS).chart;
+        let resnapped = resnap(&chart, &editor(vec![500, 1010], Some(4)));
+        (chart, resnapped)
+    }
+
+    #[test]
+    fn everything_lands_on_the_grid() {
+        let (_, resnapped) = resnapped();
+        for snap in snaps(&resnapped.chart, Some(4)) {
+            assert!(
+                snap.offset.abs() < 1e-6 || snap.divisor.is_some(),
+                "{snap:?}"
+            );
+        }
+        let slider = snaps(&resnapped.chart, Some(4));
+        let slider: Vec<f64> = slider
+            .iter()
+            .filter(|snap| snap.index == 2)
+            .map(|snap| snap.time)
+            .collect();
+        assert_eq!(slider.len(), 3);
+        assert_eq!(slider[0], 1250.0);
+        assert!((slider[1] - 1500.0).abs() < 1e-9 && (slider[2] - 1750.0).abs() < 1e-9);
+        assert_eq!(resnapped.chart.timing_points[1].time, 1000);
+        assert_eq!(resnapped.editor.bookmarks, [500, 1000]);
+    }
+
+    #[test]
+    fn red_lines_never_move() {
+        let (chart, resnapped) = resnapped();
+        // 2003 is 3 milliseconds off the first red line's beat.
+        assert_eq!(resnapped.chart.timing_points[2], chart.timing_points[2]);
+        assert_eq!(resnapped.chart.timing_points[0], chart.timing_points[0]);
+    }
+
+    #[test]
+    fn changes_report_every_shift() {
+        let (_, resnapped) = resnapped();
+        let changes: Vec<_> = resnapped
+            .changes
+            .iter()
+            .map(|change| (change.target, change.old_time, change.new_time))
+            .collect();
+        let object = |index, part| ResnapTarget::Object { index, part };
+        assert_eq!(changes.len(), 7, "{changes:?}");
+        assert_eq!(
+            changes[0],
+            (ResnapTarget::TimingPoint { index: 1 }, 1003.0, 1000.0)
+        );
+        assert_eq!(changes[1], (object(1, SnapPart::Start), 1002.0, 1000.0));
+        assert_eq!(changes[2], (object(2, SnapPart::Start), 1248.0, 1250.0));
+        assert_eq!(changes[3].0, object(2, SnapPart::End));
+        // Two spans of 68 osu!pixels at 0.28 per millisecond.
+        assert!((changes[3].1 - (1248.0 + 2.0 * 68.0 / 0.28)).abs() < 1e-9);
+        assert!((changes[3].2 - 1750.0).abs() < 1e-9);
+        assert_eq!(changes[4], (object(3, SnapPart::Start), 2997.0, 3003.0));
+        // 12 milliseconds back to a 1/16 snap rather than 13 forward.
+        assert_eq!(changes[5], (object(3, SnapPart::End), 3390.0, 3378.0));
+        assert_eq!(
+            changes[6],
+            (ResnapTarget::Bookmark { index: 1 }, 1010.0, 1000.0)
+        );
+        assert_eq!(resnapped.changes[4].shift(), 6.0);
+        assert_eq!(resnapped.changes[1].shift(), -2.0);
+    }
+
+    #[test]
+    fn nothing_changes_on_a_snapped_chart() {
+        let (_, resnapped) = resnapped();
+        let again = resnap(&resnapped.chart, &resnapped.editor);
+        assert_eq!(again.changes, []);
+        assert_eq!(again.chart, resnapped.chart);
+    }
+}
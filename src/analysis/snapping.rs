@@ -0,0 +1,229 @@
+use crate::chart::Chart;
+use crate::collections::HitObject;
+use crate::collections::TimingMap;
+use crate::utility::ratio_to_f64;
+
+/// The beat divisors objects are expected to snap to, from coarsest to finest.
+pub const SNAP_DIVISORS: [i64; 8] = [1, 2, 3, 4, 6, 8, 12, 16];
+
+/// Objects further than this many milliseconds from every snap are unsnapped.
+pub const UNSNAP_TOLERANCE: f64 = 1.0;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SnapPart {
+    Start,
+    /// A slider's repeat.
+    Repeat,
+    /// The end of a slider, spinner or osu!mania hold.
+    End,
+}
+
+/// How a point in time of an object lines up with the beat.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Snap {
+    /// Index into the chart's hit objects.
+    pub index: usize,
+    pub part: SnapPart,
+    pub time: f64,
+    /// The coarsest divisor the time is snapped to, `None` when it's unsnapped.
+    pub divisor: Option<i64>,
+    /// The closest snap of any divisor.
+    pub snapped_time: f64,
+    /// How far `time` is from `snapped_time` in milliseconds, negative when early.
+    pub offset: f64,
+}
+
+impl Snap {
+    pub fn is_unsnapped(&self) -> bool {
+        self.divisor.is_none()
+    }
+}
+
+/// The snap of every object's start, slider repeats and ends, in the chart's order.
+///
+/// Times are measured from the red line in effect at them. `beat_divisor` is the
+/// divisor the beatmap was mapped with, see [`Editor::beat_divisor`](crate::editor::Editor),
+/// which also counts as snapped when it isn't one of [`SNAP_DIVISORS`], like 1/5 or 1/7.
+pub fn snaps(chart: &Chart, beat_divisor: Option<i64>) -> Vec<Snap> {
+    let timing = chart.timing_map();
+    let slider_multiplier = ratio_to_f64(chart.slider_multiplier);
+    let divisors = divisors(beat_divisor);
+    let mut snaps = Vec::new();
+    for (index, object) in chart.hit_objects.iter().enumerate() {
+        let mut snap = |part, time| {
+            let (divisor, snapped_time) = closest_snap(&timing, time, &divisors);
+            snaps.push(Snap {
+                index,
+                part,
+                time,
+                divisor,
+                snapped_time,
+                offset: time - snapped_time,
+            });
+        };
+        let start_time = object.start_time();
+        snap(SnapPart::Start, start_time);
+        match object {
+            HitObject::Circle(_) => {}
+            HitObject::Slider(slider) => {
+                let span_duration = slider.span_duration(&timing, slider_multiplier);
+                let span_count = slider.slides.max(1);
+                for span in 1..span_count {
+                    snap(SnapPart::Repeat, start_time + span as f64 * span_duration);
+                }
+                snap(
+                    SnapPart::End,
+                    start_time + span_count as f64 * span_duration,
+                );
+            }
+            HitObject::Spinner(_) | HitObject::ManiaHold(_) => {
+                snap(SnapPart::End, object.end_time(&timing, slider_multiplier));
+            }
+        }
+    }
+    snaps
+}
+
+/// The snaps further than [`UNSNAP_TOLERANCE`] from every divisor, see [`snaps`].
+pub fn unsnapped(chart: &Chart, beat_divisor: Option<i64>) -> Vec<Snap> {
+    snaps(chart, beat_divisor)
+        .into_iter()
+        .filter(Snap::is_unsnapped)
+        .collect()
+}
+
+// The snap divisors with the beatmap's own, from coarsest to finest.
+pub(super) fn divisors(beat_divisor: Option<i64>) -> Vec<i64> {
+    let mut divisors = SNAP_DIVISORS.to_vec();
+    if let Some(divisor) = beat_divisor.filter(|&divisor| divisor > 0) {
+        divisors.push(divisor);
+    }
+    divisors.sort_unstable();
+    divisors.dedup();
+    divisors
+}
+
+// The coarsest divisor `time` is within tolerance of, and the closest snap of any divisor.
+pub(super) fn closest_snap(timing: &TimingMap, time: f64, divisors: &[i64]) -> (Option<i64>, f64) {
+    let state = timing.at(time);
+    let origin = state.red_line.time as f64;
+    let mut snapped = None;
+    let mut closest = time;
+    let mut closest_offset = f64::INFINITY;
+    for &divisor in divisors {
+        let step = state.beat_length / divisor as f64;
+        let snap_time = origin + ((time - origin) / step).round() * step;
+        let offset = (time - snap_time).abs();
+        if offset <= UNSNAP_TOLERANCE && snapped.is_none() {
+            snapped = Some(divisor);
+        }
+        if offset < closest_offset {
+            closest = snap_time;
+            closest_offset = offset;
+        }
+    }
+    (snapped, closest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::{beatmap, DIFFICULTY};
+
+    fn chart(timing_points: &str, hit_objects: &str) -> Chart {
+        beatmap(0, DIFFICULTY, timing_points, hit_objects).chart
+    }
+
+    fn circles(times: &[i64]) -> String {
+        times
+            .iter()
+            .map(|time| format!("256,192,{time},1,0,0:0:0:0:"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn divisors_of(chart: &Chart, beat_divisor: Option<i64>) -> Vec<Option<i64>> {
+        snaps(chart, beat_divisor)
+            .iter()
+            .map(|snap| snap.divisor)
+            .collect()
+    }
+
+    #[test]
+    fn times_are_measured_from_the_red_line_in_effect() {
+        // The green line doesn't move the grid, the second red line does.
+        let chart = chart(
+            "0,500,4,2,0,100,1,0\n250,-50,4,2,0,100,0,0\n1010,300,4,2,0,100,1,0",
+            &circles(&[250, 1000, 1010, 1160, 1310]),
+        );
+        assert_eq!(
+            divisors_of(&chart, None),
+            [Some(2), Some(1), Some(1), Some(2), Some(1)]
+        );
+    }
+
+    #[test]
+    fn a_millisecond_off_is_still_snapped() {
+        let chart = chart("0,500,4,2,0,100,1,0", &circles(&[999, 1001, 1002]));
+        let snaps = snaps(&chart, None);
+        assert_eq!((snaps[0].divisor, snaps[0].offset), (Some(1), -1.0));
+        assert_eq!((snaps[1].divisor, snaps[1].offset), (Some(1), 1.0));
+        assert_eq!(snaps[2].divisor, None);
+        assert_eq!((snaps[2].snapped_time, snaps[2].offset), (1000.0, 2.0));
+        assert_eq!(unsnapped(&chart, None), [snaps[2]]);
+    }
+
+    #[test]
+    fn the_coarsest_divisor_is_picked() {
+        // At 600 milliseconds a beat, 1/3 and 1/4 snaps are 200 and 150 apart.
+        let chart = chart(
+            "0,600,4,2,0,100,1,0",
+            &circles(&[600, 900, 800, 750, 700, 650, 675]),
+        );
+        assert_eq!(
+            divisors_of(&chart, None),
+            [
+                Some(1),
+                Some(2),
+                Some(3),
+                Some(4),
+                Some(6),
+                Some(12),
+                Some(8)
+            ]
+        );
+    }
+
+    #[test]
+    fn slider_repeats_and_ends_and_spinner_ends_are_snapped() {
+        // 35 osu!pixels take 1/4 of a beat, so the three spans end on 1/4 and 1/2 snaps.
+        let chart = chart(
+            "0,500,4,2,0,100,1,0",
+            "0,0,1000,2,0,L|35:0,3,35\n256,192,2000,12,0,2750",
+        );
+        let snaps: Vec<_> = snaps(&chart, None)
+            .iter()
+            .map(|snap| (snap.index, snap.part, snap.time, snap.divisor))
+            .collect();
+        assert_eq!(
+            snaps,
+            [
+                (0, SnapPart::Start, 1000.0, Some(1)),
+                (0, SnapPart::Repeat, 1125.0, Some(4)),
+                (0, SnapPart::Repeat, 1250.0, Some(2)),
+                (0, SnapPart::End, 1375.0, Some(4)),
+                (1, SnapPart::Start, 2000.0, Some(1)),
+                (1, SnapPart::End, 2750.0, Some(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_beatmaps_own_divisor_counts_as_snapped() {
+        let chart = chart("0,500,4,2,0,100,1,0", &circles(&[1100, 1400, 1500]));
+        assert_eq!(divisors_of(&chart, None), [None, None, Some(1)]);
+        assert_eq!(divisors_of(&chart, Some(5)), [Some(5), Some(5), Some(1)]);
+        assert_eq!(divisors_of(&chart, Some(0)), [None, None, Some(1)]);
+        assert_eq!(divisors(Some(4)), SNAP_DIVISORS);
+    }
+}
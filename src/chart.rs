@@ -1,5 +1,17 @@
+mod catch;
+mod combo;
+mod mania;
+mod mania_conversion;
+mod stacking;
+mod taiko;
+
 pub use crate::collections::*;
+pub use catch::*;
+pub use combo::*;
+pub use mania::*;
 pub use num::rational::Ratio;
+pub use stacking::*;
+pub use taiko::*;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Chart {
@@ -18,3 +30,11 @@ pub enum Mode {
     Catch,
     Mania,
 }
+
+impl Chart {
+    /// Resolves the timing points, which sorts them, so build it once
+    /// and share it rather than calling this for every lookup.
+    pub fn timing_map(&self) -> TimingMap {
+        TimingMap::new(&self.timing_points)
+    }
+}
@@ -0,0 +1,335 @@
+use crate::chart::Chart;
+use crate::collections::HitObject;
+use crate::collections::NestedKind;
+use crate::collections::Slider;
+use crate::collections::TimingMap;
+use crate::difficulty::Difficulty;
+use crate::utility::legacy_sort;
+use crate::utility::ratio_to_f64;
+use crate::utility::LegacyRandom;
+
+/// The width of the osu!catch playfield in osu!pixels.
+pub const CATCH_PLAYFIELD_WIDTH: f64 = 512.0;
+
+const RNG_SEED: i32 = 1337;
+const BASE_DASH_SPEED: f64 = 1.0;
+// The part of the catcher that catches objects, compared to its whole width.
+const ALLOWED_CATCH_RANGE: f64 = 0.8;
+
+/// An object as it's caught in osu!catch.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CatchObject {
+    pub kind: CatchKind,
+    pub time: f64,
+    /// Only the horizontal position matters in osu!catch.
+    pub x: f64,
+    /// Whether the catcher has to hyperdash to reach the next fruit or droplet.
+    pub hyper_dash: bool,
+    /// How much further in osu!pixels the next fruit or droplet could be
+    /// before it needs a hyperdash. 0 for hyperdashes, tiny droplets and bananas.
+    pub distance_to_hyper_dash: f64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CatchKind {
+    Fruit,
+    /// Juice stream ticks, worth combo.
+    Droplet,
+    /// Filler between droplets, worth no combo.
+    TinyDroplet,
+    Banana,
+}
+
+impl CatchObject {
+    fn new(kind: CatchKind, time: f64, x: f64) -> CatchObject {
+        CatchObject {
+            kind,
+            time,
+            x,
+            hyper_dash: false,
+            distance_to_hyper_dash: 0.0,
+        }
+    }
+
+    /// Fruits and droplets, the objects that give combo and can be hyperdashed to.
+    pub fn is_combo(&self) -> bool {
+        matches!(self.kind, CatchKind::Fruit | CatchKind::Droplet)
+    }
+}
+
+impl Chart {
+    /// The chart's objects as osu!catch plays them.
+    ///
+    /// This is also the osu!standard to osu!catch converter. osu!catch charts are stored
+    /// as osu!standard objects, so native and converted charts go through the same steps.
+    /// Sliders become juice streams and spinners banana showers. Each object is
+    /// followed by the ones generated from it, so objects placed in the middle of
+    /// a juice stream come after the whole stream, as in game.
+    ///
+    /// Tiny droplets and bananas are moved randomly like stable does.
+    /// With `hard_rock_offsets`, fruits close in time are pushed apart, as Hard Rock does.
+    /// `file_format` changes how droplets are spaced, see [`Slider::tick_distance`].
+    pub fn catch_objects(
+        &self,
+        difficulty: &Difficulty,
+        hard_rock_offsets: bool,
+        file_format: u8,
+    ) -> Vec<CatchObject> {
+        let timing = self.timing_map();
+        let slider_multiplier = ratio_to_f64(self.slider_multiplier);
+        let mut rng = LegacyRandom::new(RNG_SEED);
+        let mut last_position: Option<f32> = None;
+        let mut last_start_time = 0.0;
+        let mut objects = Vec::new();
+        for object in self.hit_objects.iter() {
+            match object {
+                HitObject::Circle(circle) => {
+                    let time = circle.time as f64;
+                    let mut x = circle.x as f32;
+                    if hard_rock_offsets {
+                        hard_rock_offset(
+                            &mut x,
+                            time,
+                            &mut last_position,
+                            &mut last_start_time,
+                            &mut rng,
+                        );
+                    }
+                    objects.push(CatchObject::new(CatchKind::Fruit, time, x as f64));
+                }
+                HitObject::Slider(slider) => {
+                    // Stable takes the last control point instead of the end of the path,
+                    // and the start time instead of the end time.
+                    let last_point = slider.curve.points.last().map_or(slider.x, |point| point.0);
+                    last_position = Some(last_point as f32);
+                    last_start_time = slider.time as f64;
+                    for mut nested in self.juice_stream(slider, &timing, file_format) {
+                        match nested.kind {
+                            CatchKind::TinyDroplet => {
+                                let offset = rng.next_range(-20.0, 20.0) as f64;
+                                nested.x +=
+                                    offset.clamp(-nested.x, CATCH_PLAYFIELD_WIDTH - nested.x);
+                            }
+                            // Stable picked a random rotation for droplets.
+                            CatchKind::Droplet => {
+                                rng.next();
+                            }
+                            _ => {}
+                        }
+                        objects.push(nested);
+                    }
+                }
+                HitObject::Spinner(_) | HitObject::ManiaHold(_) => {
+                    let start_time = object.start_time();
+                    let end_time = object.end_time(&timing, slider_multiplier);
+                    let mut spacing = end_time - start_time;
+                    while spacing > 100.0 {
+                        spacing /= 2.0;
+                    }
+                    if spacing <= 0.0 {
+                        continue;
+                    }
+                    let mut time = start_time;
+                    while time <= end_time {
+                        let x = (rng.next_double() * CATCH_PLAYFIELD_WIDTH) as f32;
+                        // Stable picked a random type, rotation and colour for each banana.
+                        rng.next();
+                        rng.next();
+                        rng.next();
+                        objects.push(CatchObject::new(CatchKind::Banana, time, x as f64));
+                        time += spacing;
+                    }
+                }
+            }
+        }
+        mark_hyper_dashes(&mut objects, difficulty);
+        objects
+    }
+
+    // Fruits at the head, repeats and tail, droplets at the ticks,
+    // and tiny droplets filling the gaps between them.
+    fn juice_stream(
+        &self,
+        slider: &Slider,
+        timing: &TimingMap,
+        file_format: u8,
+    ) -> Vec<CatchObject> {
+        let path = slider.path();
+        let x_at = |progress: f64| {
+            path.position_at(progress)
+                .0
+                .clamp(0.0, CATCH_PLAYFIELD_WIDTH)
+        };
+        // Tiny droplets are generated in the order stable generates the nested objects,
+        // where the legacy last tick always comes just before the tail.
+        let mut events = slider.nested_objects(
+            timing,
+            ratio_to_f64(self.slider_multiplier),
+            ratio_to_f64(self.slider_tick_rate),
+            file_format,
+        );
+        let position = events
+            .iter()
+            .position(|e| e.kind == NestedKind::LegacyLastTick);
+        if let Some(position) = position {
+            let legacy_last_tick = events.remove(position);
+            events.insert(events.len() - 1, legacy_last_tick);
+        }
+
+        let mut objects = Vec::new();
+        for (i, event) in events.iter().enumerate() {
+            if let Some(last) = i.checked_sub(1).map(|j| &events[j]) {
+                let since_last_tick = (event.time as i32 - last.time as i32) as f64;
+                if since_last_tick > 80.0 {
+                    let mut time_between_tiny = since_last_tick;
+                    while time_between_tiny > 100.0 {
+                        time_between_tiny /= 2.0;
+                    }
+                    let mut t = time_between_tiny;
+                    while t < since_last_tick {
+                        let progress = last.path_progress
+                            + t / since_last_tick * (event.path_progress - last.path_progress);
+                        objects.push(CatchObject::new(
+                            CatchKind::TinyDroplet,
+                            t + last.time,
+                            x_at(progress),
+                        ));
+                        t += time_between_tiny;
+                    }
+                }
+            }
+            let kind = match event.kind {
+                NestedKind::Tick => CatchKind::Droplet,
+                NestedKind::Head | NestedKind::Repeat | NestedKind::Tail => CatchKind::Fruit,
+                NestedKind::LegacyLastTick => continue,
+            };
+            objects.push(CatchObject::new(
+                kind,
+                event.time,
+                x_at(event.path_progress),
+            ));
+        }
+        objects.sort_by(|a, b| a.time.total_cmp(&b.time));
+        objects
+    }
+}
+
+// Hard Rock moves fruits that are close in time further apart,
+// and randomly moves fruits stacked on top of each other.
+fn hard_rock_offset(
+    x: &mut f32,
+    time: f64,
+    last_position: &mut Option<f32>,
+    last_start_time: &mut f64,
+    rng: &mut LegacyRandom,
+) {
+    let original = *x;
+    let last = match *last_position {
+        // Stable also starts over after objects at position zero.
+        Some(last) if last != 0.0 => last,
+        _ => {
+            *last_position = Some(original);
+            *last_start_time = time;
+            return;
+        }
+    };
+    let position_diff = original - last;
+    // Stable measured time in whole milliseconds.
+    let time_diff = (time - *last_start_time) as i32;
+    if time_diff > 1000 {
+        *last_position = Some(original);
+        *last_start_time = time;
+        return;
+    }
+    if position_diff == 0.0 {
+        let max_offset = (time_diff as f64 / 4.0).max(0.0);
+        let right = rng.next_bool();
+        let amount = (rng.next_range(0.0, max_offset) as f32).min(20.0);
+        *x = match right {
+            true if *x + amount <= CATCH_PLAYFIELD_WIDTH as f32 => *x + amount,
+            true => *x - amount,
+            false if *x - amount >= 0.0 => *x - amount,
+            false => *x + amount,
+        };
+        // The last position isn't moved on, so the next fruit is compared to the same one.
+        return;
+    }
+    if position_diff.abs() < (time_diff / 3) as f32 {
+        if position_diff > 0.0 {
+            if *x + position_diff < CATCH_PLAYFIELD_WIDTH as f32 {
+                *x += position_diff;
+            }
+        } else if *x + position_diff > 0.0 {
+            *x += position_diff;
+        }
+    }
+    *last_position = Some(*x);
+    *last_start_time = time;
+}
+
+// Stable uses the whole catcher for hyperdashes, ignoring the catch range.
+// Objects are compared to the next one in time, not the next one generated,
+// sorted the way lazer sorts them.
+fn mark_hyper_dashes(objects: &mut [CatchObject], difficulty: &Difficulty) {
+    let half_catcher_width = difficulty.catcher_width() / 2.0 / ALLOWED_CATCH_RANGE;
+    let mut combo_objects: Vec<usize> = (0..objects.len())
+        .filter(|&i| objects[i].is_combo())
+        .collect();
+    legacy_sort(&mut combo_objects, |&a, &b| {
+        objects[a].time.total_cmp(&objects[b].time)
+    });
+    let mut last_direction = 0;
+    let mut last_excess = half_catcher_width;
+    for pair in combo_objects.windows(2) {
+        let (current, next) = (objects[pair[0]], objects[pair[1]]);
+        let direction = if next.x > current.x { 1 } else { -1 };
+        // Times are truncated like stable, with a quarter of a frame of grace.
+        let time_to_next =
+            (next.time as i32 - current.time as i32) as f64 - (1000f32 / 60.0 / 4.0) as f64;
+        let distance_to_next = (next.x - current.x).abs()
+            - match last_direction == direction {
+                true => last_excess,
+                false => half_catcher_width,
+            };
+        let distance_to_hyper_dash =
+            (time_to_next * BASE_DASH_SPEED - distance_to_next) as f32 as f64;
+        if distance_to_hyper_dash < 0.0 {
+            objects[pair[0]].hyper_dash = true;
+            last_excess = half_catcher_width;
+        } else {
+            objects[pair[0]].distance_to_hyper_dash = distance_to_hyper_dash;
+            last_excess = distance_to_hyper_dash.clamp(0.0, half_catcher_width);
+        }
+        last_direction = direction;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::fixture;
+
+    #[test]
+    fn hyper_dashes_follow_time_order() {
+        let fixture = fixture("catch.osu");
+        let objects =
+            fixture
+                .chart
+                .catch_objects(&fixture.difficulty, false, fixture.filedata.file_format);
+        // The fruit in the middle of the last juice stream comes after it.
+        assert!(objects.windows(2).any(|w| w[1].time < w[0].time));
+        let mut sorted = objects.clone();
+        sorted.sort_by(|a, b| a.time.total_cmp(&b.time));
+        for object in sorted.iter_mut() {
+            object.hyper_dash = false;
+            object.distance_to_hyper_dash = 0.0;
+        }
+        mark_hyper_dashes(&mut sorted, &fixture.difficulty);
+        for object in objects.iter() {
+            let expected = sorted
+                .iter()
+                .find(|o| o.time == object.time && o.x == object.x);
+            assert_eq!(Some(object), expected);
+        }
+    }
+}
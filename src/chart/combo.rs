@@ -0,0 +1,205 @@
+use crate::chart::Chart;
+use crate::collections::HitObject;
+use crate::parts::Color;
+
+/// The combo colours of the default skin, used when a beatmap has none.
+pub const DEFAULT_COMBO_COLOURS: [Color; 4] = [
+    Color {
+        red: 255,
+        green: 192,
+        blue: 0,
+        alpha: None,
+    },
+    Color {
+        red: 0,
+        green: 202,
+        blue: 0,
+        alpha: None,
+    },
+    Color {
+        red: 18,
+        green: 124,
+        blue: 255,
+        alpha: None,
+    },
+    Color {
+        red: 242,
+        green: 24,
+        blue: 57,
+        alpha: None,
+    },
+];
+
+/// The combo an object belongs to and how it's coloured.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Combo {
+    /// The number drawn on the object, starting at 1 in each combo.
+    pub number: i64,
+    /// Which combo the object is in, starting at 0.
+    pub index: i64,
+    /// Like `index`, but with colour skips added. Wraps around the combo colours.
+    /// As in game, counting starts at 1, so the first combo takes the second colour.
+    pub colour_index: usize,
+    pub colour: Color,
+    pub last_in_combo: bool,
+}
+
+impl Chart {
+    /// Numbers and colours every hit object, in the same order as `hit_objects`.
+    ///
+    /// `combo_colours` are the beatmap's `Combo1` to `Combo8` in order,
+    /// see [`Colours::combo_colours`](crate::customization::Colours::combo_colours).
+    /// The default skin's colours are used if there are none.
+    ///
+    /// Spinners don't start a combo themselves, but the object after one always does.
+    pub fn combos(&self, combo_colours: &[Color]) -> Vec<Combo> {
+        let colours = match combo_colours.is_empty() {
+            true => &DEFAULT_COMBO_COLOURS[..],
+            false => combo_colours,
+        };
+        let mut combos: Vec<Combo> = Vec::with_capacity(self.hit_objects.len());
+        let mut index = 0;
+        let mut index_with_offsets = 0;
+        let mut number = 0;
+        // A new combo flag on a spinner is carried to the next object, along with its colour skip.
+        let mut forced_new_combo = false;
+        let mut extra_offset = 0;
+        let mut after_spinner = false;
+        for (i, object) in self.hit_objects.iter().enumerate() {
+            let flags = object.flags();
+            let is_spinner = matches!(object, HitObject::Spinner(_));
+            let mut new_combo = flags.new_combo;
+            let mut offset = flags.color_skip as usize;
+            if is_spinner {
+                forced_new_combo |= new_combo;
+                extra_offset += offset;
+                new_combo = false;
+                offset = 0;
+            } else {
+                new_combo |= forced_new_combo;
+                offset += extra_offset;
+                forced_new_combo = false;
+                extra_offset = 0;
+            }
+            let offset = if new_combo { offset } else { 0 };
+            let starts_combo = i == 0 || new_combo || (after_spinner && !is_spinner);
+            if starts_combo {
+                number = 1;
+                index += 1;
+                index_with_offsets += offset + 1;
+                if let Some(last) = combos.last_mut() {
+                    last.last_in_combo = true;
+                }
+            } else {
+                number += 1;
+            }
+            after_spinner = is_spinner;
+            combos.push(Combo {
+                number,
+                index: index - 1,
+                colour_index: index_with_offsets % colours.len(),
+                colour: colours[index_with_offsets % colours.len()],
+                last_in_combo: false,
+            });
+        }
+        if let Some(last) = combos.last_mut() {
+            last.last_in_combo = true;
+        }
+        combos
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chart::Mode;
+    use crate::collections::{Circle, HitSample, HitSound, ObjectType, Spinner, Type};
+    use num::rational::Ratio;
+
+    fn flags(object_type: ObjectType, new_combo: bool, color_skip: u8) -> Type {
+        Type {
+            object_type,
+            new_combo,
+            color_skip,
+        }
+    }
+
+    fn hit_sound() -> HitSound {
+        HitSound {
+            normal: false,
+            whistle: false,
+            finish: false,
+            clap: false,
+        }
+    }
+
+    fn circle(time: i64, new_combo: bool, color_skip: u8) -> HitObject {
+        HitObject::Circle(Circle {
+            x: 256,
+            y: 192,
+            time,
+            flags: flags(ObjectType::Circle, new_combo, color_skip),
+            hit_sound: hit_sound(),
+            hit_sample: HitSample::default(),
+        })
+    }
+
+    fn spinner(time: i64, new_combo: bool, color_skip: u8) -> HitObject {
+        HitObject::Spinner(Spinner {
+            x: 256,
+            y: 192,
+            time,
+            flags: flags(ObjectType::Spinner, new_combo, color_skip),
+            hit_sound: hit_sound(),
+            end_time: time + 500,
+            hit_sample: HitSample::default(),
+        })
+    }
+
+    fn chart() -> Chart {
+        Chart {
+            mode: Mode::Osu,
+            stack_leniency: Ratio::new(7, 10),
+            slider_multiplier: Ratio::new(14, 10),
+            slider_tick_rate: Ratio::from_integer(1),
+            timing_points: Vec::new(),
+            hit_objects: vec![
+                circle(0, true, 0),
+                circle(1000, false, 0),
+                circle(2000, true, 2),
+                // The new combo and colour skip move on to the next object.
+                spinner(3000, true, 1),
+                circle(4000, false, 0),
+                spinner(5000, false, 0),
+                // Always a new combo after a spinner.
+                circle(6000, false, 0),
+                circle(7000, false, 0),
+            ],
+        }
+    }
+
+    #[test]
+    fn combos_are_numbered_and_coloured() {
+        let combos = chart().combos(&[]);
+        let numbers: Vec<i64> = combos.iter().map(|combo| combo.number).collect();
+        let indices: Vec<i64> = combos.iter().map(|combo| combo.index).collect();
+        let colours: Vec<usize> = combos.iter().map(|combo| combo.colour_index).collect();
+        let last: Vec<bool> = combos.iter().map(|combo| combo.last_in_combo).collect();
+        assert_eq!(numbers, [1, 2, 1, 2, 1, 2, 1, 2]);
+        assert_eq!(indices, [0, 0, 1, 1, 2, 2, 3, 3]);
+        assert_eq!(colours, [1, 1, 0, 0, 2, 2, 3, 3]);
+        assert_eq!(last, [false, true, false, true, false, true, false, true]);
+        assert_eq!(combos[0].colour, DEFAULT_COMBO_COLOURS[1]);
+    }
+
+    #[test]
+    fn beatmap_colours_replace_the_default_skin() {
+        let colours = &DEFAULT_COMBO_COLOURS[..3];
+        let combos = chart().combos(colours);
+        let indices: Vec<usize> = combos.iter().map(|combo| combo.colour_index).collect();
+        assert_eq!(indices, [1, 1, 1, 1, 0, 0, 1, 1]);
+        for combo in combos {
+            assert_eq!(combo.colour, colours[combo.colour_index]);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use crate::chart::Chart;
+use crate::collections::HitObject;
+use crate::difficulty::Difficulty;
+use crate::utility::ratio_to_f64;
+
+/// An object as it's played in osu!mania.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ManiaObject {
+    pub kind: ManiaKind,
+    /// Counted from 0 on the left.
+    pub column: i64,
+    pub time: f64,
+    /// The same as `time` for notes.
+    pub end_time: f64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ManiaKind {
+    Note,
+    Hold,
+}
+
+impl Difficulty {
+    /// The number of columns in an osu!mania chart, stored as its circle size.
+    pub fn key_count(&self) -> i64 {
+        (ratio_to_f64(self.circle_size).round_ties_even() as i64).max(1)
+    }
+}
+
+/// The column of an osu!mania object at `x`, as the playfield is split into equal parts.
+pub fn mania_column(x: f64, key_count: i64) -> i64 {
+    let column_width = 512f32 / key_count as f32;
+    ((x as f32 / column_width).floor() as i64).clamp(0, key_count - 1)
+}
+
+impl HitObject {
+    /// The osu!mania column the object is in.
+    ///
+    /// The same with or without special style, which only moves the first column apart.
+    pub fn column(&self, key_count: i64) -> i64 {
+        mania_column(self.position().0, key_count)
+    }
+}
+
+impl Chart {
+    /// The chart's objects as osu!mania plays them, in the same order as `hit_objects`.
+    ///
+    /// Only osu!mania charts are played this way, other modes need converting first.
+    pub fn mania_objects(&self, key_count: i64) -> Vec<ManiaObject> {
+        self.hit_objects
+            .iter()
+            .map(|object| {
+                let time = object.start_time();
+                let (kind, end_time) = match object {
+                    HitObject::ManiaHold(hold) => (ManiaKind::Hold, hold.end_time as f64),
+                    _ => (ManiaKind::Note, time),
+                };
+                ManiaObject {
+                    kind,
+                    column: object.column(key_count),
+                    time,
+                    end_time,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::{beatmap, DIFFICULTY};
+
+    #[test]
+    fn key_count_is_the_rounded_circle_size() {
+        let key_count = |cs: &str| {
+            let difficulty = DIFFICULTY.replace("CircleSize:4", &format!("CircleSize:{cs}"));
+            beatmap(3, &difficulty, "0,500,4,2,0,100,1,0", "")
+                .difficulty
+                .key_count()
+        };
+        assert_eq!(key_count("4"), 4);
+        assert_eq!(key_count("7"), 7);
+        // Rounded half to even, like .NET.
+        assert_eq!(key_count("6.5"), 6);
+        assert_eq!(key_count("7.5"), 8);
+        assert_eq!(key_count("0"), 1);
+    }
+
+    #[test]
+    fn columns_split_the_playfield_equally() {
+        for (x, column) in [(0.0, 0), (127.0, 0), (128.0, 1), (511.0, 3), (600.0, 3)] {
+            assert_eq!(mania_column(x, 4), column, "x {x}");
+        }
+        // 512 / 7 isn't whole, so the edges follow stable's single precision.
+        assert_eq!(mania_column(73.0, 7), 0);
+        assert_eq!(mania_column(74.0, 7), 1);
+        assert_eq!(mania_column(-10.0, 7), 0);
+        assert_eq!(mania_column(448.0, 8), 7);
+    }
+
+    #[test]
+    fn objects_take_their_column_and_hold_length() {
+        let fixture = beatmap(
+            3,
+            &DIFFICULTY.replace("CircleSize:4", "CircleSize:7"),
+            "0,500,4,2,0,100,1,0",
+            "36,192,1000,1,0,0:0:0:0:\n475,192,1500,128,0,2500:0:0:0:0:",
+        );
+        let objects = fixture.chart.mania_objects(fixture.difficulty.key_count());
+        assert_eq!(
+            objects,
+            [
+                ManiaObject {
+                    kind: ManiaKind::Note,
+                    column: 0,
+                    time: 1000.0,
+                    end_time: 1000.0,
+                },
+                ManiaObject {
+                    kind: ManiaKind::Hold,
+                    column: 6,
+                    time: 1500.0,
+                    end_time: 2500.0,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,348 @@
+mod end_time_object;
+mod hit_object;
+mod path_object;
+mod pattern;
+
+use crate::chart::Chart;
+use crate::chart::ManiaObject;
+use crate::chart::Mode;
+use crate::collections::HitObject;
+use crate::collections::TimingMap;
+use crate::customization::Break;
+use crate::difficulty::Difficulty;
+use crate::mods::Mods;
+use crate::utility::ratio_to_f64;
+use crate::utility::LegacyRandom;
+use end_time_object::EndTimeObjectPatternGenerator;
+use hit_object::HitObjectPatternGenerator;
+use path_object::PathObjectPatternGenerator;
+use pattern::Generator;
+use pattern::Pattern;
+use pattern::PatternType;
+use std::collections::VecDeque;
+
+// How many of the latest notes the density is measured over.
+const MAX_NOTES_FOR_DENSITY: usize = 7;
+
+impl Chart {
+    /// The number of columns the chart is played with in osu!mania.
+    ///
+    /// Charts from other modes pick it from their objects and difficulty settings
+    /// like stable does, unless a key mod sets it.
+    /// `difficulty` is the beatmap's own, before any mods.
+    pub fn mania_key_count(&self, difficulty: &Difficulty, mods: Mods) -> i64 {
+        if self.mode == Mode::Mania {
+            return difficulty.key_count();
+        }
+        if let Some(key_count) = mods.key_count() {
+            return key_count;
+        }
+        let circle_size = ratio_to_f64(difficulty.circle_size).round_ties_even();
+        let overall_difficulty = ratio_to_f64(difficulty.overall_difficulty).round_ties_even();
+        if !self.hit_objects.is_empty() {
+            let long_objects = self
+                .hit_objects
+                .iter()
+                .filter(|object| !matches!(object, HitObject::Circle(_)))
+                .count();
+            let percent_long_objects = long_objects as f64 / self.hit_objects.len() as f64;
+            if percent_long_objects < 0.2 {
+                return 7;
+            }
+            if percent_long_objects < 0.3 || circle_size >= 5.0 {
+                return if overall_difficulty > 5.0 { 7 } else { 6 };
+            }
+            if percent_long_objects > 0.6 {
+                return if overall_difficulty > 4.0 { 5 } else { 4 };
+            }
+        }
+        (overall_difficulty as i64 + 1).clamp(4, 7)
+    }
+
+    /// The chart's objects converted to osu!mania like stable does it, sorted by time.
+    ///
+    /// Circles become notes, sliders become patterns of notes and holds
+    /// and spinners become holds, all picked with stable's random number generator.
+    /// `difficulty` is the beatmap's own, before any mods. Breaks make the chart count as
+    /// denser, which changes the patterns. Charts already in osu!mania aren't changed,
+    /// see [`Chart::mania_objects`].
+    ///
+    /// The osu!taiko and osu!catch converters are [`Chart::taiko_objects`]
+    /// and [`Chart::catch_objects`].
+    pub fn convert_to_mania(
+        &self,
+        difficulty: &Difficulty,
+        breaks: &[Break],
+        key_count: i64,
+    ) -> Vec<ManiaObject> {
+        if self.mode == Mode::Mania {
+            return self.mania_objects(key_count);
+        }
+        let mut converter = Converter::new(self, difficulty, breaks, key_count);
+        let mut objects: Vec<ManiaObject> = self
+            .hit_objects
+            .iter()
+            .flat_map(|object| converter.convert(object))
+            .collect();
+        objects.sort_by(|a, b| a.time.total_cmp(&b.time));
+        objects
+    }
+}
+
+// What's carried over from one object's conversion to the next.
+struct Converter<'a> {
+    chart: &'a Chart,
+    timing: TimingMap,
+    rng: LegacyRandom,
+    total_columns: i64,
+    conversion_difficulty: f64,
+    previous_pattern: Pattern,
+    // Which way stairs go, starting upwards.
+    last_stair: PatternType,
+    history: NoteHistory,
+}
+
+// Where and when the latest notes were, for choosing the next pattern.
+struct NoteHistory {
+    last_time: f64,
+    last_position: (f32, f32),
+    previous_note_times: VecDeque<f64>,
+    // The average time between the latest notes.
+    density: f64,
+}
+
+impl NoteHistory {
+    fn record_note(&mut self, time: f64, position: (f32, f32)) {
+        self.last_time = time;
+        self.last_position = position;
+    }
+
+    fn compute_density(&mut self, time: f64) {
+        if self.previous_note_times.len() == MAX_NOTES_FOR_DENSITY {
+            self.previous_note_times.pop_front();
+        }
+        self.previous_note_times.push_back(time);
+        let count = self.previous_note_times.len();
+        if count >= 2 {
+            self.density =
+                (self.previous_note_times[count - 1] - self.previous_note_times[0]) / count as f64;
+        }
+    }
+}
+
+impl<'a> Converter<'a> {
+    fn new(chart: &'a Chart, difficulty: &Difficulty, breaks: &[Break], key_count: i64) -> Self {
+        let drain_rate = ratio_to_f64(difficulty.hpdrain_rate) as f32;
+        let circle_size = ratio_to_f64(difficulty.circle_size) as f32;
+        let overall_difficulty = ratio_to_f64(difficulty.overall_difficulty) as f32;
+        let approach_rate = ratio_to_f64(difficulty.approach_rate) as f32;
+        let seed = (drain_rate + circle_size).round_ties_even() as i32 * 20
+            + (overall_difficulty as f64 * 41.2) as i32
+            + approach_rate.round_ties_even() as i32;
+        Converter {
+            chart,
+            timing: chart.timing_map(),
+            rng: LegacyRandom::new(seed),
+            total_columns: key_count,
+            conversion_difficulty: conversion_difficulty(chart, drain_rate, approach_rate, breaks),
+            previous_pattern: Pattern::default(),
+            last_stair: PatternType::STAIR,
+            history: NoteHistory {
+                last_time: 0.0,
+                last_position: (0.0, 0.0),
+                previous_note_times: VecDeque::with_capacity(MAX_NOTES_FOR_DENSITY),
+                density: i32::MAX as f64,
+            },
+        }
+    }
+
+    fn convert(&mut self, object: &HitObject) -> Vec<ManiaObject> {
+        let time = object.start_time();
+        let state = self.timing.at(time);
+        let history = &mut self.history;
+        let mut generator = Generator {
+            rng: &mut self.rng,
+            previous_pattern: &self.previous_pattern,
+            total_columns: self.total_columns,
+            random_start: match self.total_columns == 8 {
+                true => 1,
+                false => 0,
+            },
+            conversion_difficulty: self.conversion_difficulty,
+        };
+
+        let patterns = match object {
+            HitObject::Slider(slider) => {
+                let generator = PathObjectPatternGenerator::new(
+                    &mut generator,
+                    slider,
+                    state.beat_length * state.bpm_multiplier,
+                    ratio_to_f64(self.chart.slider_multiplier),
+                    state.kiai,
+                );
+                for i in 0..=generator.span_count {
+                    let time = (slider.time + generator.segment_duration * i) as f64;
+                    history.record_note(time, (slider.x as f32, slider.y as f32));
+                    history.compute_density(time);
+                }
+                generator.generate()
+            }
+            HitObject::Spinner(_) | HitObject::ManiaHold(_) => {
+                let end_time =
+                    object.end_time(&self.timing, ratio_to_f64(self.chart.slider_multiplier));
+                let generator = EndTimeObjectPatternGenerator::new(
+                    &mut generator,
+                    time,
+                    end_time,
+                    object.hit_sound(),
+                );
+                history.record_note(end_time, (256.0, 192.0));
+                history.compute_density(end_time);
+                // Spinners don't change which pattern the next object is based on.
+                let pattern = generator.generate();
+                return pattern.objects;
+            }
+            HitObject::Circle(circle) => {
+                history.compute_density(time);
+                let position = (circle.x as f32, circle.y as f32);
+                let generator = HitObjectPatternGenerator::new(
+                    &mut generator,
+                    time,
+                    position,
+                    &circle.hit_sound,
+                    history.last_time,
+                    history.last_position,
+                    history.density,
+                    state.beat_length,
+                    state.kiai,
+                    self.last_stair,
+                );
+                self.last_stair = generator.stair_type;
+                history.record_note(time, position);
+                vec![generator.generate()]
+            }
+        };
+
+        if let Some(last) = patterns.last() {
+            self.previous_pattern = last.clone();
+        }
+        patterns
+            .into_iter()
+            .flat_map(|pattern| pattern.objects)
+            .collect()
+    }
+}
+
+// How dense the chart is from 0 to 12, which decides how many notes patterns get.
+fn conversion_difficulty(
+    chart: &Chart,
+    drain_rate: f32,
+    approach_rate: f32,
+    breaks: &[Break],
+) -> f64 {
+    let first = chart
+        .hit_objects
+        .first()
+        .map_or(0, |object| object.start_time() as i64);
+    let last = chart
+        .hit_objects
+        .last()
+        .map_or(0, |object| object.start_time() as i64);
+    let break_time: i64 = breaks.iter().map(|b| b.end_time - b.start_time).sum();
+    // Drain time in whole seconds.
+    let mut drain_time = ((last - first - break_time) as f64 / 1000.0) as i32;
+    if drain_time == 0 {
+        drain_time = 10000;
+    }
+    let difficulty = ((drain_rate + approach_rate.clamp(4.0, 7.0)) as f64 / 1.5
+        + chart.hit_objects.len() as f64 / drain_time as f64 * 9.0)
+        / 38.0
+        * 5.0
+        / 1.15;
+    difficulty.min(12.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mods::Mods;
+    use crate::utility::{beatmap, DIFFICULTY};
+
+    // The key count of an osu!standard chart with ten objects, `sliders` of them sliders.
+    fn key_count(sliders: usize, circle_size: &str, overall_difficulty: &str, mods: Mods) -> i64 {
+        let hit_objects: Vec<String> = (0..10)
+            .map(|i| match i < sliders {
+                true => format!("0,0,{},2,0,L|100:0,1,100", i * 1000),
+                false => format!("256,192,{},1,0,0:0:0:0:", i * 1000),
+            })
+            .collect();
+        key_count_of(
+            &hit_objects.join("\n"),
+            0,
+            circle_size,
+            overall_difficulty,
+            mods,
+        )
+    }
+
+    fn key_count_of(
+        hit_objects: &str,
+        mode: u8,
+        circle_size: &str,
+        overall_difficulty: &str,
+        mods: Mods,
+    ) -> i64 {
+        let difficulty = DIFFICULTY
+            .replace("CircleSize:4", &format!("CircleSize:{circle_size}"))
+            .replace(
+                "OverallDifficulty:8",
+                &format!("OverallDifficulty:{overall_difficulty}"),
+            );
+        let fixture = beatmap(mode, &difficulty, "0,500,4,2,0,100,1,0", hit_objects);
+        fixture.chart.mania_key_count(&fixture.difficulty, mods)
+    }
+
+    #[test]
+    fn native_charts_and_key_mods_set_the_key_count() {
+        let note = "64,192,0,1,0,0:0:0:0:";
+        assert_eq!(key_count_of(note, 3, "7", "8", Mods::NONE), 7);
+        // Key mods only apply to converts.
+        assert_eq!(key_count_of(note, 3, "7", "8", Mods::KEY_4), 7);
+        assert_eq!(key_count(0, "4", "8", Mods::KEY_4), 4);
+        assert_eq!(key_count(0, "4", "8", Mods::KEY_9), 9);
+    }
+
+    #[test]
+    fn few_long_objects_convert_to_seven_keys() {
+        assert_eq!(key_count(0, "4", "0", Mods::NONE), 7);
+        assert_eq!(key_count(1, "4", "0", Mods::NONE), 7);
+    }
+
+    #[test]
+    fn some_long_objects_or_large_circles_depend_on_overall_difficulty() {
+        assert_eq!(key_count(2, "4", "6", Mods::NONE), 7);
+        assert_eq!(key_count(2, "4", "5", Mods::NONE), 6);
+        assert_eq!(key_count(5, "5", "6", Mods::NONE), 7);
+        assert_eq!(key_count(5, "5", "5", Mods::NONE), 6);
+        // Rounded half to even first, so 4.5 is 4.
+        assert_eq!(key_count(5, "4.5", "3", Mods::NONE), 4);
+        assert_eq!(key_count(8, "5", "0", Mods::NONE), 6);
+    }
+
+    #[test]
+    fn mostly_long_objects_convert_to_fewer_keys() {
+        assert_eq!(key_count(7, "4", "5", Mods::NONE), 5);
+        assert_eq!(key_count(7, "4", "4", Mods::NONE), 4);
+        assert_eq!(key_count(10, "4", "10", Mods::NONE), 5);
+    }
+
+    #[test]
+    fn otherwise_overall_difficulty_sets_the_key_count() {
+        assert_eq!(key_count(5, "4", "4", Mods::NONE), 5);
+        assert_eq!(key_count(5, "4", "4.5", Mods::NONE), 5);
+        assert_eq!(key_count(5, "4", "5.5", Mods::NONE), 7);
+        assert_eq!(key_count(6, "4", "2", Mods::NONE), 4);
+        assert_eq!(key_count(6, "4", "10", Mods::NONE), 7);
+        // Charts without objects have no share of long objects.
+        assert_eq!(key_count_of("", 0, "6", "4", Mods::NONE), 5);
+    }
+}
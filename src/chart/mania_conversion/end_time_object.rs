@@ -0,0 +1,56 @@
+use super::pattern::Generator;
+use super::pattern::Pattern;
+use crate::collections::HitSound;
+
+/// Generates a hold for a spinner, or a note when it's too short.
+pub(super) struct EndTimeObjectPatternGenerator<'a, 'b> {
+    generator: &'b mut Generator<'a>,
+    time: f64,
+    end_time: f64,
+    hit_sound: &'b HitSound,
+}
+
+impl<'a, 'b> EndTimeObjectPatternGenerator<'a, 'b> {
+    pub fn new(
+        generator: &'b mut Generator<'a>,
+        time: f64,
+        end_time: f64,
+        hit_sound: &'b HitSound,
+    ) -> Self {
+        EndTimeObjectPatternGenerator {
+            generator,
+            time,
+            end_time,
+            hit_sound,
+        }
+    }
+
+    pub fn generate(mut self) -> Pattern {
+        let mut pattern = Pattern::default();
+        let duration = self.end_time - self.time;
+        let column = match self.generator.total_columns {
+            // Short spinners with a finish go in the special column.
+            8 if self.hit_sound.finish && duration < 1000.0 => 0,
+            8 => self.random_column(None),
+            _ => self.random_column(Some(0)),
+        };
+        match duration >= 100.0 {
+            true => pattern.add(column, self.time, self.end_time),
+            false => pattern.add_note(column, self.time),
+        }
+        pattern
+    }
+
+    // Avoids the previous pattern unless it fills every column.
+    fn random_column(&mut self, lower: Option<i64>) -> i64 {
+        let generator = &mut *self.generator;
+        let previous = generator.previous_pattern;
+        let column = generator.random_column(lower, None);
+        let patterns: &[&Pattern] = match previous.column_with_objects() == generator.total_columns
+        {
+            true => &[],
+            false => &[previous],
+        };
+        generator.find_available_column(column, lower, None, false, |_| true, patterns)
+    }
+}
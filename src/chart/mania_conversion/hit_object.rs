@@ -0,0 +1,314 @@
+use super::pattern::Generator;
+use super::pattern::Pattern;
+use super::pattern::PatternType;
+use crate::collections::HitSound;
+
+/// Generates notes for a circle, from how it's placed compared to the object before it.
+pub(super) struct HitObjectPatternGenerator<'a, 'b> {
+    generator: &'b mut Generator<'a>,
+    time: f64,
+    x: f32,
+    hit_sound: &'b HitSound,
+    convert_type: PatternType,
+    /// Which way stairs go, carried from one circle to the next by the converter.
+    pub stair_type: PatternType,
+}
+
+impl<'a, 'b> HitObjectPatternGenerator<'a, 'b> {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        generator: &'b mut Generator<'a>,
+        time: f64,
+        position: (f32, f32),
+        hit_sound: &'b HitSound,
+        previous_time: f64,
+        previous_position: (f32, f32),
+        density: f64,
+        beat_length: f64,
+        kiai: bool,
+        last_stair: PatternType,
+    ) -> Self {
+        let position_separation = ((position.0 - previous_position.0).powi(2)
+            + (position.1 - previous_position.1).powi(2))
+        .sqrt();
+        let time_separation = time - previous_time;
+
+        let mut convert_type = PatternType::NONE;
+        if time_separation <= 80.0 {
+            // More than 187 BPM.
+            convert_type.insert(PatternType::FORCE_NOT_STACK | PatternType::KEEP_SINGLE);
+        } else if time_separation <= 95.0 {
+            // More than 157 BPM.
+            convert_type
+                .insert(PatternType::FORCE_NOT_STACK | PatternType::KEEP_SINGLE | last_stair);
+        } else if time_separation <= 105.0 {
+            // More than 140 BPM.
+            convert_type.insert(PatternType::FORCE_NOT_STACK | PatternType::LOW_PROBABILITY);
+        } else if time_separation <= 125.0 {
+            // More than 120 BPM.
+            convert_type.insert(PatternType::FORCE_NOT_STACK);
+        } else if time_separation <= 135.0 && position_separation < 20.0 {
+            // A stream at more than 111 BPM.
+            convert_type.insert(PatternType::CYCLE | PatternType::KEEP_SINGLE);
+        } else if time_separation <= 150.0 && position_separation < 20.0 {
+            // A stream at more than 100 BPM.
+            convert_type.insert(PatternType::FORCE_STACK | PatternType::LOW_PROBABILITY);
+        } else if position_separation < 20.0 && density >= beat_length / 2.5 {
+            // A low density stream.
+            convert_type.insert(PatternType::REVERSE | PatternType::LOW_PROBABILITY);
+        } else if density < beat_length / 2.5 || kiai {
+            // High density.
+        } else {
+            convert_type.insert(PatternType::LOW_PROBABILITY);
+        }
+
+        if !convert_type.contains(PatternType::KEEP_SINGLE) {
+            if hit_sound.finish && generator.total_columns != 8 {
+                convert_type.insert(PatternType::MIRROR);
+            } else if hit_sound.clap {
+                convert_type.insert(PatternType::GATHERED);
+            }
+        }
+
+        HitObjectPatternGenerator {
+            generator,
+            time,
+            x: position.0,
+            hit_sound,
+            convert_type,
+            stair_type: last_stair,
+        }
+    }
+
+    pub fn generate(mut self) -> Pattern {
+        let total_columns = self.generator.total_columns;
+        let random_start = self.generator.random_start;
+        let previous = self.generator.previous_pattern;
+        let mut pattern = Pattern::default();
+        if total_columns == 1 {
+            pattern.add_note(0, self.time);
+            return pattern;
+        }
+
+        let last_column = previous.objects.first().map_or(0, |object| object.column);
+        let convert_type = self.convert_type;
+        if convert_type.contains(PatternType::REVERSE) && !previous.objects.is_empty() {
+            // The previous pattern, with its columns reversed.
+            for i in random_start..total_columns {
+                if previous.column_has_object(i) {
+                    pattern.add_note(random_start + total_columns - i - 1, self.time);
+                }
+            }
+            return pattern;
+        }
+
+        if convert_type.contains(PatternType::CYCLE)
+            && previous.objects.len() == 1
+            // The special column on 8K shouldn't be overloaded.
+            && (total_columns != 8 || last_column != 0)
+            // Neither should the middle column.
+            && (total_columns % 2 == 0 || last_column != total_columns / 2)
+        {
+            // Like reversing, but for a single note.
+            pattern.add_note(random_start + total_columns - last_column - 1, self.time);
+            return pattern;
+        }
+
+        if convert_type.contains(PatternType::FORCE_STACK) && !previous.objects.is_empty() {
+            // On the same columns as the previous pattern.
+            for i in random_start..total_columns {
+                if previous.column_has_object(i) {
+                    pattern.add_note(i, self.time);
+                }
+            }
+            return pattern;
+        }
+
+        if previous.objects.len() == 1 {
+            if convert_type.contains(PatternType::STAIR) {
+                // The next column, wrapping around to the start.
+                let mut column = last_column + 1;
+                if column == total_columns {
+                    column = random_start;
+                }
+                pattern.add_note(column, self.time);
+                return pattern;
+            }
+            if convert_type.contains(PatternType::REVERSE_STAIR) {
+                // The previous column, wrapping around to the end.
+                let mut column = last_column - 1;
+                if column == random_start - 1 {
+                    column = total_columns - 1;
+                }
+                pattern.add_note(column, self.time);
+                return pattern;
+            }
+        }
+
+        if convert_type.contains(PatternType::KEEP_SINGLE) {
+            return self.random_notes(1);
+        }
+
+        let difficulty = self.generator.conversion_difficulty;
+        let low_probability = convert_type.contains(PatternType::LOW_PROBABILITY);
+        if convert_type.contains(PatternType::MIRROR) {
+            if difficulty > 6.5 {
+                return self.random_pattern_with_mirrored(0.12, 0.38, 0.12);
+            }
+            if difficulty > 4.0 {
+                return self.random_pattern_with_mirrored(0.12, 0.17, 0.0);
+            }
+            return self.random_pattern_with_mirrored(0.12, 0.0, 0.0);
+        }
+        if difficulty > 6.5 {
+            return match low_probability {
+                true => self.random_pattern(0.78, 0.42, 0.0, 0.0),
+                false => self.random_pattern(1.0, 0.62, 0.0, 0.0),
+            };
+        }
+        if difficulty > 4.0 {
+            return match low_probability {
+                true => self.random_pattern(0.35, 0.08, 0.0, 0.0),
+                false => self.random_pattern(0.52, 0.15, 0.0, 0.0),
+            };
+        }
+        if difficulty > 2.0 {
+            return match low_probability {
+                true => self.random_pattern(0.18, 0.0, 0.0, 0.0),
+                false => self.random_pattern(0.45, 0.0, 0.0, 0.0),
+            };
+        }
+        self.random_pattern(0.0, 0.0, 0.0, 0.0)
+    }
+
+    fn random_notes(&mut self, note_count: i64) -> Pattern {
+        let generator = &mut *self.generator;
+        let previous = generator.previous_pattern;
+        let mut pattern = Pattern::default();
+        let allow_stacking = !self.convert_type.contains(PatternType::FORCE_NOT_STACK);
+        let mut note_count = note_count;
+        if !allow_stacking {
+            note_count = note_count.min(
+                generator.total_columns - generator.random_start - previous.column_with_objects(),
+            );
+        }
+        let gathered = self.convert_type.contains(PatternType::GATHERED);
+        let mut column = generator.column(self.x, true);
+        for _ in 0..note_count {
+            let patterns: &[&Pattern] = match allow_stacking {
+                true => &[&pattern],
+                false => &[&pattern, previous],
+            };
+            column =
+                generator.find_available_column(column, None, None, gathered, |_| true, patterns);
+            pattern.add_note(column, self.time);
+        }
+        pattern
+    }
+
+    // A clap and a finish together make a note in the special column on 8K.
+    fn has_special_column(&self) -> bool {
+        self.hit_sound.clap && self.hit_sound.finish
+    }
+
+    fn random_pattern(&mut self, p2: f64, p3: f64, p4: f64, p5: f64) -> Pattern {
+        let note_count = self.random_note_count(p2, p3, p4, p5);
+        let mut pattern = self.random_notes(note_count);
+        if self.generator.random_start > 0 && self.has_special_column() {
+            pattern.add_note(0, self.time);
+        }
+        pattern
+    }
+
+    // Notes mirrored across the middle of the stage.
+    fn random_pattern_with_mirrored(
+        &mut self,
+        centre_probability: f64,
+        p2: f64,
+        p3: f64,
+    ) -> Pattern {
+        if self.convert_type.contains(PatternType::FORCE_NOT_STACK) {
+            return self.random_pattern(0.5 + p2 / 2.0, p2, (p2 + p3) / 2.0, p3);
+        }
+        let (note_count, add_to_centre) =
+            self.random_note_count_mirrored(centre_probability, p2, p3);
+
+        let generator = &mut *self.generator;
+        let total_columns = generator.total_columns;
+        let random_start = generator.random_start;
+        let mut pattern = Pattern::default();
+        let column_limit = match total_columns % 2 == 0 {
+            true => total_columns,
+            false => total_columns - 1,
+        } / 2;
+        let mut column = generator.random_column(None, Some(column_limit));
+        for _ in 0..note_count {
+            column = generator.find_available_column(
+                column,
+                None,
+                Some(column_limit),
+                false,
+                |_| true,
+                &[&pattern],
+            );
+            pattern.add_note(column, self.time);
+            pattern.add_note(random_start + total_columns - column - 1, self.time);
+        }
+        if add_to_centre {
+            pattern.add_note(total_columns / 2, self.time);
+        }
+        if random_start > 0 && self.has_special_column() {
+            pattern.add_note(0, self.time);
+        }
+        pattern
+    }
+
+    fn random_note_count(&mut self, mut p2: f64, mut p3: f64, mut p4: f64, mut p5: f64) -> i64 {
+        match self.generator.total_columns {
+            2 => (p2, p3, p4, p5) = (0.0, 0.0, 0.0, 0.0),
+            3 => (p2, p3, p4, p5) = (p2.min(0.1), 0.0, 0.0, 0.0),
+            4 => (p2, p3, p4, p5) = (p2.min(0.23), p3.min(0.04), 0.0, 0.0),
+            5 => (p3, p4, p5) = (p3.min(0.15), p4.min(0.03), 0.0),
+            _ => {}
+        }
+        if self.hit_sound.clap {
+            p2 = 1.0;
+        }
+        self.generator.random_note_count(p2, p3, p4, p5, 0.0)
+    }
+
+    // Stable compared against these as inverse probabilities,
+    // so some are converted back and forth around the adjustment.
+    fn random_note_count_mirrored(
+        &mut self,
+        mut centre_probability: f64,
+        mut p2: f64,
+        mut p3: f64,
+    ) -> (i64, bool) {
+        let total_columns = self.generator.total_columns;
+        match total_columns {
+            2 => (centre_probability, p2, p3) = (0.0, 0.0, 0.0),
+            3 => (centre_probability, p2, p3) = (centre_probability.min(0.03), 0.0, 0.0),
+            4 => {
+                centre_probability = 0.0;
+                p2 = 1.0 - ((1.0 - p2) * 2.0).max(0.8);
+                p3 = 0.0;
+            }
+            5 => (centre_probability, p3) = (centre_probability.min(0.03), 0.0),
+            6 => {
+                centre_probability = 0.0;
+                p2 = 1.0 - ((1.0 - p2) * 2.0).max(0.5);
+                p3 = 1.0 - ((1.0 - p3) * 2.0).max(0.85);
+            }
+            _ => {}
+        }
+        let p2 = p2.clamp(0.0, 1.0);
+        let p3 = p3.clamp(0.0, 1.0);
+
+        let centre_value = self.generator.rng.next_double();
+        let note_count = self.generator.random_note_count(p2, p3, 0.0, 0.0, 0.0);
+        let add_to_centre =
+            total_columns % 2 != 0 && note_count != 3 && centre_value > 1.0 - centre_probability;
+        (note_count, add_to_centre)
+    }
+}
@@ -0,0 +1,378 @@
+use super::pattern::Generator;
+use super::pattern::Pattern;
+use super::pattern::PatternType;
+use crate::collections::HitSound;
+use crate::collections::Slider;
+
+/// Generates notes and holds for a slider, from how long each span is.
+pub(super) struct PathObjectPatternGenerator<'a, 'b> {
+    generator: &'b mut Generator<'a>,
+    slider: &'b Slider,
+    start_time: i64,
+    end_time: i64,
+    pub segment_duration: i64,
+    pub span_count: i64,
+    convert_type: PatternType,
+}
+
+impl<'a, 'b> PathObjectPatternGenerator<'a, 'b> {
+    /// `beat_length` is stretched by the green line like stable does.
+    pub fn new(
+        generator: &'b mut Generator<'a>,
+        slider: &'b Slider,
+        beat_length: f64,
+        slider_multiplier: f64,
+        kiai: bool,
+    ) -> Self {
+        let span_count = slider.slides.max(1);
+        let start_time = slider.time;
+        let distance = slider.path().distance();
+        // Calculated like stable, which differs slightly from the slider's real duration.
+        let end_time = (start_time as f64
+            + distance * beat_length * span_count as f64 * 0.01 / slider_multiplier)
+            .floor() as i64;
+        PathObjectPatternGenerator {
+            generator,
+            slider,
+            start_time,
+            end_time,
+            segment_duration: (end_time - start_time) / span_count,
+            span_count,
+            convert_type: match kiai {
+                true => PatternType::NONE,
+                false => PatternType::LOW_PROBABILITY,
+            },
+        }
+    }
+
+    /// One pattern, or two when some objects end with the slider,
+    /// where the second one holds those and is what the next object is based on.
+    pub fn generate(mut self) -> Vec<Pattern> {
+        let original = self.generate_pattern();
+        if original.objects.len() == 1 {
+            return vec![original];
+        }
+        let (end_time_objects, intermediate_objects) = original
+            .objects
+            .into_iter()
+            .partition(|object| object.end_time.round_ties_even() as i64 == self.end_time);
+        vec![
+            Pattern {
+                objects: intermediate_objects,
+            },
+            Pattern {
+                objects: end_time_objects,
+            },
+        ]
+    }
+
+    fn generate_pattern(&mut self) -> Pattern {
+        let start_time = self.start_time;
+        if self.generator.total_columns == 1 {
+            let mut pattern = Pattern::default();
+            self.add(&mut pattern, 0, start_time, self.end_time);
+            return pattern;
+        }
+
+        let segment_duration = self.segment_duration;
+        let difficulty = self.generator.conversion_difficulty;
+        if self.span_count > 1 {
+            if segment_duration <= 90 {
+                return self.random_hold_notes(start_time, 1);
+            }
+            if segment_duration <= 120 {
+                self.convert_type.insert(PatternType::FORCE_NOT_STACK);
+                return self.random_notes(start_time, self.span_count + 1);
+            }
+            if segment_duration <= 160 {
+                return self.stair(start_time);
+            }
+            if segment_duration <= 200 && difficulty > 3.0 {
+                return self.random_multiple_notes(start_time);
+            }
+            if self.end_time - start_time >= 4000 {
+                return self.n_random_notes(start_time, 0.23, 0.0, 0.0);
+            }
+            let generator = &self.generator;
+            if segment_duration > 400
+                && self.span_count < generator.total_columns - 1 - generator.random_start
+            {
+                return self.tiled_hold_notes(start_time);
+            }
+            return self.hold_and_normal_notes(start_time);
+        }
+
+        if segment_duration <= 110 {
+            match self.generator.previous_pattern.column_with_objects()
+                < self.generator.total_columns
+            {
+                true => self.convert_type.insert(PatternType::FORCE_NOT_STACK),
+                false => self.convert_type.remove(PatternType::FORCE_NOT_STACK),
+            }
+            let note_count = match segment_duration < 80 {
+                true => 1,
+                false => 2,
+            };
+            return self.random_notes(start_time, note_count);
+        }
+
+        let low_probability = self.convert_type.contains(PatternType::LOW_PROBABILITY);
+        let (p2, p3, p4) = if difficulty > 6.5 {
+            match low_probability {
+                true => (0.78, 0.3, 0.0),
+                false => (0.85, 0.36, 0.03),
+            }
+        } else if difficulty > 4.0 {
+            match low_probability {
+                true => (0.43, 0.08, 0.0),
+                false => (0.56, 0.18, 0.0),
+            }
+        } else if difficulty > 2.5 {
+            match low_probability {
+                true => (0.3, 0.0, 0.0),
+                false => (0.37, 0.08, 0.0),
+            }
+        } else {
+            match low_probability {
+                true => (0.17, 0.0, 0.0),
+                false => (0.27, 0.0, 0.0),
+            }
+        };
+        self.n_random_notes(start_time, p2, p3, p4)
+    }
+
+    fn column(&self) -> i64 {
+        self.generator.column(self.slider.x as f32, true)
+    }
+
+    // The column of the slider, moved out of the previous pattern when stacking isn't allowed.
+    fn initial_column(&mut self) -> i64 {
+        let column = self.column();
+        let generator = &mut *self.generator;
+        let previous = generator.previous_pattern;
+        match self.convert_type.contains(PatternType::FORCE_NOT_STACK)
+            && previous.column_with_objects() < generator.total_columns
+        {
+            true => {
+                generator.find_available_column(column, None, None, false, |_| true, &[previous])
+            }
+            false => column,
+        }
+    }
+
+    // Holds that all start together and last the whole slider.
+    fn random_hold_notes(&mut self, start_time: i64, note_count: i64) -> Pattern {
+        let mut pattern = Pattern::default();
+        let generator = &mut *self.generator;
+        let previous = generator.previous_pattern;
+        let usable_columns =
+            generator.total_columns - generator.random_start - previous.column_with_objects();
+        let mut column = generator.random_column(None, None);
+        let end_time = self.end_time;
+        for _ in 0..usable_columns.min(note_count) {
+            column = generator.find_available_column(
+                column,
+                None,
+                None,
+                false,
+                |_| true,
+                &[&pattern, previous],
+            );
+            pattern.add(column, start_time as f64, end_time as f64);
+        }
+        // Any left over can stack on the previous pattern.
+        for _ in 0..note_count - usable_columns {
+            column =
+                generator.find_available_column(column, None, None, false, |_| true, &[&pattern]);
+            pattern.add(column, start_time as f64, end_time as f64);
+        }
+        pattern
+    }
+
+    // A note at each slider edge, never twice in a row in the same column.
+    fn random_notes(&mut self, mut start_time: i64, note_count: i64) -> Pattern {
+        let mut pattern = Pattern::default();
+        let mut column = self.initial_column();
+        let mut last_column = column;
+        for _ in 0..note_count {
+            self.add(&mut pattern, column, start_time, start_time);
+            column = self.generator.find_available_column(
+                column,
+                None,
+                None,
+                false,
+                |c| c != last_column,
+                &[],
+            );
+            last_column = column;
+            start_time += self.segment_duration;
+        }
+        pattern
+    }
+
+    // A note at each slider edge, going back and forth across the stage.
+    fn stair(&mut self, mut start_time: i64) -> Pattern {
+        let mut pattern = Pattern::default();
+        let mut column = self.column();
+        let mut increasing = self.generator.rng.next_double() > 0.5;
+        let total_columns = self.generator.total_columns;
+        let random_start = self.generator.random_start;
+        for _ in 0..=self.span_count {
+            self.add(&mut pattern, column, start_time, start_time);
+            start_time += self.segment_duration;
+            // Turn around at the edges of the stage.
+            if increasing {
+                if column >= total_columns - 1 {
+                    increasing = false;
+                    column -= 1;
+                } else {
+                    column += 1;
+                }
+            } else if column <= random_start {
+                increasing = true;
+                column += 1;
+            } else {
+                column -= 1;
+            }
+        }
+        pattern
+    }
+
+    // One or two notes at each slider edge.
+    fn random_multiple_notes(&mut self, mut start_time: i64) -> Pattern {
+        let mut pattern = Pattern::default();
+        let total_columns = self.generator.total_columns;
+        let random_start = self.generator.random_start;
+        let legacy = (4..=8).contains(&total_columns);
+        let interval = self
+            .generator
+            .rng
+            .next_range(1.0, (total_columns - legacy as i64) as f64) as i64;
+        let mut column = self.column();
+        for _ in 0..=self.span_count {
+            self.add(&mut pattern, column, start_time, start_time);
+            column += interval;
+            if column >= total_columns - random_start {
+                column = column - total_columns - random_start + legacy as i64;
+            }
+            column += random_start;
+            // Too many doubles in a row are unplayable on 2K.
+            if total_columns > 2 {
+                self.add(&mut pattern, column, start_time, start_time);
+            }
+            column = self.generator.random_column(None, None);
+            start_time += self.segment_duration;
+        }
+        pattern
+    }
+
+    // A random number of holds, more likely with claps and finishes.
+    fn n_random_notes(
+        &mut self,
+        start_time: i64,
+        mut p2: f64,
+        mut p3: f64,
+        mut p4: f64,
+    ) -> Pattern {
+        match self.generator.total_columns {
+            2 => (p2, p3, p4) = (0.0, 0.0, 0.0),
+            3 => (p2, p3, p4) = (p2.min(0.1), 0.0, 0.0),
+            4 => (p2, p3, p4) = (p2.min(0.3), p3.min(0.04), 0.0),
+            5 => (p2, p3, p4) = (p2.min(0.34), p3.min(0.1), p4.min(0.03)),
+            _ => {}
+        }
+        let is_double = |sound: &HitSound| sound.clap || sound.finish;
+        let can_generate_two_notes = !self.convert_type.contains(PatternType::LOW_PROBABILITY)
+            && (is_double(&self.slider.hit_sound) || is_double(self.sound_at(self.start_time)));
+        if can_generate_two_notes {
+            p2 = 1.0;
+        }
+        let note_count = self.generator.random_note_count(p2, p3, p4, 0.0, 0.0);
+        self.random_hold_notes(start_time, note_count)
+    }
+
+    // Holds starting at each slider edge, all ending together.
+    fn tiled_hold_notes(&mut self, mut start_time: i64) -> Pattern {
+        let mut pattern = Pattern::default();
+        let column_repeat = self.span_count.min(self.generator.total_columns);
+        // Because of rounding, this isn't always the slider's end time.
+        let end_time = start_time + self.segment_duration * self.span_count;
+        let mut column = self.initial_column();
+        for _ in 0..column_repeat {
+            column = self.generator.find_available_column(
+                column,
+                None,
+                None,
+                false,
+                |_| true,
+                &[&pattern],
+            );
+            self.add(&mut pattern, column, start_time, end_time);
+            start_time += self.segment_duration;
+        }
+        pattern
+    }
+
+    // A hold through the whole slider, with notes beside it at each slider edge.
+    fn hold_and_normal_notes(&mut self, mut start_time: i64) -> Pattern {
+        let mut pattern = Pattern::default();
+        let hold_column = self.initial_column();
+        self.add(&mut pattern, hold_column, start_time, self.end_time);
+
+        let total_columns = self.generator.total_columns;
+        let difficulty = self.generator.conversion_difficulty;
+        let mut column = self.generator.random_column(None, None);
+        let note_count = if difficulty > 6.5 {
+            self.generator.random_note_count(0.63, 0.0, 0.0, 0.0, 0.0)
+        } else if difficulty > 4.0 {
+            let p2 = if total_columns < 6 { 0.12 } else { 0.45 };
+            self.generator.random_note_count(p2, 0.0, 0.0, 0.0, 0.0)
+        } else if difficulty > 2.5 {
+            let p2 = if total_columns < 6 { 0.0 } else { 0.24 };
+            self.generator.random_note_count(p2, 0.0, 0.0, 0.0, 0.0)
+        } else {
+            0
+        }
+        .min(total_columns - 1);
+
+        // The head only gets notes when it has a sound of its own.
+        let head_sound = self.sound_at(start_time);
+        let ignore_head = !(head_sound.whistle || head_sound.finish || head_sound.clap);
+        for _ in 0..=self.span_count {
+            if !(ignore_head && start_time == self.start_time) {
+                let mut row = Pattern::default();
+                for _ in 0..note_count {
+                    column = self.generator.find_available_column(
+                        column,
+                        None,
+                        None,
+                        false,
+                        |c| c != hold_column,
+                        &[&row],
+                    );
+                    self.add(&mut row, column, start_time, start_time);
+                }
+                pattern.objects.append(&mut row.objects);
+            }
+            start_time += self.segment_duration;
+        }
+        pattern
+    }
+
+    // The sound of the slider edge at or after `time`.
+    fn sound_at(&self, time: i64) -> &'b HitSound {
+        let index = match self.segment_duration {
+            0 => 0,
+            duration => (time - self.start_time) / duration,
+        };
+        let slider = self.slider;
+        slider
+            .edge_sounds
+            .get(index.max(0) as usize)
+            .unwrap_or(&slider.hit_sound)
+    }
+
+    fn add(&self, pattern: &mut Pattern, column: i64, start_time: i64, end_time: i64) {
+        pattern.add(column, start_time as f64, end_time as f64);
+    }
+}
@@ -0,0 +1,164 @@
+use crate::chart::ManiaKind;
+use crate::chart::ManiaObject;
+use crate::utility::LegacyRandom;
+
+/// Objects generated together, which the next object's pattern is based on.
+#[derive(Debug, Clone, Default)]
+pub(super) struct Pattern {
+    pub objects: Vec<ManiaObject>,
+}
+
+impl Pattern {
+    pub fn column_has_object(&self, column: i64) -> bool {
+        self.objects.iter().any(|object| object.column == column)
+    }
+
+    /// The number of columns with at least one object.
+    pub fn column_with_objects(&self) -> i64 {
+        let mut columns: Vec<i64> = self.objects.iter().map(|object| object.column).collect();
+        columns.sort_unstable();
+        columns.dedup();
+        columns.len() as i64
+    }
+
+    pub fn add_note(&mut self, column: i64, time: f64) {
+        self.add(column, time, time);
+    }
+
+    // Objects starting and ending at the same time are notes.
+    pub fn add(&mut self, column: i64, time: f64, end_time: f64) {
+        self.objects.push(ManiaObject {
+            kind: match time == end_time {
+                true => ManiaKind::Note,
+                false => ManiaKind::Hold,
+            },
+            column,
+            time,
+            end_time,
+        });
+    }
+}
+
+/// Hints from the converted object about which pattern to generate.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub(super) struct PatternType(u16);
+
+impl PatternType {
+    pub const NONE: PatternType = PatternType(0);
+    pub const FORCE_STACK: PatternType = PatternType(1 << 0);
+    pub const FORCE_NOT_STACK: PatternType = PatternType(1 << 1);
+    pub const KEEP_SINGLE: PatternType = PatternType(1 << 2);
+    pub const LOW_PROBABILITY: PatternType = PatternType(1 << 3);
+    pub const GATHERED: PatternType = PatternType(1 << 7);
+    pub const MIRROR: PatternType = PatternType(1 << 8);
+    pub const REVERSE: PatternType = PatternType(1 << 9);
+    pub const CYCLE: PatternType = PatternType(1 << 10);
+    pub const STAIR: PatternType = PatternType(1 << 11);
+    pub const REVERSE_STAIR: PatternType = PatternType(1 << 12);
+
+    pub fn contains(&self, other: PatternType) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: PatternType) {
+        self.0 |= other.0;
+    }
+
+    pub fn remove(&mut self, other: PatternType) {
+        self.0 &= !other.0;
+    }
+}
+
+impl std::ops::BitOr for PatternType {
+    type Output = PatternType;
+    fn bitor(self, other: PatternType) -> PatternType {
+        PatternType(self.0 | other.0)
+    }
+}
+
+/// What every pattern generator shares.
+pub(super) struct Generator<'a> {
+    pub rng: &'a mut LegacyRandom,
+    pub previous_pattern: &'a Pattern,
+    pub total_columns: i64,
+    /// 1 on 8K, where the first column is left for special patterns.
+    pub random_start: i64,
+    /// How dense the whole chart is, from 0 to 12.
+    pub conversion_difficulty: f64,
+}
+
+impl Generator<'_> {
+    /// The column an object at `x` goes in.
+    pub fn column(&self, x: f32, allow_special: bool) -> i64 {
+        if allow_special && self.total_columns == 8 {
+            let divisor = 512f32 / 7.0;
+            return ((x / divisor).floor() as i64).clamp(0, 6) + 1;
+        }
+        let divisor = 512f32 / self.total_columns as f32;
+        ((x / divisor).floor() as i64).clamp(0, self.total_columns - 1)
+    }
+
+    /// A random number of notes from 1 to 6, where each `p` is the chance of at least that many.
+    pub fn random_note_count(&mut self, p2: f64, p3: f64, p4: f64, p5: f64, p6: f64) -> i64 {
+        let value = self.rng.next_double();
+        if value >= 1.0 - p6 {
+            6
+        } else if value >= 1.0 - p5 {
+            5
+        } else if value >= 1.0 - p4 {
+            4
+        } else if value >= 1.0 - p3 {
+            3
+        } else if value >= 1.0 - p2 {
+            2
+        } else {
+            1
+        }
+    }
+
+    pub fn random_column(&mut self, lower: Option<i64>, upper: Option<i64>) -> i64 {
+        let lower = lower.unwrap_or(self.random_start);
+        let upper = upper.unwrap_or(self.total_columns);
+        self.rng.next_range(lower as f64, upper as f64) as i64
+    }
+
+    /// Starting from `initial`, finds a column that passes `validation` and is empty in `patterns`.
+    ///
+    /// Other columns are tried at random between `lower` and `upper`,
+    /// or in order when `gathered`. Stable gave up on the whole conversion
+    /// when no column could be found, here the initial column is kept.
+    pub fn find_available_column(
+        &mut self,
+        initial: i64,
+        lower: Option<i64>,
+        upper: Option<i64>,
+        gathered: bool,
+        validation: impl Fn(i64) -> bool,
+        patterns: &[&Pattern],
+    ) -> i64 {
+        let lower_bound = lower.unwrap_or(self.random_start);
+        let upper_bound = upper.unwrap_or(self.total_columns);
+        let is_valid = |column: i64| {
+            validation(column) && !patterns.iter().any(|p| p.column_has_object(column))
+        };
+        if is_valid(initial) {
+            return initial;
+        }
+        if !(lower_bound..upper_bound).any(is_valid) {
+            return initial;
+        }
+        let mut column = initial;
+        loop {
+            column = match gathered {
+                true => match column + 1 == self.total_columns {
+                    true => self.random_start,
+                    false => column + 1,
+                },
+                false => self.random_column(lower, upper),
+            };
+            if is_valid(column) {
+                return column;
+            }
+        }
+    }
+}
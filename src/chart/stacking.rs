@@ -0,0 +1,263 @@
+use crate::chart::Chart;
+use crate::collections::HitObject;
+use crate::difficulty::Difficulty;
+use crate::utility::ratio_to_f64;
+
+// Objects closer than this in osu!pixels stack on each other.
+const STACK_DISTANCE: f64 = 3.0;
+
+/// Where an object is drawn once stacking is applied.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Stack {
+    /// Positive heights move the object up and left, negative ones down and right.
+    pub height: i64,
+    pub offset: (f64, f64),
+    pub position: (f64, f64),
+    pub end_position: (f64, f64),
+}
+
+// The parts of an object stacking looks at.
+struct Stackable {
+    is_slider: bool,
+    // Spinners and mania holds never stack.
+    ignored: bool,
+    start_time: f64,
+    end_time: f64,
+    position: (f64, f64),
+    end_position: (f64, f64),
+    path_end: (f64, f64),
+    height: i64,
+}
+
+impl Chart {
+    /// Computes the stack of every hit object, in the same order as `hit_objects`.
+    ///
+    /// `difficulty` should have mods applied, since approach rate and circle size
+    /// change the stacks. Beatmaps older than file format v6 use the original
+    /// stacking algorithm.
+    pub fn stacking(&self, difficulty: &Difficulty, file_format: u8) -> Vec<Stack> {
+        let timing = self.timing_map();
+        let slider_multiplier = ratio_to_f64(self.slider_multiplier);
+        let mut objects: Vec<Stackable> = self
+            .hit_objects
+            .iter()
+            .map(|object| {
+                let (path_end, end_time) = match object {
+                    HitObject::Slider(slider) => (
+                        slider.path().end_position(),
+                        slider.end_time(&timing, slider_multiplier),
+                    ),
+                    _ => (
+                        object.position(),
+                        object.end_time(&timing, slider_multiplier),
+                    ),
+                };
+                Stackable {
+                    is_slider: matches!(object, HitObject::Slider(_)),
+                    ignored: matches!(object, HitObject::Spinner(_) | HitObject::ManiaHold(_)),
+                    start_time: object.start_time(),
+                    end_time,
+                    position: object.position(),
+                    end_position: object.end_position(),
+                    path_end,
+                    height: 0,
+                }
+            })
+            .collect();
+
+        let threshold = difficulty.preempt() * ratio_to_f64(self.stack_leniency);
+        if file_format >= 6 {
+            apply_stacking(&mut objects, threshold);
+        } else {
+            apply_stacking_old(&mut objects, threshold);
+        }
+
+        let scale = difficulty.circle_scale();
+        objects
+            .iter()
+            .map(|object| {
+                let shift = object.height as f64 * scale * -6.4;
+                Stack {
+                    height: object.height,
+                    offset: (shift, shift),
+                    position: (object.position.0 + shift, object.position.1 + shift),
+                    end_position: (object.end_position.0 + shift, object.end_position.1 + shift),
+                }
+            })
+            .collect()
+    }
+}
+
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+}
+
+fn apply_stacking(objects: &mut [Stackable], threshold: f64) {
+    if objects.is_empty() {
+        return;
+    }
+    let count = objects.len();
+
+    // Extend the end to include the objects the last ones are stacked on.
+    let mut extended_end = count - 1;
+    for i in (0..count).rev() {
+        let mut base = i;
+        for n in base + 1..count {
+            if objects[base].ignored {
+                break;
+            }
+            if objects[n].ignored {
+                continue;
+            }
+            if objects[n].start_time - objects[base].end_time > threshold {
+                // We are no longer within stacking range of the next object.
+                break;
+            }
+            if distance(objects[base].position, objects[n].position) < STACK_DISTANCE
+                || (objects[base].is_slider
+                    && distance(objects[base].end_position, objects[n].position) < STACK_DISTANCE)
+            {
+                base = n;
+                objects[n].height = 0;
+            }
+        }
+        if base > extended_end {
+            extended_end = base;
+            if extended_end == count - 1 {
+                break;
+            }
+        }
+    }
+
+    // Reverse pass to calculate the stacks.
+    for i in (1..=extended_end).rev() {
+        // Every object without a stack yet is checked,
+        // since two stacks can be interwound with each other.
+        let mut object_i = i;
+        if objects[object_i].height != 0 || objects[object_i].ignored {
+            continue;
+        }
+        if !objects[object_i].is_slider {
+            // Either a stack of circles only, or circles underneath a slider.
+            for n in (0..i).rev() {
+                if objects[n].ignored {
+                    continue;
+                }
+                if objects[object_i].start_time - objects[n].end_time > threshold {
+                    // We are no longer within stacking range of the previous object.
+                    break;
+                }
+                // Circles under the end of the last slider in a stack are moved
+                // down and right instead, so they appear below the slider end.
+                if objects[n].is_slider
+                    && distance(objects[n].end_position, objects[object_i].position)
+                        < STACK_DISTANCE
+                {
+                    let offset = objects[object_i].height - objects[n].height + 1;
+                    for j in n + 1..=i {
+                        if distance(objects[n].end_position, objects[j].position) < STACK_DISTANCE {
+                            objects[j].height -= offset;
+                        }
+                    }
+                    // The slider is the new base, and gets handled by the outer loop.
+                    break;
+                }
+                if distance(objects[n].position, objects[object_i].position) < STACK_DISTANCE {
+                    objects[n].height = objects[object_i].height + 1;
+                    object_i = n;
+                }
+            }
+        } else {
+            // From the first slider in a stack, everything stacks upwards.
+            for n in (0..i).rev() {
+                if objects[n].ignored {
+                    continue;
+                }
+                if objects[object_i].start_time - objects[n].start_time > threshold {
+                    // We are no longer within stacking range of the previous object.
+                    break;
+                }
+                if distance(objects[n].end_position, objects[object_i].position) < STACK_DISTANCE {
+                    objects[n].height = objects[object_i].height + 1;
+                    object_i = n;
+                }
+            }
+        }
+    }
+}
+
+/// The stacking used by beatmaps older than file format v6.
+fn apply_stacking_old(objects: &mut [Stackable], threshold: f64) {
+    for i in 0..objects.len() {
+        if objects[i].height != 0 && !objects[i].is_slider {
+            continue;
+        }
+        let mut start_time = objects[i].end_time;
+        let mut slider_stack = 0;
+        for j in i + 1..objects.len() {
+            if objects[j].start_time - threshold > start_time {
+                break;
+            }
+            // Stable compares against the start time of later objects,
+            // since their end times weren't calculated yet.
+            if distance(objects[j].position, objects[i].position) < STACK_DISTANCE {
+                objects[i].height += 1;
+                start_time = objects[j].start_time;
+            } else if distance(objects[j].position, objects[i].path_end) < STACK_DISTANCE {
+                // Objects under a slider end are bumped down and right.
+                slider_stack += 1;
+                objects[j].height -= slider_stack;
+                start_time = objects[j].start_time;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utility::fixture;
+
+    fn stack_heights(file_format: u8) -> Vec<(i64, i64)> {
+        let fixture = fixture("osu.osu");
+        let stacks = fixture.chart.stacking(&fixture.difficulty, file_format);
+        assert_eq!(stacks.len(), fixture.chart.hit_objects.len());
+        fixture
+            .chart
+            .hit_objects
+            .iter()
+            .zip(stacks)
+            .filter(|(object, _)| (28333.0..=30000.0).contains(&object.start_time()))
+            .map(|(object, stack)| (object.start_time() as i64, stack.height))
+            .collect()
+    }
+
+    #[test]
+    fn circles_on_the_same_spot_stack_upwards() {
+        let expected = vec![
+            (28333, 0),
+            (29000, 3),
+            (29167, 2),
+            (29333, 1),
+            (29500, 0),
+            (30000, 0),
+        ];
+        assert_eq!(stack_heights(14), expected);
+        assert_eq!(stack_heights(5), expected);
+    }
+
+    #[test]
+    fn stacks_are_offset_by_circle_size() {
+        let fixture = fixture("osu.osu");
+        let stacks = fixture.chart.stacking(&fixture.difficulty, 14);
+        let index = fixture
+            .chart
+            .hit_objects
+            .iter()
+            .position(|object| object.start_time() == 29000.0)
+            .unwrap();
+        let stack = stacks[index];
+        let shift = 3.0 * fixture.difficulty.circle_scale() * -6.4;
+        assert_eq!(stack.offset, (shift, shift));
+        assert_eq!(stack.position, (256.0 + shift, 192.0 + shift));
+    }
+}
@@ -0,0 +1,195 @@
+use crate::chart::Chart;
+use crate::chart::Mode;
+use crate::collections::HitObject;
+use crate::collections::HitSound;
+use crate::collections::Slider;
+use crate::collections::TimingMap;
+use crate::difficulty::Difficulty;
+use crate::utility::difficulty_range;
+use crate::utility::ratio_to_f64;
+
+// osu!taiko sliders scroll this much faster than in osu!standard.
+const VELOCITY_MULTIPLIER: f64 = 1.4;
+const SWELL_HIT_MULTIPLIER: f64 = 1.65;
+
+/// An object as it's played in osu!taiko.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TaikoObject {
+    pub kind: TaikoKind,
+    pub time: f64,
+    /// The same as `time` for dons and kats.
+    pub end_time: f64,
+    /// Big notes, hit with both keys. Swells are never strong.
+    pub strong: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TaikoKind {
+    Don,
+    Kat,
+    DrumRoll,
+    Swell { required_hits: i64 },
+}
+
+impl TaikoObject {
+    pub fn is_hit(&self) -> bool {
+        matches!(self.kind, TaikoKind::Don | TaikoKind::Kat)
+    }
+
+    // Whistles and claps make a kat, finishes make it big.
+    fn hit(time: f64, hit_sound: &HitSound) -> TaikoObject {
+        TaikoObject {
+            kind: match hit_sound.whistle || hit_sound.clap {
+                true => TaikoKind::Kat,
+                false => TaikoKind::Don,
+            },
+            time,
+            end_time: time,
+            strong: hit_sound.finish,
+        }
+    }
+}
+
+impl Chart {
+    /// The chart's objects as osu!taiko plays them, sorted by time.
+    ///
+    /// This is also the osu!standard to osu!taiko converter. Charts from other modes
+    /// are converted the way stable does it, so short sliders become a run of notes
+    /// instead of a drumroll, and objects sharing a time become one strong note.
+    /// `file_format` changes how those notes are spaced.
+    ///
+    /// Native osu!taiko charts keep every object, and only finishes make them strong.
+    pub fn taiko_objects(&self, difficulty: &Difficulty, file_format: u8) -> Vec<TaikoObject> {
+        let timing = self.timing_map();
+        let mut objects = Vec::with_capacity(self.hit_objects.len());
+        for object in self.hit_objects.iter() {
+            match object {
+                HitObject::Circle(circle) => {
+                    objects.push(TaikoObject::hit(circle.time as f64, &circle.hit_sound));
+                }
+                HitObject::Slider(slider) => {
+                    self.convert_slider(slider, &timing, file_format, &mut objects);
+                }
+                HitObject::Spinner(_) | HitObject::ManiaHold(_) => {
+                    let time = object.start_time();
+                    let end_time = object.end_time(&timing, ratio_to_f64(self.slider_multiplier));
+                    let od = ratio_to_f64(difficulty.overall_difficulty);
+                    let hit_multiplier = difficulty_range(od, 3.0, 5.0, 7.5) * SWELL_HIT_MULTIPLIER;
+                    let required_hits = ((end_time - time) / 1000.0 * hit_multiplier) as i64;
+                    objects.push(TaikoObject {
+                        kind: TaikoKind::Swell {
+                            required_hits: required_hits.max(1),
+                        },
+                        time,
+                        end_time,
+                        strong: false,
+                    });
+                }
+            }
+        }
+        objects.sort_by(|a, b| a.time.total_cmp(&b.time));
+        if self.mode == Mode::Taiko {
+            return objects;
+        }
+        // Converted objects sharing a time are merged into one strong object.
+        let mut merged: Vec<TaikoObject> = Vec::with_capacity(objects.len());
+        for object in objects {
+            match merged.last_mut() {
+                Some(last) if last.time == object.time => {
+                    if !matches!(last.kind, TaikoKind::Swell { .. }) {
+                        last.strong = true;
+                    }
+                }
+                _ => merged.push(object),
+            }
+        }
+        merged
+    }
+
+    // Some floating point steps here look redundant,
+    // but they keep the conversion identical to stable's.
+    fn convert_slider(
+        &self,
+        slider: &Slider,
+        timing: &TimingMap,
+        file_format: u8,
+        objects: &mut Vec<TaikoObject>,
+    ) {
+        let time = slider.time as f64;
+        let state = timing.at(time);
+        let spans = slider.slides.max(1);
+        let distance = slider.path().distance() * spans as f64 * VELOCITY_MULTIPLIER;
+        let mut beat_length = state.beat_length * state.bpm_multiplier;
+
+        let slider_multiplier = ratio_to_f64(self.slider_multiplier);
+        let tick_rate = ratio_to_f64(self.slider_tick_rate);
+        let scoring_distance = 100.0 * slider_multiplier / tick_rate;
+        let taiko_velocity = scoring_distance * tick_rate;
+        let taiko_duration = (distance / taiko_velocity * beat_length) as i64 as f64;
+
+        if self.mode != Mode::Taiko {
+            let osu_velocity = taiko_velocity * (1000.0 / beat_length);
+            // Stable only spaces the notes by slider velocity in old beatmaps.
+            if file_format >= 8 {
+                beat_length = state.beat_length;
+            }
+            let tick_spacing = (beat_length / tick_rate).min(taiko_duration / spans as f64);
+            if tick_spacing > 0.0 && distance / osu_velocity * 1000.0 < 2.0 * beat_length {
+                // Each note takes the sound of the next slider edge, wrapping around.
+                let edge_count = spans as usize + 1;
+                let mut edge = 0;
+                let mut note_time = time;
+                while note_time <= time + taiko_duration + tick_spacing / 8.0 {
+                    let hit_sound = slider.edge_sounds.get(edge).unwrap_or(&slider.hit_sound);
+                    objects.push(TaikoObject::hit(note_time, hit_sound));
+                    edge = (edge + 1) % edge_count;
+                    note_time += tick_spacing;
+                }
+                return;
+            }
+        }
+        objects.push(TaikoObject {
+            kind: TaikoKind::DrumRoll,
+            time,
+            end_time: time + taiko_duration,
+            strong: slider.hit_sound.finish,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::fixture;
+
+    fn simultaneous(objects: &[TaikoObject]) -> usize {
+        objects
+            .windows(2)
+            .filter(|w| w[0].time == w[1].time)
+            .count()
+    }
+
+    #[test]
+    fn native_charts_keep_simultaneous_objects() {
+        let fixture = fixture("taiko.osu");
+        let objects = fixture.chart.taiko_objects(&fixture.difficulty, 14);
+        assert_eq!(simultaneous(&objects), 4);
+        let finishes = fixture
+            .chart
+            .hit_objects
+            .iter()
+            .filter(|object| matches!(object, HitObject::Circle(_)) && object.hit_sound().finish)
+            .count();
+        let strong = objects.iter().filter(|o| o.is_hit() && o.strong).count();
+        assert_eq!(strong, finishes);
+    }
+
+    #[test]
+    fn converted_charts_merge_simultaneous_objects() {
+        let fixture = fixture("taiko.osu");
+        let mut chart = fixture.chart.clone();
+        chart.mode = Mode::Osu;
+        let objects = chart.taiko_objects(&fixture.difficulty, 14);
+        assert_eq!(simultaneous(&objects), 0);
+    }
+}
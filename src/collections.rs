@@ -1,5 +1,11 @@
 pub mod hit_object;
+pub mod nested_object;
+pub mod slider_path;
+pub mod timing_map;
 pub mod timing_point;
 
 pub use hit_object::*;
+pub use nested_object::*;
+pub use slider_path::*;
+pub use timing_map::*;
 pub use timing_point::*;
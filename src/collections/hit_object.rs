@@ -1,3 +1,4 @@
+use crate::collections::timing_map::TimingMap;
 pub use crate::parts::Curve;
 pub use crate::parts::CurveType;
 pub use crate::parts::HitSample;
@@ -60,3 +61,163 @@ pub struct ManiaHold {
     pub end_time: i64,
     pub hit_sample: HitSample,
 }
+
+impl HitObject {
+    /// Times are in milliseconds as `f64`, to match [`HitObject::end_time`].
+    pub fn start_time(&self) -> f64 {
+        let time = match self {
+            HitObject::Circle(circle) => circle.time,
+            HitObject::Slider(slider) => slider.time,
+            HitObject::Spinner(spinner) => spinner.time,
+            HitObject::ManiaHold(hold) => hold.time,
+        };
+        time as f64
+    }
+
+    /// Sliders don't store their end time in the .osu file,
+    /// so the timing and slider multiplier are needed to find their duration.
+    pub fn end_time(&self, timing: &TimingMap, slider_multiplier: f64) -> f64 {
+        match self {
+            HitObject::Circle(circle) => circle.time as f64,
+            HitObject::Slider(slider) => slider.end_time(timing, slider_multiplier),
+            HitObject::Spinner(spinner) => spinner.end_time as f64,
+            HitObject::ManiaHold(hold) => hold.end_time as f64,
+        }
+    }
+
+    pub fn position(&self) -> (f64, f64) {
+        let (x, y) = match self {
+            HitObject::Circle(circle) => (circle.x, circle.y),
+            HitObject::Slider(slider) => (slider.x, slider.y),
+            HitObject::Spinner(spinner) => (spinner.x, spinner.y),
+            HitObject::ManiaHold(hold) => (hold.x, hold.y),
+        };
+        (x as f64, y as f64)
+    }
+
+    /// Sliders with an odd number of slides end at the far end of their path.
+    /// A slide count of 0 is played as a single slide.
+    pub fn end_position(&self) -> (f64, f64) {
+        match self {
+            HitObject::Slider(slider) if slider.slides.max(1) % 2 == 1 => {
+                slider.path().end_position()
+            }
+            _ => self.position(),
+        }
+    }
+
+    pub fn new_combo(&self) -> bool {
+        self.flags().new_combo
+    }
+
+    pub fn flags(&self) -> Type {
+        match self {
+            HitObject::Circle(circle) => circle.flags,
+            HitObject::Slider(slider) => slider.flags,
+            HitObject::Spinner(spinner) => spinner.flags,
+            HitObject::ManiaHold(hold) => hold.flags,
+        }
+    }
+
+    pub fn hit_sound(&self) -> &HitSound {
+        match self {
+            HitObject::Circle(circle) => &circle.hit_sound,
+            HitObject::Slider(slider) => &slider.hit_sound,
+            HitObject::Spinner(spinner) => &spinner.hit_sound,
+            HitObject::ManiaHold(hold) => &hold.hit_sound,
+        }
+    }
+
+    pub fn hit_sample(&self) -> &HitSample {
+        match self {
+            HitObject::Circle(circle) => &circle.hit_sample,
+            HitObject::Slider(slider) => &slider.hit_sample,
+            HitObject::Spinner(spinner) => &spinner.hit_sample,
+            HitObject::ManiaHold(hold) => &hold.hit_sample,
+        }
+    }
+}
+
+/// Written as a line of the [HitObjects] section.
+impl std::fmt::Display for HitObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let (x, y) = self.position();
+        write!(
+            f,
+            "{},{},{},{},{},",
+            x,
+            y,
+            self.start_time() as i64,
+            self.flags(),
+            self.hit_sound()
+        )?;
+        match self {
+            HitObject::Circle(circle) => write!(f, "{}", circle.hit_sample),
+            HitObject::Slider(slider) => {
+                write!(f, "{},{},{}", slider.curve, slider.slides, slider.length)?;
+                // Edge sounds and sets are optional, but come before the hit sample.
+                if slider.edge_sounds.is_empty()
+                    && slider.edge_sets.is_empty()
+                    && slider.hit_sample == HitSample::default()
+                {
+                    return Ok(());
+                }
+                let edges = slider.slides as usize + 1;
+                let sounds = match slider.edge_sounds.is_empty() {
+                    true => vec!["0".to_string(); edges],
+                    false => slider.edge_sounds.iter().map(|s| s.to_string()).collect(),
+                };
+                let sets = match slider.edge_sets.is_empty() {
+                    true => vec!["0:0".to_string(); edges],
+                    false => slider
+                        .edge_sets
+                        .iter()
+                        .map(|(normal, addition)| {
+                            format!("{}:{}", normal.to_u8(), addition.to_u8())
+                        })
+                        .collect(),
+                };
+                write!(
+                    f,
+                    ",{},{},{}",
+                    sounds.join("|"),
+                    sets.join("|"),
+                    slider.hit_sample
+                )
+            }
+            HitObject::Spinner(spinner) => {
+                write!(f, "{},{}", spinner.end_time, spinner.hit_sample)
+            }
+            HitObject::ManiaHold(hold) => write!(f, "{}:{}", hold.end_time, hold.hit_sample),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utility::{beatmap, DIFFICULTY};
+
+    #[test]
+    fn sliders_end_where_their_last_span_does() {
+        let fixture = beatmap(
+            0,
+            DIFFICULTY,
+            "0,500,4,2,0,100,1,0",
+            "0,0,0,2,0,L|100:0,0,100\n\
+             0,0,1000,2,0,L|100:0,1,100\n\
+             0,0,2000,2,0,L|100:0,2,100",
+        );
+        let timing = fixture.chart.timing_map();
+        let ends: Vec<_> = fixture
+            .chart
+            .hit_objects
+            .iter()
+            .map(|object| (object.end_position(), object.end_time(&timing, 1.4)))
+            .collect();
+        assert_eq!(ends[0].0, (100.0, 0.0));
+        assert_eq!(ends[1].0, (100.0, 0.0));
+        assert_eq!(ends[2].0, (0.0, 0.0));
+        // The zero slide count also lasts a single span.
+        assert_eq!(ends[0].1 - 0.0, ends[1].1 - 1000.0);
+    }
+}
@@ -0,0 +1,249 @@
+use crate::collections::hit_object::Slider;
+use crate::collections::timing_map::TimingMap;
+use crate::parts::HitSound;
+use crate::parts::SampleSet;
+
+/// Stable judges the end of a slider this many milliseconds before its tail.
+pub const LEGACY_LAST_TICK_OFFSET: f64 = 36.0;
+
+// Sliders longer than this don't get any more ticks.
+const MAX_TICK_LENGTH: f64 = 100000.0;
+
+/// An object generated along a slider's path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NestedObject {
+    pub kind: NestedKind,
+    pub time: f64,
+    pub position: (f64, f64),
+    /// Which slide of the slider this object belongs to, starting at 0.
+    pub span_index: i64,
+    /// How far along the path the object is, from 0 at the head to 1 at the end.
+    pub path_progress: f64,
+    /// Heads, repeats and tails play the slider's edge sounds. Ticks have none.
+    pub edge_sound: Option<HitSound>,
+    pub edge_set: Option<(SampleSet, SampleSet)>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum NestedKind {
+    Head,
+    Tick,
+    Repeat,
+    /// Where stable actually checks the end of the slider, see [`LEGACY_LAST_TICK_OFFSET`].
+    LegacyLastTick,
+    Tail,
+}
+
+impl Slider {
+    // The slider methods take a prebuilt `TimingMap`, see [`Chart::timing_map`],
+    // so whole-chart operations don't rebuild it for every slider.
+
+    /// Distance travelled in osu!pixels per millisecond.
+    pub fn velocity(&self, timing: &TimingMap, slider_multiplier: f64) -> f64 {
+        let time = self.time as f64;
+        self.scoring_distance(timing, slider_multiplier) / timing.beat_length_at(time)
+    }
+
+    /// Distance in osu!pixels between slider ticks.
+    ///
+    /// Beatmaps older than file format v8 space the ticks as if the slider velocity was 1.
+    pub fn tick_distance(
+        &self,
+        timing: &TimingMap,
+        slider_multiplier: f64,
+        tick_rate: f64,
+        file_format: u8,
+    ) -> f64 {
+        let tick_distance = self.scoring_distance(timing, slider_multiplier) / tick_rate;
+        match file_format < 8 {
+            true => tick_distance / timing.slider_velocity_at(self.time as f64),
+            false => tick_distance,
+        }
+    }
+
+    /// Time in milliseconds to travel the path once.
+    pub fn span_duration(&self, timing: &TimingMap, slider_multiplier: f64) -> f64 {
+        self.path().distance() / self.velocity(timing, slider_multiplier)
+    }
+
+    /// Time in milliseconds from the head to the tail, over all slides.
+    /// A slider always travels its path at least once.
+    pub fn duration(&self, timing: &TimingMap, slider_multiplier: f64) -> f64 {
+        self.slides.max(1) as f64 * self.span_duration(timing, slider_multiplier)
+    }
+
+    pub fn end_time(&self, timing: &TimingMap, slider_multiplier: f64) -> f64 {
+        self.time as f64 + self.duration(timing, slider_multiplier)
+    }
+
+    /// How far along the path the ball is at `time`, from 0 at the head to 1 at the end.
+    ///
+    /// `span_duration` is passed in since it's costly to compute,
+    /// see [`Slider::span_duration`].
+    pub fn progress_at(&self, time: f64, span_duration: f64) -> f64 {
+        let span_count = self.slides.max(1);
+        let spans = match span_duration > 0.0 {
+            true => ((time - self.time as f64) / span_duration).clamp(0.0, span_count as f64),
+            false => 0.0,
+        };
+        let span = spans.floor().min((span_count - 1) as f64);
+        match span as i64 % 2 {
+            0 => spans - span,
+            _ => 1.0 - (spans - span),
+        }
+    }
+
+    /// Generates the head, ticks, repeats and tail of the slider in time order.
+    ///
+    /// `file_format` decides how the ticks are spaced, see [`Slider::tick_distance`].
+    pub fn nested_objects(
+        &self,
+        timing: &TimingMap,
+        slider_multiplier: f64,
+        tick_rate: f64,
+        file_format: u8,
+    ) -> Vec<NestedObject> {
+        let path = self.path();
+        let start_time = self.time as f64;
+        let velocity = self.velocity(timing, slider_multiplier);
+        let span_count = self.slides.max(1);
+        let span_duration = path.distance() / velocity;
+        let length = path.distance().min(MAX_TICK_LENGTH);
+        let tick_distance = self
+            .tick_distance(timing, slider_multiplier, tick_rate, file_format)
+            .clamp(0.0, length);
+        // Ticks too close to the end of a span are skipped.
+        let min_distance_from_end = velocity * 10.0;
+
+        let nested = |kind, time, span_index, path_progress| {
+            let (edge_sound, edge_set) = match kind {
+                NestedKind::Head => self.edge(0),
+                NestedKind::Repeat => self.edge(span_index as usize + 1),
+                NestedKind::Tail => self.edge(span_count as usize),
+                _ => (None, None),
+            };
+            NestedObject {
+                kind,
+                time,
+                position: path.position_at(path_progress),
+                span_index,
+                path_progress,
+                edge_sound,
+                edge_set,
+            }
+        };
+
+        let mut objects = vec![nested(NestedKind::Head, start_time, 0, 0.0)];
+        if tick_distance != 0.0 {
+            for span in 0..span_count {
+                let span_start_time = start_time + span as f64 * span_duration;
+                let reversed = span % 2 == 1;
+                let mut ticks = Vec::new();
+                let mut distance = tick_distance;
+                while distance <= length {
+                    if distance >= length - min_distance_from_end {
+                        break;
+                    }
+                    // Ticks are always measured from the start of the path,
+                    // so ticks on reversed spans line up with the others.
+                    let path_progress = distance / length;
+                    let time_progress = if reversed {
+                        1.0 - path_progress
+                    } else {
+                        path_progress
+                    };
+                    let time = span_start_time + time_progress * span_duration;
+                    ticks.push(nested(NestedKind::Tick, time, span, path_progress));
+                    distance += tick_distance;
+                }
+                if reversed {
+                    ticks.reverse();
+                }
+                objects.append(&mut ticks);
+                if span < span_count - 1 {
+                    let time = span_start_time + span_duration;
+                    let path_progress = ((span + 1) % 2) as f64;
+                    objects.push(nested(NestedKind::Repeat, time, span, path_progress));
+                }
+            }
+        }
+
+        let total_duration = span_count as f64 * span_duration;
+        let final_span = span_count - 1;
+        let final_span_start_time = start_time + final_span as f64 * span_duration;
+        let legacy_last_tick_time = (start_time + total_duration / 2.0)
+            .max(final_span_start_time + span_duration - LEGACY_LAST_TICK_OFFSET);
+        let mut legacy_progress = match span_duration > 0.0 {
+            true => (legacy_last_tick_time - final_span_start_time) / span_duration,
+            false => 1.0,
+        };
+        if span_count % 2 == 0 {
+            legacy_progress = 1.0 - legacy_progress;
+        }
+        objects.push(nested(
+            NestedKind::LegacyLastTick,
+            legacy_last_tick_time,
+            final_span,
+            legacy_progress,
+        ));
+        objects.push(nested(
+            NestedKind::Tail,
+            start_time + total_duration,
+            final_span,
+            (span_count % 2) as f64,
+        ));
+        // Ticks close to the tail can come after the legacy last tick.
+        objects.sort_by(|a, b| a.time.total_cmp(&b.time));
+        objects
+    }
+
+    fn scoring_distance(&self, timing: &TimingMap, slider_multiplier: f64) -> f64 {
+        let time = self.time as f64;
+        100.0 * slider_multiplier * timing.slider_velocity_at(time)
+    }
+
+    /// The sounds of the `index`th edge, falling back to the slider's own sounds.
+    fn edge(&self, index: usize) -> (Option<HitSound>, Option<(SampleSet, SampleSet)>) {
+        let sound = match self.edge_sounds.get(index) {
+            Some(sound) => sound.clone(),
+            None => self.hit_sound.clone(),
+        };
+        let set = match self.edge_sets.get(index) {
+            Some(&set) => set,
+            None => (self.hit_sample.normal_set, self.hit_sample.addition_set),
+        };
+        (Some(sound), Some(set))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections::HitObject;
+    use crate::utility::{beatmap, DIFFICULTY};
+
+    #[test]
+    fn old_beatmaps_space_ticks_without_slider_velocity() {
+        let fixture = beatmap(
+            0,
+            DIFFICULTY,
+            "0,500,4,2,0,100,1,0\n0,-50,4,2,0,100,0,0",
+            "0,0,0,2,0,L|560:0,1,560",
+        );
+        let timing = fixture.chart.timing_map();
+        let HitObject::Slider(slider) = &fixture.chart.hit_objects[0] else {
+            panic!("not a slider");
+        };
+        let ticks = |file_format| {
+            slider
+                .nested_objects(&timing, 1.4, 1.0, file_format)
+                .iter()
+                .filter(|nested| nested.kind == NestedKind::Tick)
+                .count()
+        };
+        assert_eq!(slider.tick_distance(&timing, 1.4, 1.0, 14), 280.0);
+        assert_eq!(slider.tick_distance(&timing, 1.4, 1.0, 7), 140.0);
+        assert_eq!(ticks(14), 1);
+        assert_eq!(ticks(7), 3);
+    }
+}
@@ -0,0 +1,459 @@
+use crate::collections::hit_object::Slider;
+use crate::parts::Curve;
+use crate::parts::CurveType;
+
+// Tolerances used by osu! when flattening curves into line segments.
+const BEZIER_TOLERANCE: f64 = 0.25;
+const CATMULL_DETAIL: usize = 50;
+const CIRCULAR_ARC_TOLERANCE: f64 = 0.1;
+
+/// The evaluated path of a slider, flattened into line segments.
+///
+/// Positions are absolute playfield coordinates.
+/// The path has already been truncated or extended to the slider's length.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SliderPath {
+    points: Vec<(f64, f64)>,
+    cumulative_length: Vec<f64>,
+}
+
+impl SliderPath {
+    /// Flattens the curve starting at `start` and fits it to `expected_length`.
+    ///
+    /// A non-positive expected length leaves the path at its calculated length,
+    /// the same as a slider without a length in the .osu file.
+    pub fn new(start: (f64, f64), curve: &Curve, expected_length: f64) -> Self {
+        let mut vertices = Vec::with_capacity(curve.points.len() + 1);
+        vertices.push(start);
+        vertices.extend(curve.points.iter().map(|&(x, y)| (x as f64, y as f64)));
+
+        let mut curve_type = curve._type;
+        if curve_type == CurveType::Perfect {
+            if vertices.len() != 3 {
+                curve_type = CurveType::Bezier;
+            } else if is_linear(vertices[0], vertices[1], vertices[2]) {
+                // Stable treats colinear perfect curves as linear paths.
+                curve_type = CurveType::Linear;
+            }
+        }
+
+        let mut points: Vec<(f64, f64)> = Vec::new();
+        for segment in segments(&vertices, curve_type) {
+            let sub_path = match segment.len() {
+                1 => segment.to_vec(),
+                _ => sub_path(segment, curve_type),
+            };
+            // Segments share their boundary vertex, which only needs to appear once.
+            let skip_first =
+                matches!((points.last(), sub_path.first()), (Some(a), Some(b)) if a == b);
+            points.extend(sub_path.into_iter().skip(skip_first as usize));
+        }
+
+        let mut path = Self {
+            points,
+            cumulative_length: Vec::new(),
+        };
+        path.fit_length(expected_length);
+        path
+    }
+
+    /// The sampled points along the path.
+    pub fn points(&self) -> &[(f64, f64)] {
+        &self.points
+    }
+
+    /// The length of the path in osu!pixels.
+    pub fn distance(&self) -> f64 {
+        self.cumulative_length.last().copied().unwrap_or(0.0)
+    }
+
+    /// The position at `progress` along the path, from 0 at the head to 1 at the end.
+    pub fn position_at(&self, progress: f64) -> (f64, f64) {
+        let distance = progress.clamp(0.0, 1.0) * self.distance();
+        let index = self
+            .cumulative_length
+            .partition_point(|&length| length < distance);
+        self.interpolate(index, distance)
+    }
+
+    pub fn start_position(&self) -> (f64, f64) {
+        self.position_at(0.0)
+    }
+
+    pub fn end_position(&self) -> (f64, f64) {
+        self.position_at(1.0)
+    }
+
+    fn interpolate(&self, index: usize, distance: f64) -> (f64, f64) {
+        if self.points.is_empty() {
+            return (0.0, 0.0);
+        }
+        if index == 0 {
+            return self.points[0];
+        }
+        if index >= self.points.len() {
+            return self.points[self.points.len() - 1];
+        }
+        let (p0, p1) = (self.points[index - 1], self.points[index]);
+        let (d0, d1) = (
+            self.cumulative_length[index - 1],
+            self.cumulative_length[index],
+        );
+        // Avoid dividing by almost zero when two points are extremely close.
+        if (d1 - d0).abs() < 1e-7 {
+            return p0;
+        }
+        let weight = (distance - d0) / (d1 - d0);
+        add(p0, scale(sub(p1, p0), weight))
+    }
+
+    /// Truncates or extends the last segment so the path is exactly `expected` long.
+    fn fit_length(&mut self, expected: f64) {
+        let mut calculated = 0.0;
+        self.cumulative_length = Vec::with_capacity(self.points.len());
+        self.cumulative_length.push(0.0);
+        for pair in self.points.windows(2) {
+            calculated += length(sub(pair[1], pair[0]));
+            self.cumulative_length.push(calculated);
+        }
+        if expected <= 0.0 || calculated == expected {
+            return;
+        }
+        let count = self.points.len();
+        // Stable doesn't extend paths whose last two points are equal.
+        if count >= 2 && self.points[count - 1] == self.points[count - 2] && expected > calculated {
+            self.cumulative_length.push(calculated);
+            return;
+        }
+        // The last length is always incorrect.
+        self.cumulative_length.pop();
+        let mut end_index = count as isize - 1;
+        if calculated > expected {
+            // Drop every segment that lies entirely beyond the expected length.
+            while matches!(self.cumulative_length.last(), Some(&length) if length >= expected) {
+                self.cumulative_length.pop();
+                self.points.remove(end_index as usize);
+                end_index -= 1;
+            }
+        }
+        if end_index <= 0 {
+            self.cumulative_length.push(0.0);
+            return;
+        }
+        let end_index = end_index as usize;
+        // Shorten or lengthen the final segment along its own direction.
+        let direction = normalize(sub(self.points[end_index], self.points[end_index - 1]));
+        let remaining = expected - self.cumulative_length.last().copied().unwrap_or(0.0);
+        self.points[end_index] = add(self.points[end_index - 1], scale(direction, remaining));
+        self.cumulative_length.push(expected);
+    }
+}
+
+impl Slider {
+    /// Evaluates the slider's curve from its head.
+    pub fn path(&self) -> SliderPath {
+        SliderPath::new((self.x as f64, self.y as f64), &self.curve, self.length)
+    }
+}
+
+/// Splits the vertices into segments at repeated points, known as red anchors.
+///
+/// Neighbouring segments share their boundary vertex.
+fn segments(vertices: &[(f64, f64)], curve_type: CurveType) -> Vec<&[(f64, f64)]> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    // The last point can't start a new segment.
+    for end in 1..vertices.len().saturating_sub(1) {
+        if vertices[end] != vertices[end - 1] {
+            continue;
+        }
+        // Legacy catmull sliders only have one segment,
+        // but a first control point repeating the head is still dropped.
+        if curve_type == CurveType::Centripetal && end > 1 {
+            continue;
+        }
+        segments.push(&vertices[start..end]);
+        start = end;
+    }
+    segments.push(&vertices[start..]);
+    segments
+}
+
+fn sub_path(vertices: &[(f64, f64)], curve_type: CurveType) -> Vec<(f64, f64)> {
+    match curve_type {
+        CurveType::Linear => vertices.to_vec(),
+        CurveType::Centripetal => catmull(vertices),
+        CurveType::Perfect if vertices.len() == 3 => match circular_arc(vertices) {
+            Some(path) => path,
+            // Fall back to a numerically stable bezier for degenerate circles.
+            None => bezier(vertices),
+        },
+        _ => bezier(vertices),
+    }
+}
+
+fn bezier_is_flat_enough(points: &[(f64, f64)]) -> bool {
+    points.windows(3).all(|w| {
+        let deviation = add(sub(w[0], scale(w[1], 2.0)), w[2]);
+        length_squared(deviation) <= BEZIER_TOLERANCE * BEZIER_TOLERANCE * 4.0
+    })
+}
+
+/// Splits a bezier curve into two halves with de Casteljau's algorithm.
+fn bezier_subdivide(points: &[(f64, f64)], left: &mut [(f64, f64)], right: &mut [(f64, f64)]) {
+    let count = points.len();
+    let mut midpoints = points.to_vec();
+    for i in 0..count {
+        left[i] = midpoints[0];
+        right[count - i - 1] = midpoints[count - i - 1];
+        for j in 0..count - i - 1 {
+            midpoints[j] = scale(add(midpoints[j], midpoints[j + 1]), 0.5);
+        }
+    }
+}
+
+fn bezier_approximate(points: &[(f64, f64)], output: &mut Vec<(f64, f64)>) {
+    let count = points.len();
+    let mut left = vec![(0.0, 0.0); count];
+    let mut right = vec![(0.0, 0.0); count];
+    bezier_subdivide(points, &mut left, &mut right);
+    left.extend_from_slice(&right[1..]);
+    output.push(points[0]);
+    for i in 1..count - 1 {
+        let index = 2 * i;
+        let point = scale(
+            add(
+                add(left[index - 1], scale(left[index], 2.0)),
+                left[index + 1],
+            ),
+            0.25,
+        );
+        output.push(point);
+    }
+}
+
+fn bezier(points: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let mut output = Vec::new();
+    if points.is_empty() {
+        return output;
+    }
+    let mut to_flatten = vec![points.to_vec()];
+    while let Some(parent) = to_flatten.pop() {
+        if bezier_is_flat_enough(&parent) {
+            bezier_approximate(&parent, &mut output);
+            continue;
+        }
+        let mut left = vec![(0.0, 0.0); parent.len()];
+        let mut right = vec![(0.0, 0.0); parent.len()];
+        bezier_subdivide(&parent, &mut left, &mut right);
+        to_flatten.push(right);
+        to_flatten.push(left);
+    }
+    output.push(points[points.len() - 1]);
+    output
+}
+
+/// osu! calls these centripetal, but the curve it draws is a uniform Catmull-Rom spline.
+fn catmull(points: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let count = points.len();
+    let mut output = Vec::with_capacity((count - 1) * CATMULL_DETAIL * 2);
+    for i in 0..count - 1 {
+        let v1 = if i > 0 { points[i - 1] } else { points[i] };
+        let v2 = points[i];
+        let v3 = if i < count - 1 {
+            points[i + 1]
+        } else {
+            sub(scale(v2, 2.0), v1)
+        };
+        let v4 = if i < count - 2 {
+            points[i + 2]
+        } else {
+            sub(scale(v3, 2.0), v2)
+        };
+        for c in 0..CATMULL_DETAIL {
+            output.push(catmull_point(
+                v1,
+                v2,
+                v3,
+                v4,
+                c as f64 / CATMULL_DETAIL as f64,
+            ));
+            output.push(catmull_point(
+                v1,
+                v2,
+                v3,
+                v4,
+                (c + 1) as f64 / CATMULL_DETAIL as f64,
+            ));
+        }
+    }
+    output
+}
+
+fn catmull_point(
+    v1: (f64, f64),
+    v2: (f64, f64),
+    v3: (f64, f64),
+    v4: (f64, f64),
+    t: f64,
+) -> (f64, f64) {
+    let t2 = t * t;
+    let t3 = t * t2;
+    let component = |a: f64, b: f64, c: f64, d: f64| {
+        0.5 * (2.0 * b
+            + (-a + c) * t
+            + (2.0 * a - 5.0 * b + 4.0 * c - d) * t2
+            + (-a + 3.0 * b - 3.0 * c + d) * t3)
+    };
+    (
+        component(v1.0, v2.0, v3.0, v4.0),
+        component(v1.1, v2.1, v3.1, v4.1),
+    )
+}
+
+/// Returns `None` when the three points don't describe a usable circle.
+fn circular_arc(points: &[(f64, f64)]) -> Option<Vec<(f64, f64)>> {
+    let (a, b, c) = (points[0], points[1], points[2]);
+    if is_linear(a, b, c) {
+        return None;
+    }
+    let d = 2.0 * (a.0 * (b.1 - c.1) + b.0 * (c.1 - a.1) + c.0 * (a.1 - b.1));
+    let (a_sq, b_sq, c_sq) = (length_squared(a), length_squared(b), length_squared(c));
+    let centre = (
+        (a_sq * (b.1 - c.1) + b_sq * (c.1 - a.1) + c_sq * (a.1 - b.1)) / d,
+        (a_sq * (c.0 - b.0) + b_sq * (a.0 - c.0) + c_sq * (b.0 - a.0)) / d,
+    );
+    let d_a = sub(a, centre);
+    let d_c = sub(c, centre);
+    let radius = length(d_a);
+    let theta_start = d_a.1.atan2(d_a.0);
+    let mut theta_end = d_c.1.atan2(d_c.0);
+    while theta_end < theta_start {
+        theta_end += 2.0 * std::f64::consts::PI;
+    }
+    let mut direction = 1.0;
+    let mut theta_range = theta_end - theta_start;
+    // Draw the arc on whichever side of AC that B lies.
+    let ortho_a_to_c = (c.1 - a.1, -(c.0 - a.0));
+    if dot(ortho_a_to_c, sub(b, a)) < 0.0 {
+        direction = -direction;
+        theta_range = 2.0 * std::f64::consts::PI - theta_range;
+    }
+    let amount_points = if 2.0 * radius <= CIRCULAR_ARC_TOLERANCE {
+        2
+    } else {
+        let step = 2.0 * (1.0 - CIRCULAR_ARC_TOLERANCE / radius).acos();
+        ((theta_range / step).ceil() as usize).max(2)
+    };
+    let mut output = Vec::with_capacity(amount_points);
+    for i in 0..amount_points {
+        let fraction = i as f64 / (amount_points - 1) as f64;
+        let theta = theta_start + direction * fraction * theta_range;
+        output.push(add(centre, (theta.cos() * radius, theta.sin() * radius)));
+    }
+    Some(output)
+}
+
+fn is_linear(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> bool {
+    ((b.1 - a.1) * (c.0 - a.0) - (b.0 - a.0) * (c.1 - a.1)).abs() < 1e-3
+}
+
+fn add(a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    (a.0 + b.0, a.1 + b.1)
+}
+
+fn sub(a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+fn scale(a: (f64, f64), scalar: f64) -> (f64, f64) {
+    (a.0 * scalar, a.1 * scalar)
+}
+
+fn dot(a: (f64, f64), b: (f64, f64)) -> f64 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+fn length_squared(a: (f64, f64)) -> f64 {
+    dot(a, a)
+}
+
+fn length(a: (f64, f64)) -> f64 {
+    length_squared(a).sqrt()
+}
+
+fn normalize(a: (f64, f64)) -> (f64, f64) {
+    let length = length(a);
+    if length == 0.0 {
+        return a;
+    }
+    scale(a, 1.0 / length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(curve_type: CurveType, points: &[(i64, i64)], expected_length: f64) -> SliderPath {
+        let curve = Curve {
+            _type: curve_type,
+            points: points.to_vec(),
+        };
+        SliderPath::new((0.0, 0.0), &curve, expected_length)
+    }
+
+    fn assert_near(actual: (f64, f64), expected: (f64, f64), tolerance: f64) {
+        let distance = length(sub(actual, expected));
+        assert!(distance <= tolerance, "{actual:?} != {expected:?}");
+    }
+
+    #[test]
+    fn linear_paths_are_fit_to_their_length() {
+        let full = path(CurveType::Linear, &[(100, 0)], 0.0);
+        assert_eq!(full.distance(), 100.0);
+        let truncated = path(CurveType::Linear, &[(100, 0), (100, 100)], 50.0);
+        assert_eq!(truncated.distance(), 50.0);
+        assert_near(truncated.end_position(), (50.0, 0.0), 1e-9);
+        let extended = path(CurveType::Linear, &[(100, 0)], 150.0);
+        assert_near(extended.end_position(), (150.0, 0.0), 1e-9);
+    }
+
+    #[test]
+    fn perfect_curves_follow_their_circle() {
+        let arc = path(CurveType::Perfect, &[(100, 100), (200, 0)], 0.0);
+        assert!((arc.distance() - 100.0 * std::f64::consts::PI).abs() < 0.5);
+        assert_near(arc.position_at(0.5), (100.0, 100.0), 0.5);
+        for &point in arc.points() {
+            assert!((length(sub(point, (100.0, 0.0))) - 100.0).abs() < 0.5);
+        }
+        // Stable treats three points in a line as a linear path.
+        let line = path(CurveType::Perfect, &[(50, 0), (100, 0)], 0.0);
+        assert_eq!(line.points(), &[(0.0, 0.0), (50.0, 0.0), (100.0, 0.0)]);
+    }
+
+    #[test]
+    fn bezier_curves_split_at_red_anchors() {
+        let curve = path(CurveType::Bezier, &[(100, 100), (200, 0)], 0.0);
+        assert!((curve.distance() - 229.5587).abs() < 0.5);
+        assert_near(curve.position_at(0.5), (100.0, 50.0), 0.5);
+        assert_near(curve.end_position(), (200.0, 0.0), 1e-9);
+
+        let corner = path(CurveType::Bezier, &[(100, 0), (100, 0), (100, 100)], 0.0);
+        assert_eq!(corner.distance(), 200.0);
+        assert_near(corner.position_at(0.5), (100.0, 0.0), 1e-9);
+    }
+
+    #[test]
+    fn catmull_curves_pass_through_their_points() {
+        let curve = path(CurveType::Centripetal, &[(100, 50), (200, 0)], 0.0);
+        for target in [(100.0, 50.0), (200.0, 0.0)] {
+            let closest = curve
+                .points()
+                .iter()
+                .map(|&point| length(sub(point, target)))
+                .fold(f64::INFINITY, f64::min);
+            assert!(closest < 1e-9);
+        }
+        assert_near(curve.end_position(), (200.0, 0.0), 1e-9);
+    }
+}
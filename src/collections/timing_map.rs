@@ -0,0 +1,252 @@
+use crate::collections::timing_point::TimingPoint;
+use crate::parts::SampleSet;
+
+/// Everything the timing points control at a given time.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TimingState {
+    /// The uninherited timing point controlling the beat.
+    pub red_line: TimingPoint,
+    /// Milliseconds per beat, clamped to the range osu! accepts.
+    pub beat_length: f64,
+    pub bpm: f64,
+    pub meter: i64,
+    pub slider_velocity: f64,
+    /// How much the green line stretches the beat, as stable calculates it.
+    /// Close to the inverse of `slider_velocity`, but with a wider range.
+    pub bpm_multiplier: f64,
+    /// `SampleSet::Default` means the beatmap's own sample set from [General].
+    pub sample_set: SampleSet,
+    pub sample_index: i64,
+    pub volume: i64,
+    pub kiai: bool,
+}
+
+/// Timing points resolved into the state they produce,
+/// so the state at any time can be looked up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimingMap {
+    // One state per distinct timing point timestamp, in time order.
+    states: Vec<(i64, TimingState)>,
+    // Used before the first timing point.
+    initial: TimingState,
+}
+
+impl TimingMap {
+    pub fn new(timing_points: &[TimingPoint]) -> Self {
+        let mut points = timing_points.to_vec();
+        points.sort_by_key(|point| point.time);
+
+        let first_red = match points.iter().find(|point| point.uninherited) {
+            Some(point) => *point,
+            None => TimingPoint {
+                beat_length: 1000.0,
+                ..Default::default()
+            },
+        };
+        let mut initial = TimingState::from_red_line(first_red);
+        // Effects only start with the first timing point.
+        initial.kiai = false;
+
+        let mut states: Vec<(i64, TimingState)> = Vec::new();
+        let mut state = initial;
+        for group in points.chunk_by(|a, b| a.time == b.time) {
+            // The first red line at a timestamp is the one osu! keeps,
+            // while green lines override it and each other in file order.
+            let red = group.iter().find(|point| point.uninherited);
+            let green = group.iter().rev().find(|point| !point.uninherited);
+            if let Some(red) = red {
+                state = TimingState::from_red_line(*red);
+            }
+            if let Some(green) = green {
+                state.slider_velocity = match green.beat_length < 0.0 {
+                    true => (-100.0 / green.beat_length).clamp(0.1, 10.0),
+                    false => 1.0,
+                };
+                state.bpm_multiplier = match green.beat_length < 0.0 {
+                    true => (-green.beat_length as f32).clamp(10.0, 10000.0) as f64 / 100.0,
+                    false => 1.0,
+                };
+                state.apply_samples(green);
+            }
+            states.push((group[0].time, state));
+        }
+        // Samples before the first timing point come from the first one.
+        if let Some(&(_, first)) = states.first() {
+            initial.sample_set = first.sample_set;
+            initial.sample_index = first.sample_index;
+            initial.volume = first.volume;
+        }
+        Self { states, initial }
+    }
+
+    /// The timing state in effect at `time`.
+    ///
+    /// Before the first red line, the beat comes from the first red line.
+    pub fn at(&self, time: f64) -> TimingState {
+        let index = self
+            .states
+            .partition_point(|&(point_time, _)| point_time as f64 <= time);
+        match index {
+            0 => self.initial,
+            _ => self.states[index - 1].1,
+        }
+    }
+
+    pub fn red_line_at(&self, time: f64) -> TimingPoint {
+        self.at(time).red_line
+    }
+
+    pub fn beat_length_at(&self, time: f64) -> f64 {
+        self.at(time).beat_length
+    }
+
+    pub fn bpm_at(&self, time: f64) -> f64 {
+        self.at(time).bpm
+    }
+
+    pub fn meter_at(&self, time: f64) -> i64 {
+        self.at(time).meter
+    }
+
+    pub fn slider_velocity_at(&self, time: f64) -> f64 {
+        self.at(time).slider_velocity
+    }
+
+    pub fn sample_set_at(&self, time: f64) -> SampleSet {
+        self.at(time).sample_set
+    }
+
+    pub fn sample_index_at(&self, time: f64) -> i64 {
+        self.at(time).sample_index
+    }
+
+    pub fn volume_at(&self, time: f64) -> i64 {
+        self.at(time).volume
+    }
+
+    pub fn kiai_at(&self, time: f64) -> bool {
+        self.at(time).kiai
+    }
+
+    /// The red lines that take effect, in time order.
+    pub fn red_lines(&self) -> Vec<TimingPoint> {
+        let mut red_lines: Vec<TimingPoint> = Vec::new();
+        for (_, state) in self.states.iter() {
+            if red_lines.last() != Some(&state.red_line) {
+                red_lines.push(state.red_line);
+            }
+        }
+        red_lines
+    }
+}
+
+impl TimingState {
+    fn from_red_line(red_line: TimingPoint) -> Self {
+        let beat_length = if red_line.beat_length.is_nan() {
+            1000.0
+        } else {
+            red_line.beat_length.clamp(6.0, 60000.0)
+        };
+        let mut state = Self {
+            red_line,
+            beat_length,
+            bpm: 60000.0 / beat_length,
+            meter: red_line.meter,
+            slider_velocity: 1.0,
+            bpm_multiplier: 1.0,
+            sample_set: SampleSet::Default,
+            sample_index: 0,
+            volume: 100,
+            kiai: false,
+        };
+        state.apply_samples(&red_line);
+        state
+    }
+
+    fn apply_samples(&mut self, point: &TimingPoint) {
+        self.sample_set = point.sample_set;
+        self.sample_index = point.sample_index;
+        self.volume = point.volume;
+        self.kiai = point.effects.kiai;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn red(time: i64, beat_length: f64) -> TimingPoint {
+        TimingPoint {
+            time,
+            beat_length,
+            ..Default::default()
+        }
+    }
+
+    fn green(time: i64, beat_length: f64) -> TimingPoint {
+        TimingPoint {
+            time,
+            beat_length,
+            uninherited: false,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn states_resolve_in_time_order() {
+        let mut kiai_green = green(3000, -50.0);
+        kiai_green.effects.kiai = true;
+        kiai_green.volume = 40;
+        // Out of order, with a green line listed before the red line it shares a time with.
+        let map = TimingMap::new(&[
+            kiai_green,
+            green(1000, -200.0),
+            red(1000, 500.0),
+            red(2000, 250.0),
+        ]);
+
+        let before = map.at(0.0);
+        assert_eq!(before.beat_length, 500.0);
+        assert_eq!(before.slider_velocity, 1.0);
+        assert!(!before.kiai);
+
+        let first = map.at(1500.0);
+        assert_eq!(first.beat_length, 500.0);
+        assert_eq!(first.slider_velocity, 0.5);
+        assert_eq!(first.bpm, 120.0);
+
+        // Red lines reset the slider velocity.
+        assert_eq!(map.slider_velocity_at(2000.0), 1.0);
+        assert_eq!(map.beat_length_at(2999.0), 250.0);
+
+        let kiai = map.at(3000.0);
+        assert_eq!(kiai.beat_length, 250.0);
+        assert_eq!(kiai.slider_velocity, 2.0);
+        assert_eq!(kiai.volume, 40);
+        assert!(kiai.kiai);
+
+        assert_eq!(map.red_lines(), vec![red(1000, 500.0), red(2000, 250.0)]);
+    }
+
+    #[test]
+    fn values_are_clamped_like_osu() {
+        let map = TimingMap::new(&[red(0, 1.0), green(1000, -1.0), green(2000, -100000.0)]);
+        assert_eq!(map.beat_length_at(0.0), 6.0);
+        assert_eq!(map.slider_velocity_at(1000.0), 10.0);
+        assert_eq!(map.slider_velocity_at(2000.0), 0.1);
+        assert_eq!(map.at(2000.0).bpm_multiplier, 100.0);
+    }
+
+    #[test]
+    fn first_red_line_at_a_time_wins() {
+        let map = TimingMap::new(&[red(0, 400.0), red(0, 300.0)]);
+        assert_eq!(map.beat_length_at(0.0), 400.0);
+    }
+
+    #[test]
+    fn charts_without_red_lines_default_to_60_bpm() {
+        let map = TimingMap::new(&[green(0, -50.0)]);
+        assert_eq!(map.bpm_at(500.0), 60.0);
+        assert_eq!(map.slider_velocity_at(500.0), 2.0);
+    }
+}
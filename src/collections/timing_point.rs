@@ -29,3 +29,22 @@ impl Default for TimingPoint {
         }
     }
 }
+
+/// Written as a line of the [TimingPoints] section.
+impl std::fmt::Display for TimingPoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let effects = self.effects.kiai as u8 | (self.effects.ommit_barline as u8) << 3;
+        write!(
+            f,
+            "{},{},{},{},{},{},{},{}",
+            self.time,
+            self.beat_length,
+            self.meter,
+            self.sample_set.to_u8(),
+            self.sample_index,
+            self.volume,
+            self.uninherited as u8,
+            effects
+        )
+    }
+}
@@ -5,6 +5,7 @@ pub use crate::parts::Countdown;
 pub use crate::parts::OverlayPosition;
 pub use crate::parts::SampleSet;
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Customization {
     pub sample_set: SampleSet,             // Default = Normal
     pub letterbox_in_breaks: bool,         // Default = 0
@@ -15,10 +16,98 @@ pub struct Customization {
     pub skin_preference: Option<String>,   // No default. Is it optional? TODO
     pub epilepsy_warning: bool,            // Default = 0
     pub countdown: Countdown,              // Default = 1 (normal)
-    pub special_style: bool,               // Default = 0
+    /// osu!mania's N+1 layout, such as 7+1 on 8 keys, where the first column is a scratch
+    /// column set apart from the rest. It changes how the stage is drawn and which keys
+    /// play it, not which column an object is in,
+    /// see [`HitObject::column`](crate::collections::HitObject::column).
+    pub special_style: bool, // Default = 0
     pub widescreen_storyboard: bool,       // Default = 0
     pub samples_match_playback_rate: bool, // Default = 0
     pub backgrounds: Vec<Background>,
     pub breaks: Vec<Break>,
-    pub colors: Vec<Color>,
+    /// Videos, storyboard objects and the other `[Events]` lines this crate doesn't model,
+    /// kept as written and in file order.
+    pub other_events: Vec<String>,
+    pub colours: Colours,
+}
+
+/// The [Colours] section, with its entries kept in file order.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Colours {
+    pub entries: Vec<(ColourKey, Color)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColourKey {
+    Combo(u8), // From 1 to 8.
+    SliderTrackOverride,
+    SliderBorder,
+    Other(String),
+}
+
+impl Colours {
+    /// The combo colours ordered from `Combo1` to `Combo8`.
+    pub fn combo_colours(&self) -> Vec<Color> {
+        let mut combos = self
+            .entries
+            .iter()
+            .filter_map(|(key, colour)| match key {
+                ColourKey::Combo(number) => Some((*number, *colour)),
+                _ => None,
+            })
+            .collect::<Vec<(u8, Color)>>();
+        combos.sort_by_key(|(number, _)| *number);
+        combos.into_iter().map(|(_, colour)| colour).collect()
+    }
+
+    pub fn slider_track_override(&self) -> Option<Color> {
+        self.get(&ColourKey::SliderTrackOverride)
+    }
+
+    pub fn slider_border(&self) -> Option<Color> {
+        self.get(&ColourKey::SliderBorder)
+    }
+
+    /// Later entries override earlier ones with the same key.
+    pub fn get(&self, key: &ColourKey) -> Option<Color> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|(entry_key, _)| entry_key == key)
+            .map(|(_, colour)| *colour)
+    }
+
+    /// Replaces the entry with the same key in place, or adds it to the end.
+    pub fn set(&mut self, key: ColourKey, colour: Color) {
+        match self
+            .entries
+            .iter_mut()
+            .find(|(entry_key, _)| *entry_key == key)
+        {
+            Some(entry) => entry.1 = colour,
+            None => self.entries.push((key, colour)),
+        }
+    }
+}
+
+impl std::fmt::Display for ColourKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ColourKey::Combo(number) => write!(f, "Combo{number}"),
+            ColourKey::SliderTrackOverride => write!(f, "SliderTrackOverride"),
+            ColourKey::SliderBorder => write!(f, "SliderBorder"),
+            ColourKey::Other(key) => write!(f, "{key}"),
+        }
+    }
+}
+
+/// Writes the [Colours] section in its original order.
+impl std::fmt::Display for Colours {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "[Colours]")?;
+        for (key, colour) in self.entries.iter() {
+            writeln!(f, "{key} : {colour}")?;
+        }
+        Ok(())
+    }
 }
@@ -1,10 +1,189 @@
-/// The fields are represented with u8's,
-/// but the actual value they represent
-/// is that divided by 10.
+use crate::chart::Mode;
+use crate::utility::difficulty_range;
+use crate::utility::ratio_to_f64;
+pub use num::rational::Ratio;
+
+/// The values are stored exactly as written in the .osu file.
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Difficulty {
-    pub circle_size: u8,
-    pub hpdrain_rate: u8,
-    pub overall_difficulty: u8,
-    pub approach_rate: u8,
+    pub circle_size: Ratio<i64>,
+    pub hpdrain_rate: Ratio<i64>,
+    pub overall_difficulty: Ratio<i64>,
+    pub approach_rate: Ratio<i64>,
+}
+
+/// How far from an object's time, in milliseconds either way,
+/// a hit still gets each judgement.
+///
+/// Judgements a mode doesn't have are `None`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct HitWindows {
+    pub perfect: Option<f64>, // osu!mania only
+    pub great: f64,
+    pub good: Option<f64>, // osu!mania only
+    pub ok: f64,
+    pub meh: Option<f64>, // Not in osu!taiko
+    pub miss: f64,
+}
+
+impl Difficulty {
+    /// The size of objects relative to CS 5, as drawn in game.
+    pub fn circle_scale(&self) -> f64 {
+        let circle_size = ratio_to_f64(self.circle_size);
+        // osu! draws circles very slightly larger than the formula gives.
+        (1.0 - 0.7 * (circle_size - 5.0) / 5.0) / 2.0 * 1.00041
+    }
+
+    /// The radius of circles in osu!pixels.
+    pub fn circle_radius(&self) -> f64 {
+        64.0 * self.circle_scale()
+    }
+
+    /// The width in osu!pixels of the part of the osu!catch catcher that catches objects.
+    pub fn catcher_width(&self) -> f64 {
+        let circle_size = ratio_to_f64(self.circle_size);
+        let scale = 1.0 - 0.7 * (circle_size - 5.0) / 5.0;
+        // Objects can be caught a little inside the edges of the catcher sprite.
+        106.75 * scale.abs() * 0.8
+    }
+
+    /// How long in milliseconds before its time an object appears.
+    pub fn preempt(&self) -> f64 {
+        difficulty_range(ratio_to_f64(self.approach_rate), 1800.0, 1200.0, 450.0)
+    }
+
+    /// How long in milliseconds an object takes to fade in once it appears.
+    pub fn fade_in(&self) -> f64 {
+        400.0 * (self.preempt() / 450.0).min(1.0)
+    }
+
+    /// Hit windows from overall difficulty.
+    /// osu!catch doesn't judge timing, so it has none.
+    pub fn hit_windows(&self, mode: Mode) -> Option<HitWindows> {
+        let od = ratio_to_f64(self.overall_difficulty);
+        match mode {
+            Mode::Osu => Some(HitWindows {
+                perfect: None,
+                great: difficulty_range(od, 80.0, 50.0, 20.0),
+                good: None,
+                ok: difficulty_range(od, 140.0, 100.0, 60.0),
+                meh: Some(difficulty_range(od, 200.0, 150.0, 100.0)),
+                miss: 400.0,
+            }),
+            Mode::Taiko => Some(HitWindows {
+                perfect: None,
+                great: difficulty_range(od, 50.0, 35.0, 20.0),
+                good: None,
+                ok: difficulty_range(od, 120.0, 80.0, 50.0),
+                meh: None,
+                miss: difficulty_range(od, 135.0, 95.0, 70.0),
+            }),
+            Mode::Catch => None,
+            Mode::Mania => Some(HitWindows {
+                perfect: Some(16.0),
+                great: 64.0 - 3.0 * od,
+                good: Some(97.0 - 3.0 * od),
+                ok: 127.0 - 3.0 * od,
+                meh: Some(151.0 - 3.0 * od),
+                miss: 188.0 - 3.0 * od,
+            }),
+        }
+    }
+
+    /// Full rotations needed to clear a spinner lasting `duration` milliseconds.
+    pub fn spinner_rotations(&self, duration: f64) -> i64 {
+        let od = ratio_to_f64(self.overall_difficulty);
+        let rotations_per_second = difficulty_range(od, 3.0, 5.0, 7.5);
+        (duration / 1000.0 * rotations_per_second) as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::beatmap;
+
+    // Parsed from a beatmap, so fractional values go through the same path as real files.
+    fn parsed(cs: &str, od: &str, ar: &str) -> Difficulty {
+        let section = format!(
+            "HPDrainRate:5\nCircleSize:{cs}\nOverallDifficulty:{od}\nApproachRate:{ar}\n\
+             SliderMultiplier:1.4\nSliderTickRate:1"
+        );
+        beatmap(0, &section, "0,500,4,2,0,100,1,0", "").difficulty
+    }
+
+    fn assert_near(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn circle_sizes() {
+        for (cs, radius) in [
+            ("0", 54.422304),
+            ("4", 36.4949568),
+            ("4.2", 35.59858944),
+            ("5", 32.01312),
+            ("7", 23.0494464),
+        ] {
+            let difficulty = parsed(cs, "5", "5");
+            assert_near(difficulty.circle_radius(), radius);
+            assert_near(difficulty.circle_scale(), radius / 64.0);
+        }
+    }
+
+    #[test]
+    fn approach_rates() {
+        for (ar, preempt, fade_in) in [
+            ("0", 1800.0, 400.0),
+            ("5", 1200.0, 400.0),
+            ("9", 600.0, 400.0),
+            ("9.35", 547.5, 400.0),
+            ("10", 450.0, 400.0),
+            ("10.5", 375.0, 400.0 * 375.0 / 450.0),
+        ] {
+            let difficulty = parsed("4", "5", ar);
+            assert_near(difficulty.preempt(), preempt);
+            assert_near(difficulty.fade_in(), fade_in);
+        }
+    }
+
+    #[test]
+    fn hit_windows_per_mode() {
+        let difficulty = parsed("4", "8.5", "9");
+        let osu = difficulty.hit_windows(Mode::Osu).unwrap();
+        assert_near(osu.great, 29.0);
+        assert_near(osu.ok, 72.0);
+        assert_near(osu.meh.unwrap(), 115.0);
+        assert_eq!((osu.perfect, osu.good, osu.miss), (None, None, 400.0));
+
+        let taiko = difficulty.hit_windows(Mode::Taiko).unwrap();
+        assert_near(taiko.great, 24.5);
+        assert_near(taiko.ok, 59.0);
+        assert_near(taiko.miss, 77.5);
+        assert_eq!(taiko.meh, None);
+
+        assert_eq!(difficulty.hit_windows(Mode::Catch), None);
+
+        let mania = difficulty.hit_windows(Mode::Mania).unwrap();
+        assert_eq!(mania.perfect, Some(16.0));
+        assert_near(mania.great, 38.5);
+        assert_near(mania.good.unwrap(), 71.5);
+        assert_near(mania.ok, 101.5);
+        assert_near(mania.meh.unwrap(), 125.5);
+        assert_near(mania.miss, 162.5);
+
+        let easiest = parsed("4", "0", "9").hit_windows(Mode::Osu).unwrap();
+        assert_eq!(
+            (easiest.great, easiest.ok, easiest.meh),
+            (80.0, 140.0, Some(200.0))
+        );
+    }
+
+    #[test]
+    fn spinner_rotations() {
+        assert_eq!(parsed("4", "8", "9").spinner_rotations(2000.0), 13);
+        assert_eq!(parsed("4", "3", "9").spinner_rotations(2000.0), 8);
+        assert_eq!(parsed("4", "10", "9").spinner_rotations(1000.0), 7);
+        assert_eq!(parsed("4", "5", "9").spinner_rotations(0.0), 0);
+    }
 }
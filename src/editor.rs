@@ -1,5 +1,6 @@
 pub use num::rational::Ratio;
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Editor {
     pub bookmarks: Vec<i64>,
     pub distance_spacing: Option<Ratio<i64>>,
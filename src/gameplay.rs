@@ -0,0 +1,535 @@
+mod slider;
+mod spinner;
+
+use crate::chart::Mode;
+use crate::collections::HitObject;
+use crate::difficulty::HitWindows;
+use crate::mods::Adjusted;
+use crate::score::Judgement;
+use crate::score::Judgements;
+use crate::score::TimedJudgement;
+use slider::SliderState;
+use spinner::SpinnerState;
+
+/// Which buttons are held, stored the same way as in replays.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Buttons(pub u8);
+
+impl Buttons {
+    pub const NONE: Buttons = Buttons(0);
+    pub const MOUSE_1: Buttons = Buttons(1 << 0);
+    pub const MOUSE_2: Buttons = Buttons(1 << 1);
+    pub const KEY_1: Buttons = Buttons(1 << 2); // Always set along with Mouse1.
+    pub const KEY_2: Buttons = Buttons(1 << 3); // Always set along with Mouse2.
+    pub const SMOKE: Buttons = Buttons(1 << 4);
+
+    pub fn contains(&self, other: Buttons) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: Buttons) {
+        self.0 |= other.0;
+    }
+
+    /// Whether any button that hits objects is held.
+    pub fn any_held(&self) -> bool {
+        self.contains(Self::MOUSE_1) || self.contains(Self::MOUSE_2)
+    }
+}
+
+impl std::ops::BitOr for Buttons {
+    type Output = Buttons;
+    fn bitor(self, other: Buttons) -> Buttons {
+        Buttons(self.0 | other.0)
+    }
+}
+
+/// Where the cursor is and which buttons are held at a point in time.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Frame {
+    /// In milliseconds, on the same clock as the chart's objects.
+    pub time: f64,
+    pub position: (f64, f64),
+    pub buttons: Buttons,
+}
+
+/// How one object was judged.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectJudgement {
+    /// Index into the chart's hit objects.
+    pub index: usize,
+    pub judgement: Judgement,
+    /// When the judgement was decided.
+    pub time: f64,
+    /// How late the object was hit in milliseconds, negative when early.
+    /// `None` for spinners and objects that weren't hit.
+    pub hit_error: Option<f64>,
+    /// The combo right after the object was judged.
+    pub combo: i64,
+    /// Whether the object or any part of it broke combo.
+    pub broke_combo: bool,
+    /// Judgements of slider heads, ticks, repeats and ends, and spinner spins.
+    pub nested: Vec<TimedJudgement>,
+    /// How fast a spinner was spun on average over its duration, in rotations per minute
+    /// of real time.
+    pub spinner_rpm: Option<f64>,
+}
+
+/// The outcome of judging a whole play.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JudgedPlay {
+    /// One for each osu!standard object, in the chart's order.
+    pub objects: Vec<ObjectJudgement>,
+    /// Every judgement including nested ones, in time order,
+    /// see [`simulate_health`](crate::health::simulate_health).
+    pub judgements: Vec<TimedJudgement>,
+    pub counts: Judgements,
+    pub max_combo: i64,
+}
+
+// The playfield's centre, where spinners are spun around.
+const SPINNER_CENTRE: (f64, f64) = (256.0, 192.0);
+// How early in milliseconds a click still counts as a miss instead of being ignored.
+const MISS_WINDOW: f64 = 400.0;
+
+/// Judges an osu!standard play from its input, like stable does.
+///
+/// Circles and slider heads are hit by pressing a button while hovering them within
+/// the hit windows. Note lock stops an object from being hit while an earlier one is
+/// still waiting, until that one's time has come. Hitting an object misses the earlier
+/// ones that were skipped. Slider ticks, repeats and ends need a button held inside the
+/// follow circle, and spinners count rotations around the centre at a speed capped in real time.
+///
+/// `frames` should be in time order. `file_format` decides how objects stack and how
+/// slider ticks are spaced, see [`Chart::stacking`](crate::chart::Chart::stacking) and
+/// [`Slider::tick_distance`](crate::collections::Slider::tick_distance). Mods other than the ones
+/// `adjusted` already applied, like Relax, aren't simulated.
+pub fn judge_play(adjusted: &Adjusted, file_format: u8, frames: &[Frame]) -> JudgedPlay {
+    let mut engine = Engine::new(adjusted, file_format);
+    let mut previous = frames.first().copied().unwrap_or_default();
+    previous.buttons = Buttons::NONE;
+    for frame in frames {
+        engine.expire_heads(frame.time);
+        for button in [Buttons::MOUSE_1, Buttons::MOUSE_2] {
+            if frame.buttons.contains(button) && !previous.buttons.contains(button) {
+                engine.press(frame);
+            }
+        }
+        for slider in engine.sliders.iter_mut() {
+            slider.update(frame, engine.radius, &mut engine.events);
+        }
+        for spinner in engine.spinners.iter_mut() {
+            spinner.update(&previous, frame, &mut engine.events);
+        }
+        previous = *frame;
+    }
+    engine.finish()
+}
+
+// How a judgement changes the combo.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ComboChange {
+    Increase,
+    Break,
+    Keep,
+}
+
+// A judgement as it's made, before combo is counted.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Event {
+    index: usize,
+    time: f64,
+    judgement: Judgement,
+    combo: ComboChange,
+    // Whether this decides the object's judgement, rather than a part of it.
+    is_final: bool,
+    hit_error: Option<f64>,
+}
+
+// Something that's hit by clicking: a circle or a slider head.
+struct Head {
+    index: usize,
+    time: f64,
+    position: (f64, f64),
+    slider: Option<usize>,
+}
+
+struct Engine {
+    windows: HitWindows,
+    radius: f64,
+    // How many objects the chart has, mania holds included.
+    object_count: usize,
+    heads: Vec<Head>,
+    // Heads before this one have all been judged. Note lock makes them go in order.
+    next_head: usize,
+    sliders: Vec<SliderState>,
+    spinners: Vec<SpinnerState>,
+    events: Vec<Event>,
+}
+
+impl Engine {
+    fn new(adjusted: &Adjusted, file_format: u8) -> Self {
+        let chart = &adjusted.chart;
+        let difficulty = &adjusted.difficulty;
+        let stacks = chart.stacking(difficulty, file_format);
+        let timing = chart.timing_map();
+        let mut engine = Engine {
+            windows: difficulty
+                .hit_windows(Mode::Osu)
+                .expect("osu!standard has hit windows"),
+            radius: difficulty.circle_radius(),
+            object_count: chart.hit_objects.len(),
+            heads: Vec::new(),
+            next_head: 0,
+            sliders: Vec::new(),
+            spinners: Vec::new(),
+            events: Vec::new(),
+        };
+        for (index, (object, stack)) in chart.hit_objects.iter().zip(stacks).enumerate() {
+            let time = object.start_time();
+            match object {
+                HitObject::Circle(_) => engine.heads.push(Head {
+                    index,
+                    time,
+                    position: stack.position,
+                    slider: None,
+                }),
+                HitObject::Slider(slider) => {
+                    engine.heads.push(Head {
+                        index,
+                        time,
+                        position: stack.position,
+                        slider: Some(engine.sliders.len()),
+                    });
+                    engine.sliders.push(SliderState::new(
+                        index,
+                        slider,
+                        chart,
+                        &timing,
+                        file_format,
+                        stack.offset,
+                    ));
+                }
+                HitObject::Spinner(spinner) => engine.spinners.push(SpinnerState::new(
+                    index,
+                    time,
+                    spinner.end_time as f64,
+                    difficulty.spinner_rotations(
+                        (spinner.end_time - spinner.time) as f64 / adjusted.clock_rate,
+                    ),
+                    adjusted.clock_rate,
+                )),
+                HitObject::ManiaHold(_) => {}
+            }
+        }
+        engine
+    }
+
+    // Misses the heads that can't be hit anymore by `time`.
+    fn expire_heads(&mut self, time: f64) {
+        while let Some(head) = self.heads.get(self.next_head) {
+            let latest = head.time + self.windows.meh.unwrap_or(self.windows.ok);
+            if time <= latest {
+                break;
+            }
+            self.judge_head(self.next_head, latest, None);
+        }
+    }
+
+    fn press(&mut self, frame: &Frame) {
+        let time = frame.time;
+        let hovered = self.heads[self.next_head..]
+            .iter()
+            .take_while(|head| time >= head.time - MISS_WINDOW)
+            .position(|head| distance(frame.position, head.position) <= self.radius);
+        let Some(offset) = hovered else {
+            return;
+        };
+        let target = self.next_head + offset;
+        // Note lock: every earlier head that's still waiting has to have reached its time.
+        if target > self.next_head && time < self.heads[target - 1].time {
+            return;
+        }
+        while self.next_head < target {
+            self.judge_head(self.next_head, time, None);
+        }
+        let error = time - self.heads[target].time;
+        self.judge_head(target, time, Some(error));
+    }
+
+    // Judges the next head, as hit `error` milliseconds late or missed when `None`.
+    fn judge_head(&mut self, head_index: usize, time: f64, error: Option<f64>) {
+        debug_assert_eq!(head_index, self.next_head);
+        self.next_head += 1;
+        let head = &self.heads[head_index];
+        let windows = &self.windows;
+        let judgement = match error.map(f64::abs) {
+            Some(error) if error <= windows.great => Judgement::Great,
+            Some(error) if error <= windows.ok => Judgement::Ok,
+            Some(error) if error <= windows.meh.unwrap_or(windows.ok) => Judgement::Meh,
+            _ => Judgement::Miss,
+        };
+        let hit_error = error.filter(|_| judgement != Judgement::Miss);
+        match head.slider {
+            Some(slider) => {
+                let slider = &mut self.sliders[slider];
+                slider.hit_head(time, hit_error, &mut self.events);
+            }
+            None => self.events.push(Event {
+                index: head.index,
+                time,
+                judgement,
+                combo: match judgement {
+                    Judgement::Miss => ComboChange::Break,
+                    _ => ComboChange::Increase,
+                },
+                is_final: true,
+                hit_error,
+            }),
+        }
+    }
+
+    fn finish(mut self) -> JudgedPlay {
+        self.expire_heads(f64::INFINITY);
+        for slider in self.sliders.iter_mut() {
+            slider.finish(&mut self.events);
+        }
+        for spinner in self.spinners.iter_mut() {
+            spinner.finish(&mut self.events);
+        }
+        // Stable, so judgements made together keep the order they were made in.
+        self.events.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        let mut objects: Vec<Option<ObjectJudgement>> = vec![None; self.object_count];
+        let mut counts = Judgements::default();
+        let mut combo = 0;
+        let mut max_combo = 0;
+        let mut judgements = Vec::with_capacity(self.events.len());
+        for event in self.events.iter() {
+            match event.combo {
+                ComboChange::Increase => combo += 1,
+                ComboChange::Break => combo = 0,
+                ComboChange::Keep => {}
+            }
+            max_combo = max_combo.max(combo);
+            let judged = TimedJudgement {
+                time: event.time,
+                judgement: event.judgement,
+            };
+            judgements.push(judged);
+            let object = objects[event.index].get_or_insert_with(|| ObjectJudgement {
+                index: event.index,
+                judgement: Judgement::Miss,
+                time: event.time,
+                hit_error: None,
+                combo,
+                broke_combo: false,
+                nested: Vec::new(),
+                spinner_rpm: None,
+            });
+            if event.hit_error.is_some() {
+                object.hit_error = event.hit_error;
+            }
+            if event.combo == ComboChange::Break {
+                object.broke_combo = true;
+            }
+            if !event.is_final {
+                object.nested.push(judged);
+                continue;
+            }
+            object.judgement = event.judgement;
+            object.time = event.time;
+            object.combo = combo;
+            match event.judgement {
+                Judgement::Great => counts.count_300 += 1,
+                Judgement::Ok => counts.count_100 += 1,
+                Judgement::Meh => counts.count_50 += 1,
+                _ => counts.count_miss += 1,
+            }
+        }
+        for spinner in self.spinners.iter() {
+            if let Some(object) = objects[spinner.index].as_mut() {
+                object.spinner_rpm = Some(spinner.rpm());
+            }
+        }
+        JudgedPlay {
+            objects: objects.into_iter().flatten().collect(),
+            judgements,
+            counts,
+            max_combo,
+        }
+    }
+}
+
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mods::Mods;
+    use crate::utility::{beatmap, DIFFICULTY};
+
+    // At OD 8 the hit windows are 32, 76 and 120 milliseconds.
+    fn play(hit_objects: &str, frames: &[Frame]) -> JudgedPlay {
+        let fixture = beatmap(0, DIFFICULTY, "0,500,4,2,0,100,1,0", hit_objects);
+        let adjusted = Mods::NONE
+            .apply(&fixture.chart, &fixture.difficulty, None)
+            .unwrap();
+        judge_play(&adjusted, 14, frames)
+    }
+
+    fn frame(time: f64, position: (f64, f64), buttons: Buttons) -> Frame {
+        Frame {
+            time,
+            position,
+            buttons,
+        }
+    }
+
+    // Clicks at each time and position, letting go right after.
+    fn clicks(clicks: &[(f64, (f64, f64))]) -> Vec<Frame> {
+        clicks
+            .iter()
+            .flat_map(|&(time, position)| {
+                [
+                    frame(time, position, Buttons::MOUSE_1),
+                    frame(time + 1.0, position, Buttons::NONE),
+                ]
+            })
+            .collect()
+    }
+
+    const CIRCLES: &str = "100,100,1000,1,0,0:0:0:0:\n400,100,1100,1,0,0:0:0:0:";
+
+    #[test]
+    fn note_lock_ignores_presses_on_later_circles() {
+        let play = play(
+            CIRCLES,
+            &clicks(&[
+                (950.0, (400.0, 100.0)),
+                (1000.0, (100.0, 100.0)),
+                (1100.0, (400.0, 100.0)),
+            ]),
+        );
+        let judged: Vec<_> = play
+            .objects
+            .iter()
+            .map(|o| (o.judgement, o.hit_error))
+            .collect();
+        assert_eq!(
+            judged,
+            [(Judgement::Great, Some(0.0)), (Judgement::Great, Some(0.0))]
+        );
+        assert_eq!(play.max_combo, 2);
+    }
+
+    #[test]
+    fn hitting_a_later_circle_misses_the_skipped_ones() {
+        let play = play(CIRCLES, &clicks(&[(1050.0, (400.0, 100.0))]));
+        let first = &play.objects[0];
+        assert_eq!(
+            (first.judgement, first.time, first.hit_error),
+            (Judgement::Miss, 1050.0, None)
+        );
+        let second = &play.objects[1];
+        assert_eq!(
+            (second.judgement, second.hit_error),
+            (Judgement::Ok, Some(-50.0))
+        );
+        assert_eq!((play.counts.count_100, play.counts.count_miss), (1, 1));
+        assert_eq!(second.combo, 1);
+    }
+
+    #[test]
+    fn hit_windows_include_their_edges() {
+        let judgement = |error: f64| {
+            let play = play(
+                "100,100,1000,1,0,0:0:0:0:",
+                &clicks(&[(1000.0 + error, (100.0, 100.0))]),
+            );
+            (play.objects[0].judgement, play.objects[0].hit_error)
+        };
+        for (error, expected) in [
+            (-32.0, Judgement::Great),
+            (32.0, Judgement::Great),
+            (32.5, Judgement::Ok),
+            (-76.0, Judgement::Ok),
+            (76.5, Judgement::Meh),
+            (120.0, Judgement::Meh),
+            (-120.0, Judgement::Meh),
+        ] {
+            assert_eq!(judgement(error), (expected, Some(error)), "{error}");
+        }
+        // Too early to hit but close enough to count, or too late.
+        assert_eq!(judgement(-121.0), (Judgement::Miss, None));
+        assert_eq!(judgement(-400.0), (Judgement::Miss, None));
+        assert_eq!(judgement(121.0), (Judgement::Miss, None));
+        // Further than the miss window, the click is ignored and the circle can still be hit.
+        let play = play(
+            "100,100,1000,1,0,0:0:0:0:",
+            &clicks(&[(599.0, (100.0, 100.0)), (1000.0, (100.0, 100.0))]),
+        );
+        assert_eq!(play.objects[0].judgement, Judgement::Great);
+    }
+
+    // A slider from (100, 100) to (400, 100) from 1000 for about 1071 milliseconds,
+    // with ticks at 1500 and 2000 and the legacy last tick 36 milliseconds before its end.
+    const SLIDER: &str = "100,100,1000,2,0,L|400:100,1,300\n400,300,2500,1,0,0:0:0:0:";
+
+    // Follows the ball from the slider's start until `release`, then moves away.
+    fn follow(release: f64) -> Vec<Frame> {
+        let mut frames: Vec<Frame> = (0..)
+            .map(|i| 1000.0 + i as f64 * 10.0)
+            .take_while(|&time| time < release)
+            .map(|time| {
+                frame(
+                    time,
+                    (100.0 + 0.28 * (time - 1000.0), 100.0),
+                    Buttons::MOUSE_1,
+                )
+            })
+            .collect();
+        frames.push(frame(release, (250.0, 300.0), Buttons::NONE));
+        frames
+    }
+
+    fn nested(object: &ObjectJudgement) -> Vec<Judgement> {
+        object.nested.iter().map(|n| n.judgement).collect()
+    }
+
+    #[test]
+    fn leaving_the_follow_circle_breaks_combo() {
+        let play = play(SLIDER, &follow(1700.0));
+        let slider = &play.objects[0];
+        assert_eq!(
+            nested(slider),
+            [
+                Judgement::LargeTickHit,
+                Judgement::LargeTickHit,
+                Judgement::LargeTickMiss,
+                Judgement::LargeTickMiss
+            ]
+        );
+        assert!(slider.broke_combo);
+        // Two of the head and three ticks were hit.
+        assert_eq!(slider.judgement, Judgement::Ok);
+        assert_eq!(slider.combo, 0);
+        assert_eq!(play.max_combo, 2);
+    }
+
+    #[test]
+    fn missing_the_legacy_last_tick_keeps_combo() {
+        let mut frames = follow(2030.0);
+        frames.extend(clicks(&[(2500.0, (400.0, 300.0))]));
+        let play = play(SLIDER, &frames);
+        let slider = &play.objects[0];
+        assert_eq!(nested(slider).last(), Some(&Judgement::LargeTickMiss));
+        assert!(!slider.broke_combo);
+        assert_eq!(slider.judgement, Judgement::Ok);
+        assert_eq!(slider.combo, 3);
+        assert_eq!(play.objects[1].combo, 4);
+        assert_eq!(play.max_combo, 4);
+    }
+}
@@ -0,0 +1,188 @@
+use super::distance;
+use super::ComboChange;
+use super::Event;
+use super::Frame;
+use crate::chart::Chart;
+use crate::collections::NestedKind;
+use crate::collections::NestedObject;
+use crate::collections::Slider;
+use crate::collections::SliderPath;
+use crate::collections::TimingMap;
+use crate::score::Judgement;
+use crate::utility::ratio_to_f64;
+
+// How much bigger than a circle the follow circle is once the ball is being followed.
+const FOLLOW_RADIUS_SCALE: f64 = 2.4;
+
+/// Follows a slider's ball to judge its ticks, repeats and end.
+pub(super) struct SliderState {
+    index: usize,
+    slider: Slider,
+    path: SliderPath,
+    // How far stacking moved the slider.
+    offset: (f64, f64),
+    start_time: f64,
+    span_duration: f64,
+    // Ticks, repeats and the legacy last tick, in time order.
+    nested: Vec<NestedObject>,
+    next_nested: usize,
+    head_judged: bool,
+    head_time: f64,
+    tracking: bool,
+    // Out of the head and every nested object.
+    hits: usize,
+    finished: bool,
+}
+
+impl SliderState {
+    pub fn new(
+        index: usize,
+        slider: &Slider,
+        chart: &Chart,
+        timing: &TimingMap,
+        file_format: u8,
+        offset: (f64, f64),
+    ) -> Self {
+        let slider_multiplier = ratio_to_f64(chart.slider_multiplier);
+        let tick_rate = ratio_to_f64(chart.slider_tick_rate);
+        let nested = slider
+            .nested_objects(timing, slider_multiplier, tick_rate, file_format)
+            .into_iter()
+            .filter(|n| {
+                matches!(
+                    n.kind,
+                    NestedKind::Tick | NestedKind::Repeat | NestedKind::LegacyLastTick
+                )
+            })
+            .collect();
+        SliderState {
+            index,
+            slider: slider.clone(),
+            path: slider.path(),
+            offset,
+            start_time: slider.time as f64,
+            span_duration: slider.span_duration(timing, slider_multiplier),
+            nested,
+            next_nested: 0,
+            head_judged: false,
+            head_time: slider.time as f64,
+            tracking: false,
+            hits: 0,
+            finished: false,
+        }
+    }
+
+    fn end_time(&self) -> f64 {
+        self.start_time + self.span_duration * self.slider.slides.max(1) as f64
+    }
+
+    /// Judges the head, as hit `hit_error` milliseconds late or missed when `None`.
+    pub fn hit_head(&mut self, time: f64, hit_error: Option<f64>, events: &mut Vec<Event>) {
+        self.head_judged = true;
+        self.head_time = time;
+        let hit = hit_error.is_some();
+        if hit {
+            self.hits += 1;
+            // Hitting the head starts following the ball.
+            self.tracking = true;
+        }
+        events.push(Event {
+            index: self.index,
+            time,
+            judgement: match hit {
+                true => Judgement::LargeTickHit,
+                false => Judgement::LargeTickMiss,
+            },
+            combo: match hit {
+                true => ComboChange::Increase,
+                false => ComboChange::Break,
+            },
+            is_final: false,
+            hit_error,
+        });
+        self.try_finish(events);
+    }
+
+    pub fn update(&mut self, frame: &Frame, radius: f64, events: &mut Vec<Event>) {
+        if self.finished || (frame.time < self.start_time && !self.tracking) {
+            return;
+        }
+        let radius = match self.tracking {
+            true => radius * FOLLOW_RADIUS_SCALE,
+            false => radius,
+        };
+        let ball = self.ball_position(frame.time);
+        self.tracking = frame.buttons.any_held() && distance(frame.position, ball) <= radius;
+        self.judge_nested(frame.time, events);
+        self.try_finish(events);
+    }
+
+    /// Misses whatever's left once there's no more input.
+    pub fn finish(&mut self, events: &mut Vec<Event>) {
+        self.tracking = false;
+        self.judge_nested(f64::INFINITY, events);
+        self.try_finish(events);
+    }
+
+    // Judges the nested objects up to `time` by whether the ball is being followed.
+    fn judge_nested(&mut self, time: f64, events: &mut Vec<Event>) {
+        while let Some(nested) = self.nested.get(self.next_nested) {
+            if nested.time > time {
+                break;
+            }
+            self.next_nested += 1;
+            let hit = self.tracking;
+            if hit {
+                self.hits += 1;
+            }
+            events.push(Event {
+                index: self.index,
+                time: nested.time,
+                judgement: match hit {
+                    true => Judgement::LargeTickHit,
+                    false => Judgement::LargeTickMiss,
+                },
+                combo: match (hit, nested.kind) {
+                    (true, _) => ComboChange::Increase,
+                    // Missing the end doesn't break combo in stable.
+                    (false, NestedKind::LegacyLastTick) => ComboChange::Keep,
+                    (false, _) => ComboChange::Break,
+                },
+                is_final: false,
+                hit_error: None,
+            });
+        }
+    }
+
+    // Judges the whole slider by how much of it was hit, once every part is judged.
+    fn try_finish(&mut self, events: &mut Vec<Event>) {
+        if self.finished || !self.head_judged || self.next_nested < self.nested.len() {
+            return;
+        }
+        self.finished = true;
+        let total = self.nested.len() + 1;
+        let judgement = if self.hits == total {
+            Judgement::Great
+        } else if self.hits * 2 >= total {
+            Judgement::Ok
+        } else if self.hits > 0 {
+            Judgement::Meh
+        } else {
+            Judgement::Miss
+        };
+        events.push(Event {
+            index: self.index,
+            time: self.end_time().max(self.head_time),
+            judgement,
+            combo: ComboChange::Keep,
+            is_final: true,
+            hit_error: None,
+        });
+    }
+
+    fn ball_position(&self, time: f64) -> (f64, f64) {
+        let progress = self.slider.progress_at(time, self.span_duration);
+        let (x, y) = self.path.position_at(progress);
+        (x + self.offset.0, y + self.offset.1)
+    }
+}
@@ -0,0 +1,171 @@
+use super::ComboChange;
+use super::Event;
+use super::Frame;
+use super::SPINNER_CENTRE;
+use crate::score::Judgement;
+use std::f64::consts::PI;
+use std::f64::consts::TAU;
+
+// Stable can't be spun faster than 477 rotations per minute, in radians per millisecond
+// of real time.
+const MAX_SPIN_RATE: f64 = 477.0 * TAU / 60000.0;
+
+/// Counts the rotations of the cursor around the centre while a spinner lasts.
+pub(super) struct SpinnerState {
+    pub index: usize,
+    start_time: f64,
+    end_time: f64,
+    required_rotations: i64,
+    // Times are the chart's, so they're divided by this to get real time.
+    clock_rate: f64,
+    // Spun in radians, where turning back takes away.
+    angle: f64,
+    awarded_rotations: i64,
+    finished: bool,
+}
+
+impl SpinnerState {
+    /// `required_rotations` should come from the spinner's length in real time,
+    /// since the spin speed is capped in real time too. Rate mods then need the same
+    /// spin speed as without them, rather than making fast spinners impossible.
+    pub fn new(
+        index: usize,
+        start_time: f64,
+        end_time: f64,
+        required_rotations: i64,
+        clock_rate: f64,
+    ) -> Self {
+        SpinnerState {
+            index,
+            start_time,
+            end_time,
+            required_rotations,
+            clock_rate,
+            angle: 0.0,
+            awarded_rotations: 0,
+            finished: false,
+        }
+    }
+
+    pub fn update(&mut self, previous: &Frame, frame: &Frame, events: &mut Vec<Event>) {
+        if self.finished {
+            return;
+        }
+        let elapsed = frame.time.min(self.end_time) - previous.time.max(self.start_time);
+        if elapsed > 0.0 && frame.buttons.any_held() {
+            let mut delta = angle(frame.position) - angle(previous.position);
+            if delta > PI {
+                delta -= TAU;
+            } else if delta < -PI {
+                delta += TAU;
+            }
+            let limit = MAX_SPIN_RATE * elapsed / self.clock_rate;
+            self.angle += delta.clamp(-limit, limit);
+            self.award_rotations(frame.time.min(self.end_time), events);
+        }
+        if frame.time >= self.end_time {
+            self.finish(events);
+        }
+    }
+
+    /// Judges the spinner by how much of it was spun, once it's over.
+    pub fn finish(&mut self, events: &mut Vec<Event>) {
+        if self.finished {
+            return;
+        }
+        self.finished = true;
+        let progress = match self.required_rotations {
+            0 => 1.0,
+            required => self.rotations() as f64 / required as f64,
+        };
+        let judgement = if progress >= 1.0 {
+            Judgement::Great
+        } else if progress > 0.9 {
+            Judgement::Ok
+        } else if progress > 0.75 {
+            Judgement::Meh
+        } else {
+            Judgement::Miss
+        };
+        events.push(Event {
+            index: self.index,
+            time: self.end_time,
+            judgement,
+            combo: match judgement {
+                Judgement::Miss => ComboChange::Break,
+                _ => ComboChange::Increase,
+            },
+            is_final: true,
+            hit_error: None,
+        });
+    }
+
+    /// Rotations per minute of real time on average over the spinner's duration.
+    pub fn rpm(&self) -> f64 {
+        let minutes = (self.end_time - self.start_time) / self.clock_rate / 60000.0;
+        match minutes > 0.0 {
+            true => self.angle.abs() / TAU / minutes,
+            false => 0.0,
+        }
+    }
+
+    fn rotations(&self) -> i64 {
+        (self.angle.abs() / TAU) as i64
+    }
+
+    // Each full rotation scores, and the ones past the required rotations are a bonus.
+    fn award_rotations(&mut self, time: f64, events: &mut Vec<Event>) {
+        while self.awarded_rotations < self.rotations() {
+            self.awarded_rotations += 1;
+            events.push(Event {
+                index: self.index,
+                time,
+                judgement: match self.awarded_rotations > self.required_rotations {
+                    true => Judgement::LargeBonus,
+                    false => Judgement::SmallBonus,
+                },
+                combo: ComboChange::Keep,
+                is_final: false,
+                hit_error: None,
+            });
+        }
+    }
+}
+
+fn angle(position: (f64, f64)) -> f64 {
+    (position.1 - SPINNER_CENTRE.1).atan2(position.0 - SPINNER_CENTRE.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::Buttons;
+
+    // Spins as fast as possible for a second of the chart, at `clock_rate`.
+    fn spin(clock_rate: f64) -> SpinnerState {
+        let mut spinner = SpinnerState::new(0, 0.0, 1000.0, 0, clock_rate);
+        let mut events = Vec::new();
+        let frame = |time: f64| Frame {
+            time,
+            position: (
+                SPINNER_CENTRE.0 + 50.0 * (time / 4.0).cos(),
+                SPINNER_CENTRE.1 + 50.0 * (time / 4.0).sin(),
+            ),
+            buttons: Buttons::MOUSE_1,
+        };
+        let mut previous = frame(0.0);
+        for i in 1..=1000 {
+            let current = frame(i as f64);
+            spinner.update(&previous, &current, &mut events);
+            previous = current;
+        }
+        spinner
+    }
+
+    #[test]
+    fn spin_rate_is_capped_in_real_time() {
+        assert_eq!(spin(1.0).rotations(), 7);
+        assert_eq!(spin(1.5).rotations(), 5);
+        assert!((spin(1.5).rpm() - 477.0).abs() < 1.0);
+    }
+}
@@ -0,0 +1,344 @@
+use crate::chart::Mode;
+use crate::customization::Break;
+use crate::difficulty::Difficulty;
+use crate::score::Judgement;
+use crate::score::TimedJudgement;
+use crate::utility::difficulty_range;
+use crate::utility::ratio_to_f64;
+
+// What the best judgements add to the health bar, which holds 1.
+const MAX_HEALTH_INCREASE: f64 = 0.05;
+// How close to its target a perfect play's lowest health has to get.
+const HEALTH_LENIENCY: f64 = 0.01;
+// The drain rate search halves its step each time, so this is far more than it needs.
+const MAX_DRAIN_ITERATIONS: usize = 64;
+
+/// Health between 0 and 1 at some point in a play.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct HealthPoint {
+    pub time: f64,
+    pub health: f64,
+}
+
+/// How health went over a play.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthCurve {
+    /// Health right before and right after each judgement, in time order.
+    pub points: Vec<HealthPoint>,
+    /// When health first ran out, if it did.
+    pub failure: Option<f64>,
+    /// Health lost per millisecond between objects, 0 in modes that don't drain.
+    pub drain_rate: f64,
+}
+
+impl HealthCurve {
+    /// The lowest health reached.
+    pub fn lowest(&self) -> Option<HealthPoint> {
+        self.points
+            .iter()
+            .copied()
+            .min_by(|a, b| a.health.total_cmp(&b.health))
+    }
+}
+
+/// Simulates the health bar through a play.
+///
+/// `judgements` should hold every judgement of the play in time order, and `difficulty`
+/// is as played, with mods applied. osu!standard and osu!catch drain health between
+/// objects but not during breaks, at a rate where a perfect play gets down to a lowest
+/// health set by HP drain rate. osu!mania doesn't drain, and osu!taiko starts empty and
+/// fails when no more than half full at the end. The simulation carries on after failing,
+/// as with No Fail.
+pub fn simulate_health(
+    mode: Mode,
+    difficulty: &Difficulty,
+    breaks: &[Break],
+    judgements: &[TimedJudgement],
+) -> HealthCurve {
+    let rules = HealthRules::new(mode, difficulty, judgements);
+    let drain_rate = match mode {
+        Mode::Osu | Mode::Catch => rules.drain_rate(breaks, judgements),
+        Mode::Taiko | Mode::Mania => 0.0,
+    };
+    let mut points = Vec::with_capacity(judgements.len() * 2);
+    let mut failure = rules.run(drain_rate, breaks, judgements, |point| points.push(point));
+    if mode == Mode::Taiko {
+        failure = points
+            .last()
+            .filter(|point| point.health <= 0.5)
+            .map(|point| point.time);
+    }
+    HealthCurve {
+        points,
+        failure,
+        drain_rate,
+    }
+}
+
+// How each judgement changes health in a mode.
+struct HealthRules {
+    mode: Mode,
+    hp_drain_rate: f64,
+    // osu!taiko scales its gains by the number of objects, so the bar fills up over the chart.
+    taiko_hit_multiplier: f64,
+    taiko_miss_multiplier: f64,
+}
+
+impl HealthRules {
+    fn new(mode: Mode, difficulty: &Difficulty, judgements: &[TimedJudgement]) -> Self {
+        let hp_drain_rate = ratio_to_f64(difficulty.hpdrain_rate);
+        let hits = judgements
+            .iter()
+            .filter(|j| j.judgement.max(mode) == Judgement::Great)
+            .count();
+        HealthRules {
+            mode,
+            hp_drain_rate,
+            taiko_hit_multiplier: 1.0
+                / (hits.max(1) as f64 * difficulty_range(hp_drain_rate, 0.5, 0.75, 0.98)),
+            taiko_miss_multiplier: difficulty_range(hp_drain_rate, 0.0018, 0.0075, 0.012),
+        }
+    }
+
+    fn starting_health(&self) -> f64 {
+        match self.mode {
+            Mode::Taiko => 0.0,
+            _ => 1.0,
+        }
+    }
+
+    fn increase(&self, judgement: Judgement) -> f64 {
+        match self.mode {
+            Mode::Mania => self.mania_increase(judgement),
+            Mode::Taiko => match judgement {
+                Judgement::Great | Judgement::Ok => {
+                    default_increase(judgement) / MAX_HEALTH_INCREASE * self.taiko_hit_multiplier
+                }
+                Judgement::Miss => -self.taiko_miss_multiplier,
+                _ => 0.0,
+            },
+            Mode::Osu | Mode::Catch => default_increase(judgement),
+        }
+    }
+
+    // Scaled by HP drain rate instead of draining over time.
+    fn mania_increase(&self, judgement: Judgement) -> f64 {
+        let drain_rate = self.hp_drain_rate;
+        match judgement {
+            Judgement::Perfect => 0.0055 - drain_rate * 0.0005,
+            Judgement::Great | Judgement::LargeTickHit => 0.005 - drain_rate * 0.0005,
+            Judgement::Good => 0.004 - drain_rate * 0.0004,
+            Judgement::Ok => 0.0,
+            Judgement::Meh => -(drain_rate + 1.0) * 0.0016,
+            Judgement::Miss => -(drain_rate + 1.0) * 0.0075,
+            // Missing a hold's end costs half as much as missing a note.
+            Judgement::LargeTickMiss => -(drain_rate + 1.0) * 0.00375,
+            _ => 0.0,
+        }
+    }
+
+    // Finds the drain rate by trying rates until a perfect play's lowest health is
+    // close enough to the target, moving half as far each time.
+    fn drain_rate(&self, breaks: &[Break], judgements: &[TimedJudgement]) -> f64 {
+        if judgements.is_empty() {
+            return 0.0;
+        }
+        let perfect: Vec<TimedJudgement> = judgements
+            .iter()
+            .map(|j| TimedJudgement {
+                time: j.time,
+                judgement: j.judgement.max(self.mode),
+            })
+            .collect();
+        let target = difficulty_range(self.hp_drain_rate, 0.95, 0.7, 0.3);
+        let mut adjustment = 1.0;
+        let mut rate = 1.0;
+        for _ in 0..MAX_DRAIN_ITERATIONS {
+            let mut lowest = self.starting_health();
+            self.run(rate, breaks, &perfect, |point| {
+                lowest = lowest.min(point.health)
+            });
+            if (lowest - target).abs() <= HEALTH_LENIENCY {
+                break;
+            }
+            adjustment *= 2.0;
+            rate += (lowest - target).signum() / adjustment;
+        }
+        rate
+    }
+
+    // Drains from each judgement to the next and applies them, passing health right before
+    // and right after each one to `on_point`. Returns when health first ran out.
+    fn run(
+        &self,
+        drain_rate: f64,
+        breaks: &[Break],
+        judgements: &[TimedJudgement],
+        mut on_point: impl FnMut(HealthPoint),
+    ) -> Option<f64> {
+        let mut health = self.starting_health();
+        let mut failure = None;
+        let mut last_time = judgements.first().map_or(0.0, |j| j.time);
+        for judgement in judgements {
+            let time = judgement.time;
+            for (start, end) in draining_intervals(breaks, last_time, time) {
+                let drained = (end - start) * drain_rate;
+                if failure.is_none() && drain_rate > 0.0 && health > 0.0 && drained >= health {
+                    failure = Some(start + health / drain_rate);
+                }
+                health = (health - drained).max(0.0);
+            }
+            on_point(HealthPoint { time, health });
+            health = (health + self.increase(judgement.judgement)).clamp(0.0, 1.0);
+            if failure.is_none() && health <= 0.0 && self.mode != Mode::Taiko {
+                failure = Some(time);
+            }
+            on_point(HealthPoint { time, health });
+            last_time = time;
+        }
+        failure
+    }
+}
+
+// What each judgement adds to the health bar, or takes away from it.
+fn default_increase(judgement: Judgement) -> f64 {
+    MAX_HEALTH_INCREASE
+        * match judgement {
+            Judgement::Perfect => 1.05,
+            Judgement::Great | Judgement::LargeTickHit | Judgement::LargeBonus => 1.0,
+            Judgement::Good => 0.75,
+            Judgement::Ok | Judgement::SmallTickHit | Judgement::SmallBonus => 0.5,
+            Judgement::Meh => -0.05,
+            Judgement::SmallTickMiss => -0.5,
+            Judgement::Miss | Judgement::LargeTickMiss => -1.0,
+        }
+}
+
+// The parts of the time from `start` to `end` that aren't in a break.
+fn draining_intervals(breaks: &[Break], start: f64, end: f64) -> Vec<(f64, f64)> {
+    let mut intervals = Vec::new();
+    let mut current = start;
+    for b in breaks {
+        let (break_start, break_end) = (b.start_time as f64, b.end_time as f64);
+        if break_end <= current || break_start >= end {
+            continue;
+        }
+        if break_start > current {
+            intervals.push((current, break_start));
+        }
+        current = break_end;
+    }
+    if current < end {
+        intervals.push((current, end));
+    }
+    intervals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::{beatmap, DIFFICULTY};
+
+    fn difficulty(hp_drain_rate: &str) -> Difficulty {
+        let difficulty =
+            DIFFICULTY.replace("HPDrainRate:5", &format!("HPDrainRate:{hp_drain_rate}"));
+        beatmap(0, &difficulty, "0,500,4,2,0,100,1,0", "").difficulty
+    }
+
+    fn judgements(judgements: &[(f64, Judgement)]) -> Vec<TimedJudgement> {
+        judgements
+            .iter()
+            .map(|&(time, judgement)| TimedJudgement { time, judgement })
+            .collect()
+    }
+
+    #[test]
+    fn breaks_dont_drain() {
+        let breaks = [Break {
+            start_time: 2000,
+            end_time: 10000,
+        }];
+        let judgements = judgements(&[
+            (0.0, Judgement::Great),
+            (1000.0, Judgement::Great),
+            (11000.0, Judgement::Great),
+        ]);
+        let curve = simulate_health(Mode::Osu, &difficulty("5"), &breaks, &judgements);
+        assert!(curve.drain_rate > 0.0);
+        assert_eq!(curve.failure, None);
+        // Only the second before and the second after the break drain.
+        let drained = curve.points[3].health - curve.points[4].health;
+        assert!(
+            (drained - curve.drain_rate * 2000.0).abs() < 1e-9,
+            "{drained}"
+        );
+        // The lowest health of a perfect play is close to HP drain rate's target.
+        assert!((curve.lowest().unwrap().health - 0.7).abs() <= HEALTH_LENIENCY);
+    }
+
+    #[test]
+    fn passive_drain_fails_between_judgements() {
+        let difficulty = difficulty("5");
+        let judgements = judgements(&[(0.0, Judgement::Great), (5000.0, Judgement::Great)]);
+        let rules = HealthRules::new(Mode::Osu, &difficulty, &judgements);
+        // Full health runs out a second after the first judgement.
+        let failure = rules.run(0.001, &[], &judgements, |_| {});
+        assert_eq!(failure, Some(1000.0));
+        assert_eq!(rules.run(0.0001, &[], &judgements, |_| {}), None);
+    }
+
+    #[test]
+    fn misses_fail_once_health_is_empty() {
+        let mut played = vec![(0.0, Judgement::Great)];
+        played.extend((1..=20).map(|i| (i as f64 * 1000.0, Judgement::Miss)));
+        let played = judgements(&played);
+        let rules = HealthRules::new(Mode::Osu, &difficulty("5"), &played);
+        // 20 misses of 0.05 empty the bar.
+        assert_eq!(rules.run(0.0, &[], &played, |_| {}), Some(20000.0));
+    }
+
+    #[test]
+    fn taiko_starts_empty_and_fails_at_half_health() {
+        let difficulty = difficulty("0");
+        let played = judgements(&[(0.0, Judgement::Great), (500.0, Judgement::Great)]);
+        let curve = simulate_health(Mode::Taiko, &difficulty, &[], &played);
+        assert_eq!(curve.points[0].health, 0.0);
+        assert_eq!(curve.drain_rate, 0.0);
+        assert_eq!(curve.points.last().unwrap().health, 1.0);
+        assert_eq!(curve.failure, None);
+
+        // Missing the first note leaves the bar at exactly half, which fails.
+        let played = judgements(&[(0.0, Judgement::Miss), (500.0, Judgement::Ok)]);
+        let curve = simulate_health(Mode::Taiko, &difficulty, &[], &played);
+        assert_eq!(curve.points.last().unwrap().health, 0.5);
+        assert_eq!(curve.failure, Some(500.0));
+    }
+
+    #[test]
+    fn mania_gains_and_losses_scale_with_hp_drain_rate() {
+        let played = judgements(&[
+            (0.0, Judgement::Miss),
+            (100.0, Judgement::Perfect),
+            (200.0, Judgement::Meh),
+            (300.0, Judgement::LargeTickMiss),
+        ]);
+        let curve = simulate_health(Mode::Mania, &difficulty("5"), &[], &played);
+        assert_eq!(curve.drain_rate, 0.0);
+        let after: Vec<f64> = curve
+            .points
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .map(|p| p.health)
+            .collect();
+        let expected = [
+            1.0 - 6.0 * 0.0075,
+            1.0 - 6.0 * 0.0075 + 0.003,
+            1.0 - 6.0 * 0.0075 + 0.003 - 6.0 * 0.0016,
+            1.0 - 6.0 * 0.0075 + 0.003 - 6.0 * 0.0016 - 6.0 * 0.00375,
+        ];
+        for (health, expected) in after.iter().zip(expected) {
+            assert!((health - expected).abs() < 1e-12, "{health} != {expected}");
+        }
+    }
+}
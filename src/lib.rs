@@ -1,9 +1,20 @@
+pub mod analysis;
 mod chart;
 mod collections;
 pub mod customization;
 pub mod difficulty;
 pub mod editor;
 pub mod filedata;
+pub mod gameplay;
+pub mod health;
 pub mod metadata;
+pub mod mods;
 pub mod parse;
 mod parts;
+pub mod performance;
+pub mod rate;
+pub mod rating;
+pub mod replay;
+pub mod score;
+mod utility;
+pub mod write;
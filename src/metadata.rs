@@ -1,3 +1,4 @@
+#[derive(Debug, Clone, PartialEq)]
 pub struct Metadata {
     pub title: Option<String>,
     pub title_unicode: Option<String>,
@@ -0,0 +1,544 @@
+use crate::chart::Chart;
+use crate::chart::Mode;
+use crate::collections::HitObject;
+use crate::difficulty::Difficulty;
+use crate::utility::ratio_to_f64;
+pub use num::rational::Ratio;
+use thiserror::Error;
+
+/// Game mods as the bitflags used by osu! stable, such as in replays and the API.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Mods(pub u32);
+
+impl Mods {
+    pub const NONE: Mods = Mods(0);
+    pub const NO_FAIL: Mods = Mods(1 << 0);
+    pub const EASY: Mods = Mods(1 << 1);
+    pub const TOUCH_DEVICE: Mods = Mods(1 << 2);
+    pub const HIDDEN: Mods = Mods(1 << 3);
+    pub const HARD_ROCK: Mods = Mods(1 << 4);
+    pub const SUDDEN_DEATH: Mods = Mods(1 << 5);
+    pub const DOUBLE_TIME: Mods = Mods(1 << 6);
+    pub const RELAX: Mods = Mods(1 << 7);
+    pub const HALF_TIME: Mods = Mods(1 << 8);
+    pub const NIGHTCORE: Mods = Mods(1 << 9); // Always set along with DoubleTime.
+    pub const FLASHLIGHT: Mods = Mods(1 << 10);
+    pub const AUTOPLAY: Mods = Mods(1 << 11);
+    pub const SPUN_OUT: Mods = Mods(1 << 12);
+    pub const AUTOPILOT: Mods = Mods(1 << 13);
+    pub const PERFECT: Mods = Mods(1 << 14); // Always set along with SuddenDeath.
+    pub const KEY_4: Mods = Mods(1 << 15);
+    pub const KEY_5: Mods = Mods(1 << 16);
+    pub const KEY_6: Mods = Mods(1 << 17);
+    pub const KEY_7: Mods = Mods(1 << 18);
+    pub const KEY_8: Mods = Mods(1 << 19);
+    pub const FADE_IN: Mods = Mods(1 << 20);
+    pub const RANDOM: Mods = Mods(1 << 21);
+    pub const CINEMA: Mods = Mods(1 << 22);
+    pub const TARGET: Mods = Mods(1 << 23);
+    pub const KEY_9: Mods = Mods(1 << 24);
+    pub const KEY_COOP: Mods = Mods(1 << 25);
+    pub const KEY_1: Mods = Mods(1 << 26);
+    pub const KEY_3: Mods = Mods(1 << 27);
+    pub const KEY_2: Mods = Mods(1 << 28);
+    pub const SCORE_V2: Mods = Mods(1 << 29);
+    pub const MIRROR: Mods = Mods(1 << 30);
+
+    pub const KEY_MODS: Mods = Mods(
+        Self::KEY_1.0
+            | Self::KEY_2.0
+            | Self::KEY_3.0
+            | Self::KEY_4.0
+            | Self::KEY_5.0
+            | Self::KEY_6.0
+            | Self::KEY_7.0
+            | Self::KEY_8.0
+            | Self::KEY_9.0,
+    );
+
+    // Pairs of mods that can't be enabled together.
+    const INCOMPATIBLE: [(Mods, Mods); 11] = [
+        (Self::EASY, Self::HARD_ROCK),
+        (Self::DOUBLE_TIME, Self::HALF_TIME),
+        (Self::NO_FAIL, Self::SUDDEN_DEATH),
+        (Self::NO_FAIL, Self::RELAX),
+        (Self::NO_FAIL, Self::AUTOPILOT),
+        (Self::SUDDEN_DEATH, Self::RELAX),
+        (Self::SUDDEN_DEATH, Self::AUTOPILOT),
+        (Self::RELAX, Self::AUTOPILOT),
+        (Self::AUTOPLAY, Self::RELAX),
+        (Self::AUTOPLAY, Self::AUTOPILOT),
+        (Self::SPUN_OUT, Self::AUTOPILOT),
+    ];
+
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// True if every mod in `other` is enabled.
+    pub fn contains(&self, other: Mods) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// True if any mod in `other` is enabled.
+    pub fn intersects(&self, other: Mods) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    pub fn insert(&mut self, other: Mods) {
+        self.0 |= other.0;
+    }
+
+    pub fn remove(&mut self, other: Mods) {
+        self.0 &= !other.0;
+    }
+
+    /// Checks for mods that can't be enabled together,
+    /// and for mods missing the mod they always come with.
+    pub fn validate(&self) -> Result<(), ModsError> {
+        for (a, b) in Self::INCOMPATIBLE {
+            if self.contains(a) && self.contains(b) {
+                return Err(ModsError::Incompatible {
+                    first: a,
+                    second: b,
+                });
+            }
+        }
+        if self.contains(Self::NIGHTCORE) && !self.contains(Self::DOUBLE_TIME) {
+            return Err(ModsError::Missing {
+                with: Self::NIGHTCORE,
+                missing: Self::DOUBLE_TIME,
+            });
+        }
+        if self.contains(Self::PERFECT) && !self.contains(Self::SUDDEN_DEATH) {
+            return Err(ModsError::Missing {
+                with: Self::PERFECT,
+                missing: Self::SUDDEN_DEATH,
+            });
+        }
+        if self.contains(Self::CINEMA) && !self.contains(Self::AUTOPLAY) {
+            return Err(ModsError::Missing {
+                with: Self::CINEMA,
+                missing: Self::AUTOPLAY,
+            });
+        }
+        if (self.0 & Self::KEY_MODS.0).count_ones() > 1 {
+            return Err(ModsError::KeyCount { mods: *self });
+        }
+        Ok(())
+    }
+
+    /// The number of columns a key mod picks for osu!mania converts.
+    pub fn key_count(&self) -> Option<i64> {
+        [
+            Self::KEY_1,
+            Self::KEY_2,
+            Self::KEY_3,
+            Self::KEY_4,
+            Self::KEY_5,
+            Self::KEY_6,
+            Self::KEY_7,
+            Self::KEY_8,
+            Self::KEY_9,
+        ]
+        .iter()
+        .position(|key| self.contains(*key))
+        .map(|i| i as i64 + 1)
+    }
+
+    /// How fast the song plays.
+    pub fn clock_rate(&self) -> f64 {
+        if self.intersects(Self::DOUBLE_TIME | Self::NIGHTCORE) {
+            1.5
+        } else if self.contains(Self::HALF_TIME) {
+            0.75
+        } else {
+            1.0
+        }
+    }
+
+    /// Applies Hard Rock and Easy to the difficulty settings.
+    ///
+    /// In osu!mania, they change the hit windows instead of overall difficulty,
+    /// see [`Mods::hit_window_multiplier`].
+    pub fn apply_to_difficulty(&self, difficulty: &Difficulty, mode: Mode) -> Difficulty {
+        let mut difficulty = *difficulty;
+        let ten = Ratio::from_integer(10);
+        if self.contains(Self::HARD_ROCK) {
+            let scale = |value: Ratio<i64>, factor: Ratio<i64>| (value * factor).min(ten);
+            if mode != Mode::Mania {
+                difficulty.circle_size = scale(difficulty.circle_size, Ratio::new(13, 10));
+                difficulty.overall_difficulty =
+                    scale(difficulty.overall_difficulty, Ratio::new(14, 10));
+            }
+            difficulty.approach_rate = scale(difficulty.approach_rate, Ratio::new(14, 10));
+            difficulty.hpdrain_rate = scale(difficulty.hpdrain_rate, Ratio::new(14, 10));
+        }
+        if self.contains(Self::EASY) {
+            let half = Ratio::new(1, 2);
+            if mode != Mode::Mania {
+                difficulty.circle_size *= half;
+                difficulty.overall_difficulty *= half;
+            }
+            difficulty.approach_rate *= half;
+            difficulty.hpdrain_rate *= half;
+        }
+        difficulty
+    }
+
+    /// How Hard Rock and Easy scale osu!mania's hit windows.
+    pub fn hit_window_multiplier(&self, mode: Mode) -> f64 {
+        match mode {
+            Mode::Mania if self.contains(Self::HARD_ROCK) => 1.0 / 1.4,
+            Mode::Mania if self.contains(Self::EASY) => 1.4,
+            _ => 1.0,
+        }
+    }
+
+    /// How much the mods multiply the score by in stable.
+    ///
+    /// Unranked mods like Relax and Autopilot count as 1.
+    pub fn score_multiplier(&self, mode: Mode) -> f64 {
+        let (hard_rock, double_time) = match mode {
+            Mode::Osu | Mode::Taiko => (1.06, 1.12),
+            Mode::Catch => (1.12, 1.06),
+            // Only mods that make the chart easier change the score in osu!mania.
+            Mode::Mania => (1.0, 1.0),
+        };
+        let (hidden, flashlight, half_time) = match mode {
+            Mode::Mania => (1.0, 1.0, 0.5),
+            _ => (1.06, 1.12, 0.3),
+        };
+        [
+            (Self::NO_FAIL, 0.5),
+            (Self::EASY, 0.5),
+            (Self::HALF_TIME, half_time),
+            (Self::HARD_ROCK, hard_rock),
+            (Self::DOUBLE_TIME, double_time),
+            (Self::HIDDEN, hidden),
+            (Self::FLASHLIGHT, flashlight),
+            (Self::SPUN_OUT, 0.9),
+        ]
+        .iter()
+        .filter(|(mods, _)| self.contains(*mods))
+        .map(|(_, multiplier)| multiplier)
+        .product()
+    }
+
+    /// Produces a copy of the beatmap as played with these mods.
+    ///
+    /// Difficulty Adjust has no stable mod ID, so it's passed separately
+    /// and replaces the difficulty settings before anything else.
+    pub fn apply(
+        &self,
+        chart: &Chart,
+        difficulty: &Difficulty,
+        difficulty_adjust: Option<&DifficultyAdjust>,
+    ) -> Result<Adjusted, ModsError> {
+        self.validate()?;
+        let original_difficulty = *difficulty;
+        let mut difficulty = *difficulty;
+        if let Some(adjust) = difficulty_adjust {
+            if self.intersects(Self::EASY | Self::HARD_ROCK) {
+                return Err(ModsError::DifficultyAdjust { mods: *self });
+            }
+            difficulty = adjust.apply(&difficulty);
+        }
+        let difficulty = self.apply_to_difficulty(&difficulty, chart.mode);
+
+        let mut chart = chart.clone();
+        // Hard Rock flips the playfield vertically in osu!standard.
+        if self.contains(Self::HARD_ROCK) && chart.mode == Mode::Osu {
+            for object in chart.hit_objects.iter_mut() {
+                flip_vertically(object);
+            }
+        }
+
+        let clock_rate = self.clock_rate();
+        let approach_rate = rate_adjusted_approach_rate(&difficulty, clock_rate);
+        let overall_difficulty = rate_adjusted_overall_difficulty(
+            &difficulty,
+            chart.mode,
+            clock_rate,
+            self.hit_window_multiplier(chart.mode),
+        );
+        Ok(Adjusted {
+            mods: *self,
+            chart,
+            original_difficulty,
+            difficulty,
+            clock_rate,
+            approach_rate,
+            overall_difficulty,
+        })
+    }
+}
+
+impl std::ops::BitOr for Mods {
+    type Output = Mods;
+    fn bitor(self, other: Mods) -> Mods {
+        Mods(self.0 | other.0)
+    }
+}
+
+impl std::ops::BitAnd for Mods {
+    type Output = Mods;
+    fn bitand(self, other: Mods) -> Mods {
+        Mods(self.0 & other.0)
+    }
+}
+
+/// The lazer Difficulty Adjust mod, which overrides difficulty settings.
+/// Settings left as `None` keep the beatmap's value.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct DifficultyAdjust {
+    pub circle_size: Option<Ratio<i64>>,
+    pub hpdrain_rate: Option<Ratio<i64>>,
+    pub overall_difficulty: Option<Ratio<i64>>,
+    pub approach_rate: Option<Ratio<i64>>,
+}
+
+impl DifficultyAdjust {
+    pub fn apply(&self, difficulty: &Difficulty) -> Difficulty {
+        Difficulty {
+            circle_size: self.circle_size.unwrap_or(difficulty.circle_size),
+            hpdrain_rate: self.hpdrain_rate.unwrap_or(difficulty.hpdrain_rate),
+            overall_difficulty: self
+                .overall_difficulty
+                .unwrap_or(difficulty.overall_difficulty),
+            approach_rate: self.approach_rate.unwrap_or(difficulty.approach_rate),
+        }
+    }
+}
+
+/// A beatmap as played with mods.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Adjusted {
+    pub mods: Mods,
+    pub chart: Chart,
+    /// The beatmap's own difficulty settings, which conversions between modes are based on.
+    pub original_difficulty: Difficulty,
+    /// Difficulty settings after Difficulty Adjust, Hard Rock and Easy.
+    /// Objects keep their original times, so these aren't rate adjusted.
+    pub difficulty: Difficulty,
+    pub clock_rate: f64,
+    /// The approach rate that the preempt time feels like once the rate is applied.
+    pub approach_rate: f64,
+    /// The overall difficulty that the hit windows feel like once the rate is applied.
+    /// osu!catch doesn't judge timing, so its value is left unchanged.
+    pub overall_difficulty: f64,
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ModsError {
+    #[error("{first:?} can't be enabled together with {second:?}")]
+    Incompatible { first: Mods, second: Mods },
+    #[error("{with:?} requires {missing:?}")]
+    Missing { with: Mods, missing: Mods },
+    #[error("more than one key mod in {mods:?}")]
+    KeyCount { mods: Mods },
+    #[error("difficulty adjust can't be used with easy or hard rock in {mods:?}")]
+    DifficultyAdjust { mods: Mods },
+}
+
+fn flip_vertically(object: &mut HitObject) {
+    match object {
+        HitObject::Circle(circle) => circle.y = 384 - circle.y,
+        HitObject::Slider(slider) => {
+            slider.y = 384 - slider.y;
+            for point in slider.curve.points.iter_mut() {
+                point.1 = 384 - point.1;
+            }
+        }
+        HitObject::Spinner(spinner) => spinner.y = 384 - spinner.y,
+        HitObject::ManiaHold(hold) => hold.y = 384 - hold.y,
+    }
+}
+
+fn rate_adjusted_approach_rate(difficulty: &Difficulty, clock_rate: f64) -> f64 {
+    let preempt = difficulty.preempt() / clock_rate;
+    if preempt > 1200.0 {
+        (1800.0 - preempt) / 120.0
+    } else {
+        5.0 + (1200.0 - preempt) / 150.0
+    }
+}
+
+fn rate_adjusted_overall_difficulty(
+    difficulty: &Difficulty,
+    mode: Mode,
+    clock_rate: f64,
+    window_multiplier: f64,
+) -> f64 {
+    match difficulty.hit_windows(mode) {
+        Some(windows) => {
+            let great = windows.great * window_multiplier / clock_rate;
+            match mode {
+                Mode::Osu => (80.0 - great) / 6.0,
+                Mode::Taiko => (50.0 - great) / 3.0,
+                _ => (64.0 - great) / 3.0,
+            }
+        }
+        None => ratio_to_f64(difficulty.overall_difficulty),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::{beatmap, DIFFICULTY};
+
+    fn assert_near(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn invalid_combinations_are_rejected() {
+        assert_eq!(
+            (Mods::EASY | Mods::HARD_ROCK).validate(),
+            Err(ModsError::Incompatible {
+                first: Mods::EASY,
+                second: Mods::HARD_ROCK
+            })
+        );
+        assert_eq!(
+            (Mods::HIDDEN | Mods::HALF_TIME | Mods::DOUBLE_TIME).validate(),
+            Err(ModsError::Incompatible {
+                first: Mods::DOUBLE_TIME,
+                second: Mods::HALF_TIME
+            })
+        );
+        assert_eq!(
+            Mods::NIGHTCORE.validate(),
+            Err(ModsError::Missing {
+                with: Mods::NIGHTCORE,
+                missing: Mods::DOUBLE_TIME
+            })
+        );
+        assert_eq!(
+            Mods::PERFECT.validate(),
+            Err(ModsError::Missing {
+                with: Mods::PERFECT,
+                missing: Mods::SUDDEN_DEATH
+            })
+        );
+        let keys = Mods::KEY_4 | Mods::KEY_7;
+        assert_eq!(keys.validate(), Err(ModsError::KeyCount { mods: keys }));
+        assert_eq!(
+            (Mods::DOUBLE_TIME | Mods::NIGHTCORE | Mods::SUDDEN_DEATH | Mods::PERFECT).validate(),
+            Ok(())
+        );
+
+        let fixture = beatmap(0, DIFFICULTY, "0,500,4,2,0,100,1,0", "");
+        let adjust = DifficultyAdjust::default();
+        let error = Mods::HARD_ROCK
+            .apply(&fixture.chart, &fixture.difficulty, Some(&adjust))
+            .unwrap_err();
+        assert_eq!(
+            error,
+            ModsError::DifficultyAdjust {
+                mods: Mods::HARD_ROCK
+            }
+        );
+    }
+
+    #[test]
+    fn hard_rock_and_easy_scale_the_difficulty() {
+        let fixture = beatmap(0, DIFFICULTY, "0,500,4,2,0,100,1,0", "");
+        let difficulty = fixture.difficulty;
+        let values = |difficulty: Difficulty| {
+            (
+                difficulty.circle_size,
+                difficulty.overall_difficulty,
+                difficulty.approach_rate,
+                difficulty.hpdrain_rate,
+            )
+        };
+
+        // Overall difficulty and approach rate are capped at 10.
+        let hard_rock = Mods::HARD_ROCK.apply_to_difficulty(&difficulty, Mode::Osu);
+        assert_eq!(
+            values(hard_rock),
+            (
+                Ratio::new(26, 5),
+                Ratio::from_integer(10),
+                Ratio::from_integer(10),
+                Ratio::from_integer(7)
+            )
+        );
+        let easy = Mods::EASY.apply_to_difficulty(&difficulty, Mode::Osu);
+        assert_eq!(
+            values(easy),
+            (
+                Ratio::from_integer(2),
+                Ratio::from_integer(4),
+                Ratio::new(9, 2),
+                Ratio::new(5, 2)
+            )
+        );
+
+        // osu!mania keeps its key count and scales the hit windows instead.
+        let mania = Mods::HARD_ROCK.apply_to_difficulty(&difficulty, Mode::Mania);
+        assert_eq!(mania.circle_size, difficulty.circle_size);
+        assert_eq!(mania.overall_difficulty, difficulty.overall_difficulty);
+        assert_near(
+            Mods::HARD_ROCK.hit_window_multiplier(Mode::Mania),
+            1.0 / 1.4,
+        );
+        assert_near(Mods::EASY.hit_window_multiplier(Mode::Mania), 1.4);
+        assert_near(Mods::HARD_ROCK.hit_window_multiplier(Mode::Osu), 1.0);
+    }
+
+    #[test]
+    fn hard_rock_flips_osu_charts() {
+        let objects = "100,50,0,1,0,0:0:0:0:\n100,50,1000,2,0,B|200:100|300:0,1,250";
+        let osu = beatmap(0, DIFFICULTY, "0,500,4,2,0,100,1,0", objects);
+        let adjusted = Mods::HARD_ROCK
+            .apply(&osu.chart, &osu.difficulty, None)
+            .unwrap();
+        let HitObject::Slider(slider) = &adjusted.chart.hit_objects[1] else {
+            panic!("not a slider");
+        };
+        assert_eq!(adjusted.chart.hit_objects[0].position(), (100.0, 334.0));
+        assert_eq!((slider.x, slider.y), (100, 334));
+        assert_eq!(slider.curve.points, vec![(200, 284), (300, 384)]);
+
+        let taiko = beatmap(1, DIFFICULTY, "0,500,4,2,0,100,1,0", objects);
+        let adjusted = Mods::HARD_ROCK
+            .apply(&taiko.chart, &taiko.difficulty, None)
+            .unwrap();
+        assert_eq!(adjusted.chart, taiko.chart);
+    }
+
+    #[test]
+    fn rate_mods_adjust_approach_rate_and_overall_difficulty() {
+        let fixture = beatmap(0, DIFFICULTY, "0,500,4,2,0,100,1,0", "");
+        let adjusted = |mods: Mods, mode: Mode| {
+            let mut chart = fixture.chart.clone();
+            chart.mode = mode;
+            mods.apply(&chart, &fixture.difficulty, None).unwrap()
+        };
+
+        let nomod = adjusted(Mods::NONE, Mode::Osu);
+        assert_near(nomod.approach_rate, 9.0);
+        assert_near(nomod.overall_difficulty, 8.0);
+
+        let double_time = adjusted(Mods::DOUBLE_TIME, Mode::Osu);
+        assert_eq!(double_time.clock_rate, 1.5);
+        assert_near(double_time.approach_rate, 5.0 + 800.0 / 150.0);
+        assert_near(double_time.overall_difficulty, (80.0 - 32.0 / 1.5) / 6.0);
+        assert_eq!(double_time.difficulty, fixture.difficulty);
+
+        let half_time = adjusted(Mods::HALF_TIME, Mode::Osu);
+        assert_eq!(half_time.clock_rate, 0.75);
+        assert_near(half_time.approach_rate, 5.0 + 400.0 / 150.0);
+        assert_near(half_time.overall_difficulty, (80.0 - 32.0 / 0.75) / 6.0);
+
+        let taiko = adjusted(Mods::DOUBLE_TIME, Mode::Taiko);
+        assert_near(taiko.overall_difficulty, (50.0 - 26.0 / 1.5) / 3.0);
+        let mania = adjusted(Mods::HARD_ROCK, Mode::Mania);
+        assert_near(mania.overall_difficulty, (64.0 - 40.0 / 1.4) / 3.0);
+        let catch = adjusted(Mods::DOUBLE_TIME, Mode::Catch);
+        assert_near(catch.overall_difficulty, 8.0);
+    }
+}
@@ -1,4 +1,6 @@
+mod colours;
 pub mod errors;
+mod events;
 
 pub use super::chart::*;
 pub use super::customization::*;
@@ -9,6 +11,7 @@ pub use super::metadata::*;
 use bitvec::prelude::*;
 pub use errors::*;
 
+#[allow(clippy::type_complexity)]
 pub fn parse_str(
     s: &str,
     chart: bool, //                   [General]                  [Difficulty]        [TimingPoints]         [HitObjects]
@@ -52,7 +55,8 @@ pub fn parse_str(
     let mut samples_match_playback_rate = None;
     let mut backgrounds = Vec::new();
     let mut breaks = Vec::new();
-    let mut colors = Vec::new();
+    let mut other_events = Vec::new();
+    let mut colours = Colours::default();
     // Difficulty section
     let mut circle_size = None;
     let mut hpdrain_rate = None;
@@ -94,28 +98,36 @@ pub fn parse_str(
         "[HitObjects]",
     ];
     let mut section = "[Preamble]";
+    // Lines keep their indentation for storyboard commands, see `events::parse_line`.
     let mut lines = s
         .lines()
-        .map(|l| l.trim())
-        .filter(|l| !l.is_empty())
+        .map(|l| l.trim_end())
+        .filter(|l| !l.trim().is_empty())
         .peekable();
     let mut current_line = lines.next();
-    while current_line != None {
-        let line = current_line.unwrap();
+    while let Some(raw_line) = current_line {
+        let line = raw_line.trim();
         // Go the the next section if we find a section header.
         if let Some(position) = sections
             .iter()
             // Hopfully filtering by ascii fixes some encoding issues.
             .position(|x| *x == line.chars().filter(|l| l.is_ascii()).collect::<String>())
         {
-            section = sections[position];
-            sections = sections.drain(..position).collect();
+            // Each section is only read once.
+            section = sections.remove(position);
+            current_line = lines.next();
             continue;
         } // Otherwise try to parse the line.
+        if line.starts_with("//") {
+            current_line = lines.next();
+            continue;
+        }
+        // Lines for collections that weren't requested are skipped.
         let mut parsed = false;
         match section {
             "[Preamble]" => 'preamble: {
                 if !filedata {
+                    parsed = true;
                     break 'preamble;
                 }
                 // Check for version
@@ -132,6 +144,7 @@ pub fn parse_str(
             }
             "[General]" => 'general: {
                 if !chart && !customization && !filedata {
+                    parsed = true;
                     break 'general;
                 };
                 let (key, value) = match line.split_once(':') {
@@ -149,6 +162,10 @@ pub fn parse_str(
                             Ok(3) => mode = Some(Mode::Mania),
                             _ => break 'general,
                         },
+                        "StackLeniency" => match from_str_ratio(value) {
+                            Ok(value) => stack_leniency = Some(value),
+                            _ => break 'general,
+                        },
                         _ => key_matched = false,
                     };
                 };
@@ -162,19 +179,19 @@ pub fn parse_str(
                             "Drum" => sample_set = Some(SampleSet::Drum),
                             _ => break 'general,
                         },
-                        "LetterboxInBreaks" => match value.parse() {
+                        "LetterboxInBreaks" => match from_str_bool(value) {
                             Ok(value) => letterbox_in_breaks = Some(value),
                             _ => break 'general,
                         },
-                        "StoryFireInFront" => match value.parse() {
+                        "StoryFireInFront" => match from_str_bool(value) {
                             Ok(value) => story_fire_in_front = Some(value),
                             _ => break 'general,
                         },
-                        "UseSkinSprites" => match value.parse() {
+                        "UseSkinSprites" => match from_str_bool(value) {
                             Ok(value) => use_skin_sprites = Some(value),
                             _ => break 'general,
                         },
-                        "AlwaysShowPlayField" => match value.parse() {
+                        "AlwaysShowPlayField" => match from_str_bool(value) {
                             Ok(value) => always_show_play_field = Some(value),
                             _ => break 'general,
                         },
@@ -186,9 +203,9 @@ pub fn parse_str(
                         },
                         "SkinPreference" => match value.is_empty() {
                             false => skin_preference = Some(value.into()),
-                            _ => break 'general,
+                            _ => skin_preference = None,
                         },
-                        "EpilepsyWarning" => match value.parse() {
+                        "EpilepsyWarning" => match from_str_bool(value) {
                             Ok(value) => epilepsy_warning = Some(value),
                             _ => break 'general,
                         },
@@ -199,15 +216,15 @@ pub fn parse_str(
                             Ok(3) => countdown = Some(Countdown::Double),
                             _ => break 'general,
                         },
-                        "SpecialStyle" => match value.parse() {
+                        "SpecialStyle" => match from_str_bool(value) {
                             Ok(value) => special_style = Some(value),
                             _ => break 'general,
                         },
-                        "WidescreenStoryboard" => match value.parse() {
+                        "WidescreenStoryboard" => match from_str_bool(value) {
                             Ok(value) => widescreen_storyboard = Some(value),
                             _ => break 'general,
                         },
-                        "SamplesMatchPlaybackRate" => match value.parse() {
+                        "SamplesMatchPlaybackRate" => match from_str_bool(value) {
                             Ok(value) => samples_match_playback_rate = Some(value),
                             _ => break 'general,
                         },
@@ -215,7 +232,6 @@ pub fn parse_str(
                     };
                 };
                 if filedata && !key_matched {
-                    key_matched = true;
                     match key {
                         "AudioFilename" => match value.is_empty() {
                             false => audio_filename = Some(value.into()),
@@ -227,7 +243,7 @@ pub fn parse_str(
                         },
                         "AudioHash" => match value.is_empty() {
                             false => audio_hash = Some(value.into()),
-                            _ => break 'general,
+                            _ => audio_hash = None,
                         },
                         "PreviewTime" => match value.parse() {
                             Ok(value) => preview_time = Some(value),
@@ -237,13 +253,16 @@ pub fn parse_str(
                             Ok(value) => countdown_offset = Some(value),
                             _ => break 'general,
                         },
-                        _ => key_matched = false,
+                        _ => {}
                     };
                 };
-                parsed = key_matched;
+                // Keys that aren't modelled, or belong to collections that
+                // weren't requested, are skipped.
+                parsed = true;
             }
             "[Editor]" => 'editor: {
                 if !editor {
+                    parsed = true;
                     break 'editor;
                 };
                 let (key, value) = match line.split_once(':') {
@@ -251,38 +270,39 @@ pub fn parse_str(
                     _ => break 'editor,
                 };
                 match key {
-                    "[Bookmarks]" => {
+                    "Bookmarks" => {
                         let mut tokens: Vec<i64> = vec![];
-                        for raw_token in value.split(',') {
-                            match raw_token.parse() {
+                        for raw_token in value.split(',').filter(|t| !t.trim().is_empty()) {
+                            match raw_token.trim().parse() {
                                 Ok(token) => tokens.push(token),
                                 _ => break 'editor,
                             }
                         }
                         bookmarks.append(&mut tokens);
                     }
-                    "[DistanceSpacing]" => match from_str_ratio(value) {
+                    "DistanceSpacing" => match from_str_ratio(value) {
                         Ok(value) => distance_spacing = Some(value),
                         _ => break 'editor,
                     },
-                    "[BeatDivisor]" => match value.parse() {
+                    "BeatDivisor" => match value.parse() {
                         Ok(value) => beat_divisor = Some(value),
                         _ => break 'editor,
                     },
-                    "[GridSize]" => match value.parse() {
+                    "GridSize" => match value.parse() {
                         Ok(value) => grid_size = Some(value),
                         _ => break 'editor,
                     },
-                    "[TimelineZoom]" => match from_str_ratio(value) {
+                    "TimelineZoom" => match from_str_ratio(value) {
                         Ok(value) => timeline_zoom = Some(value),
                         _ => break 'editor,
                     },
-                    _ => break 'editor,
+                    _ => {} // Not modelled.
                 };
                 parsed = true;
             }
             "[Metadata]" => 'metadata: {
                 if !metadata {
+                    parsed = true;
                     break 'metadata;
                 };
                 let (key, value) = match line.split_once(':') {
@@ -298,8 +318,7 @@ pub fn parse_str(
                     "Version" => version = Some(value.into()),
                     "Source" => source = Some(value.into()),
                     "Tags" => {
-                        for token in value.split(',') {
-                            // Check delimiter TODO
+                        for token in value.split_whitespace() {
                             tags.push(token.into());
                         }
                     }
@@ -311,12 +330,13 @@ pub fn parse_str(
                         Ok(value) => beatmap_set_id = Some(value),
                         _ => break 'metadata,
                     },
-                    _ => break 'metadata,
+                    _ => {} // Not modelled.
                 };
                 parsed = true;
             }
-            "Difficulty" => 'difficulty: {
+            "[Difficulty]" => 'difficulty: {
                 if !chart && !difficulty {
+                    parsed = true;
                     break 'difficulty;
                 };
                 let (key, value) = match line.split_once(':') {
@@ -327,10 +347,6 @@ pub fn parse_str(
                 if chart {
                     key_matched = true;
                     match key {
-                        "StackLeniency" => match from_str_ratio(value) {
-                            Ok(value) => stack_leniency = Some(value),
-                            _ => break 'difficulty,
-                        },
                         "SliderMultiplier" => match from_str_ratio(value) {
                             Ok(value) => slider_multiplier = Some(value),
                             _ => break 'difficulty,
@@ -343,68 +359,50 @@ pub fn parse_str(
                     }
                 }
                 if difficulty && !key_matched {
-                    key_matched = true;
                     match key {
-                        "CircleSize" => match from_str_one_decimal(value) {
+                        "CircleSize" => match from_str_ratio(value) {
                             Ok(value) => circle_size = Some(value),
                             _ => break 'difficulty,
                         },
-                        "HPDrainRate" => match from_str_one_decimal(value) {
+                        "HPDrainRate" => match from_str_ratio(value) {
                             Ok(value) => hpdrain_rate = Some(value),
                             _ => break 'difficulty,
                         },
-                        "OverallDifficulty" => match from_str_one_decimal(value) {
+                        "OverallDifficulty" => match from_str_ratio(value) {
                             Ok(value) => overall_difficulty = Some(value),
                             _ => break 'difficulty,
                         },
-                        "ApproachRate" => match from_str_one_decimal(value) {
+                        "ApproachRate" => match from_str_ratio(value) {
                             Ok(value) => approach_rate = Some(value),
                             _ => break 'difficulty,
                         },
-                        _ => key_matched = false,
+                        _ => {}
                     }
                 }
-                parsed = key_matched;
+                // Keys that aren't modelled are skipped.
+                parsed = true;
             }
             "[Events]" => 'events: {
-                /* // Basically broken TODO
                 if !customization {
+                    parsed = true;
                     break 'events;
                 };
-
-                let mut split = line.splitn(2, ',');
-                let event_type = match split.next() {
-                    Some(value) => value,
+                match events::parse_line(raw_line) {
+                    Some(events::Event::Background(background)) => backgrounds.push(background),
+                    Some(events::Event::Break(event)) => breaks.push(event),
+                    Some(events::Event::Other(event)) => other_events.push(event),
                     _ => break 'events,
                 };
-                let start_time = match split.next() {
-                    // This is broken for Storyboards TODO
-                    Some(value) => match value.parse() {
-                        Ok(time) => time,
-                        _ => break 'events,
-                    },
-                    _ => break 'events,
-                };
-                let event_params = match split.next() {
-                    Some(value) => value,
-                    _ => break 'events,
-                };
-                match event_type {
-                    "0" => {}           // Parse background TODO
-                    "1" | "Video" => {} // Parse video TODO
-                    "2" => {}           // Parse break TODO
-                    // Enumerate and parse all events TODO
-                    _ => break 'events,
-                }
-                parsed = true; */
+                parsed = true;
             }
             "[TimingPoints]" => 'timing_points: {
                 if !chart {
+                    parsed = true;
                     break 'timing_points;
                 };
                 let mut tokens = line.split(',').map(|t| t.trim());
                 let time = match tokens.next() {
-                    Some(token) => match token.trim().parse() {
+                    Some(token) => match from_str_time(token) {
                         Ok(time) => time,
                         _ => break 'timing_points,
                     },
@@ -449,7 +447,7 @@ pub fn parse_str(
                     _ => 100,
                 };
                 let uninherited = match tokens.next() {
-                    Some(token) => match token.trim().parse() {
+                    Some(token) => match from_str_bool(token) {
                         Ok(uninherited) => uninherited,
                         _ => break 'timing_points,
                     },
@@ -461,7 +459,7 @@ pub fn parse_str(
                             let bits = number.view_bits::<Lsb0>();
                             Effects {
                                 kiai: bits[0],
-                                ommit_barline: bits[1],
+                                ommit_barline: bits[3],
                             }
                         }
                         _ => break 'timing_points,
@@ -484,33 +482,31 @@ pub fn parse_str(
                 parsed = true;
             }
             "[Colours]" => 'colours: {
-                // More research needs to be done on this field TODO
                 if !customization {
+                    parsed = true;
                     break 'colours;
                 };
-                let mut rgb = match line.split_once(':') {
-                    Some((_, rhs)) => rhs.split(','),
-                    _ => break 'colours,
-                };
-                let (red, green, blue) = match (rgb.next(), rgb.next(), rgb.next()) {
-                    (Some(r), Some(g), Some(b)) => match (r.parse(), g.parse(), b.parse()) {
-                        (Ok(r), Ok(g), Ok(b)) => (r, g, b),
-                        _ => break 'colours,
-                    },
+                match colours::parse_line(line) {
+                    Some((key, colour)) => colours.entries.push((key, colour)),
                     _ => break 'colours,
                 };
-                colors.push(Color { red, green, blue });
                 parsed = true;
             }
             "[HitObjects]" => 'hit_objects: {
                 if !chart {
+                    parsed = true;
                     break 'hit_objects;
                 };
                 let mut tokens = line.split(',').map(|t| t.trim());
                 let (x, y, time, flags) =
                     match (tokens.next(), tokens.next(), tokens.next(), tokens.next()) {
                         (Some(x), Some(y), Some(time), Some(flags)) => {
-                            match (x.parse(), y.parse(), time.parse(), flags.parse::<u8>()) {
+                            match (
+                                from_str_time(x),
+                                from_str_time(y),
+                                from_str_time(time),
+                                flags.parse::<u8>(),
+                            ) {
                                 (Ok(x), Ok(y), Ok(time), Ok(flags)) => (x, y, time, flags),
                                 _ => break 'hit_objects,
                             }
@@ -518,12 +514,11 @@ pub fn parse_str(
                         _ => break 'hit_objects,
                     };
                 let flag_bits = flags.view_bits::<Lsb0>();
-                let object_type = match {
-                    flag_bits[0] as usize * 2_usize.pow(0)
-                        + flag_bits[1] as usize * 2_usize.pow(1)
-                        + flag_bits[3] as usize * 2_usize.pow(3)
-                        + flag_bits[7] as usize * 2_usize.pow(7)
-                } {
+                let object_type = match flag_bits[0] as usize * 2_usize.pow(0)
+                    + flag_bits[1] as usize * 2_usize.pow(1)
+                    + flag_bits[3] as usize * 2_usize.pow(3)
+                    + flag_bits[7] as usize * 2_usize.pow(7)
+                {
                     1 => ObjectType::Circle,      // 2^0
                     2 => ObjectType::Slider,      // 2^1
                     8 => ObjectType::Spinner,     // 2^3
@@ -569,6 +564,9 @@ pub fn parse_str(
                     }
                 }
                 fn parse_hit_sample(sample: &str) -> Option<HitSample> {
+                    if sample.is_empty() {
+                        return Some(HitSample::default());
+                    }
                     let mut tokens = sample.split(':').map(|t| t.trim());
                     let normal_set = match tokens.next() {
                         Some(value) => match parse_sample_set(value) {
@@ -601,7 +599,7 @@ pub fn parse_str(
                     let filename = match tokens.next() {
                         Some(value) => match value.is_empty() {
                             false => Some(value.into()),
-                            _ => return None,
+                            _ => None,
                         },
                         _ => None,
                     };
@@ -642,16 +640,14 @@ pub fn parse_str(
                             _ => break 'hit_objects,
                         };
                         let mut curve_points: Vec<(i64, i64)> = vec![];
-                        let mut point = curve_split.next();
-                        while point != None {
-                            match point.unwrap().split_once(':') {
+                        for point in curve_split {
+                            match point.split_once(':') {
                                 Some((x, y)) => match (x.trim().parse(), y.trim().parse()) {
                                     (Ok(x), Ok(y)) => curve_points.push((x, y)),
                                     _ => break 'hit_objects,
                                 },
                                 _ => break 'hit_objects,
                             }
-                            point = curve_split.next();
                         }
                         let curve = Curve {
                             _type: curve_type,
@@ -666,27 +662,19 @@ pub fn parse_str(
                             }
                             _ => break 'hit_objects,
                         };
-                        let mut sounds_split = match tokens.next() {
-                            Some(token) => token.split('|').map(|t| t.trim()),
-                            _ => break 'hit_objects,
-                        };
+                        // Edge sounds, edge sets and the hit sample are optional.
+                        let sounds_split = tokens.next().unwrap_or("").split('|').map(|t| t.trim());
                         let mut edge_sounds: Vec<HitSound> = vec![];
-                        let mut sound = sounds_split.next();
-                        while sound != None {
-                            match sound.unwrap().parse::<u8>() {
+                        for sound in sounds_split.filter(|t| !t.is_empty()) {
+                            match sound.parse::<u8>() {
                                 Ok(number) => edge_sounds.push(parse_hit_sound(number)),
                                 _ => break 'hit_objects,
                             }
-                            sound = sounds_split.next();
                         }
-                        let mut sets_split = match tokens.next() {
-                            Some(token) => token.split('|').map(|t| t.trim()),
-                            _ => break 'hit_objects,
-                        };
+                        let sets_split = tokens.next().unwrap_or("").split('|').map(|t| t.trim());
                         let mut edge_sets: Vec<(SampleSet, SampleSet)> = vec![];
-                        let set = sets_split.next();
-                        while set != None {
-                            match set.unwrap().split_once(':') {
+                        for set in sets_split.filter(|t| !t.is_empty()) {
+                            match set.split_once(':') {
                                 Some((normal, addition)) => {
                                     match (parse_sample_set(normal), parse_sample_set(addition)) {
                                         (Some(normal), Some(addition)) => {
@@ -720,7 +708,7 @@ pub fn parse_str(
                         let (end_time, hit_sample) =
                             match (tokens.next(), tokens.next().unwrap_or("")) {
                                 (Some(time), sample) => {
-                                    match (time.parse(), parse_hit_sample(sample)) {
+                                    match (from_str_time(time), parse_hit_sample(sample)) {
                                         (Ok(time), Some(sample)) => (time, sample),
                                         _ => break 'hit_objects,
                                     }
@@ -741,7 +729,7 @@ pub fn parse_str(
                         let (end_time, hit_sample) = match tokens.next() {
                             Some(token) => match token.split_once(':') {
                                 Some((time, sample)) => {
-                                    match (time.parse(), parse_hit_sample(sample)) {
+                                    match (from_str_time(time), parse_hit_sample(sample)) {
                                         (Ok(time), Some(sample)) => (time, sample),
                                         _ => break 'hit_objects,
                                     }
@@ -787,15 +775,8 @@ pub fn parse_str(
                     })
                 }
             },
-            stack_leniency: match stack_leniency {
-                Some(value) => value,
-                None => {
-                    return Err(ParseError::MissingField {
-                        field: "stack_leniency".into(),
-                        collection: "chart".into(),
-                    })
-                }
-            },
+            // Old beatmaps don't specify it.
+            stack_leniency: stack_leniency.unwrap_or(Ratio::new(7, 10)),
             slider_multiplier: match slider_multiplier {
                 Some(value) => value,
                 None => {
@@ -837,7 +818,8 @@ pub fn parse_str(
             samples_match_playback_rate: samples_match_playback_rate.unwrap_or(false),
             backgrounds,
             breaks,
-            colors,
+            other_events,
+            colours,
         })
     } else {
         None
@@ -872,7 +854,8 @@ pub fn parse_str(
                     })
                 }
             },
-            approach_rate: match approach_rate {
+            // Old beatmaps tie approach rate to overall difficulty.
+            approach_rate: match approach_rate.or(overall_difficulty) {
                 Some(value) => value,
                 None => {
                     return Err(ParseError::MissingField {
@@ -946,7 +929,27 @@ pub fn parse_str(
     Ok((chart, customization, difficulty, editor, filedata, metadata))
 }
 
+// Some beatmaps write "1" for true and "0" for false.
+fn from_str_bool(value: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    match value {
+        "0" => Ok(false),
+        "1" => Ok(true),
+        _ => Ok(value.parse()?),
+    }
+}
+
+// Times and positions are integers, but some beatmaps write them with decimals.
+fn from_str_time(value: &str) -> Result<i64, Box<dyn std::error::Error>> {
+    match value.parse() {
+        Ok(value) => Ok(value),
+        _ => Ok(value.parse::<f64>()? as i64),
+    }
+}
+
 fn from_str_ratio(decimal: &str) -> Result<Ratio<i64>, Box<dyn std::error::Error>> {
+    if let Some(magnitude) = decimal.strip_prefix('-') {
+        return Ok(-from_str_ratio(magnitude)?);
+    }
     let numerator;
     let denominator;
     if let Some((lhs, rhs)) = decimal.split_once('.') {
@@ -967,16 +970,3 @@ fn from_str_ratio(decimal: &str) -> Result<Ratio<i64>, Box<dyn std::error::Error
 
     Ok(Ratio::new(numerator, denominator))
 }
-
-fn from_str_one_decimal(decimal: &str) -> Result<u8, Box<dyn std::error::Error>> {
-    if let Some((lhs, rhs)) = decimal.split_once('.') {
-        match (lhs.is_empty(), rhs.is_empty()) {
-            (false, false) => Ok(lhs.parse::<u8>()? * 10 + rhs.parse::<u8>()?),
-            (true, false) => Ok(rhs.parse::<u8>()?),
-            (false, true) => Ok(lhs.parse::<u8>()? * 10),
-            (true, true) => Ok(0),
-        }
-    } else {
-        Ok(decimal.parse::<u8>()? * 10)
-    }
-}
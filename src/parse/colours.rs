@@ -1,39 +1,41 @@
-use rayon::prelude::*;
-use std::str::FromStr;
+use crate::customization::ColourKey;
+use crate::parts::Color;
 
-use crate::beatmap::customization::color::Color;
-
-pub fn parse(data: Vec<&str>) -> Result<Vec<Returns>, String> {
-    // Parse colors
-    let mut lines = data
-        .par_iter()
-        .map(|s| match s.split(':').next_back() {
-            Some(s) => {
-                return match Color::from_str(s.trim()) {
-                    Ok(c) => Ok(c),
-                    Err(e) => Err(format!("invalid line \"{}\" in [Colours] parsing", s)),
-                }
-            }
-            None => Err(format!("invalid line \"{}\" in [Colours] parsing", s)),
-        })
-        .collect::<Vec<Result<Color, String>>>();
-    // Check for errors
-    if lines.par_iter().any(|l| l.is_err()) {
-        return Err(lines
-            .par_iter()
-            .filter(|l| l.is_err())
-            .map(|l| l.unwrap_err())
-            .collect::<Vec<String>>()
-            .join(", "));
-    } else {
-        // Unwrap and return colors
-        Ok(lines
-            .par_iter()
-            .map(|c| Returns::Color(c.unwrap()))
-            .collect::<Vec<Returns>>())
+/// Parses a line such as `Combo1 : 255,128,0` from the [Colours] section.
+///
+/// An optional fourth value is the alpha channel.
+pub fn parse_line(line: &str) -> Option<(ColourKey, Color)> {
+    let (key, value) = line.split_once(':')?;
+    let key = match key.trim() {
+        "SliderTrackOverride" => ColourKey::SliderTrackOverride,
+        "SliderBorder" => ColourKey::SliderBorder,
+        key => match key.strip_prefix("Combo").map(|number| number.parse()) {
+            Some(Ok(number @ 1..=8)) => ColourKey::Combo(number),
+            _ => ColourKey::Other(key.into()),
+        },
+    };
+    let mut tokens = value.split(',').map(|t| t.trim());
+    let (red, green, blue) = match (tokens.next(), tokens.next(), tokens.next()) {
+        (Some(r), Some(g), Some(b)) => match (r.parse(), g.parse(), b.parse()) {
+            (Ok(r), Ok(g), Ok(b)) => (r, g, b),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let alpha = match tokens.next() {
+        Some(a) => Some(a.parse().ok()?),
+        None => None,
+    };
+    if tokens.next().is_some() {
+        return None;
     }
-}
-
-pub enum Returns {
-    Color(Color),
+    Some((
+        key,
+        Color {
+            red,
+            green,
+            blue,
+            alpha,
+        },
+    ))
 }
@@ -0,0 +1,44 @@
+use crate::customization::Background;
+use crate::customization::Break;
+
+/// The events this crate keeps from the [Events] section.
+pub enum Event {
+    Background(Background),
+    Break(Break),
+    /// Videos and storyboard commands aren't modelled, so the line is kept as written.
+    Other(String),
+}
+
+/// `line` keeps its indentation, which nests storyboard commands.
+pub fn parse_line(line: &str) -> Option<Event> {
+    let raw = line;
+    let line = line.trim();
+    let tokens: Vec<&str> = line.split(',').map(|t| t.trim()).collect();
+    match tokens[0] {
+        "0" | "Background" => {
+            let filename = tokens.get(2)?.trim_matches('"');
+            let xoffset = match tokens.get(3) {
+                Some(value) => value.parse().ok()?,
+                None => 0,
+            };
+            let yoffset = match tokens.get(4) {
+                Some(value) => value.parse().ok()?,
+                None => 0,
+            };
+            Some(Event::Background(Background {
+                filename: filename.into(),
+                xoffset,
+                yoffset,
+            }))
+        }
+        "2" | "Break" => {
+            let start_time = tokens.get(1)?.parse::<f64>().ok()? as i64;
+            let end_time = tokens.get(2)?.parse::<f64>().ok()? as i64;
+            Some(Event::Break(Break {
+                start_time,
+                end_time,
+            }))
+        }
+        _ => Some(Event::Other(raw.to_string())),
+    }
+}
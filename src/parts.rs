@@ -5,17 +5,46 @@ pub struct Background {
     pub yoffset: i64,
 }
 
+/// Written as a background event line.
+impl std::fmt::Display for Background {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "0,0,\"{}\",{},{}",
+            self.filename, self.xoffset, self.yoffset
+        )
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Break {
     pub start_time: i64,
     pub end_time: i64,
 }
 
+/// Written as a break event line.
+impl std::fmt::Display for Break {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "2,{},{}", self.start_time, self.end_time)
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Color {
     pub red: u8,
     pub green: u8,
     pub blue: u8,
+    pub alpha: Option<u8>, // Rarely present, written as a fourth value.
+}
+
+impl std::fmt::Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{},{},{}", self.red, self.green, self.blue)?;
+        if let Some(alpha) = self.alpha {
+            write!(f, ",{alpha}")?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -32,6 +61,23 @@ pub struct Curve {
     pub points: Vec<(i64, i64)>,
 }
 
+/// Written as the curve field of a slider, starting with the curve type.
+impl std::fmt::Display for Curve {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let letter = match self._type {
+            CurveType::Bezier => "B",
+            CurveType::Centripetal => "C",
+            CurveType::Linear => "L",
+            CurveType::Perfect => "P",
+        };
+        write!(f, "{letter}")?;
+        for (x, y) in self.points.iter() {
+            write!(f, "|{x}:{y}")?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum CurveType {
     Bezier,
@@ -43,8 +89,8 @@ pub enum CurveType {
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Effects {
     pub kiai: bool, // 1 on
-    // 2 is unused
-    pub ommit_barline: bool, // 4 on
+    // 2 and 4 are unused
+    pub ommit_barline: bool, // 8 on
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -67,6 +113,21 @@ impl Default for HitSample {
     }
 }
 
+/// Written as `normalSet:additionSet:index:volume:filename`.
+impl std::fmt::Display for HitSample {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}:{}:{}",
+            self.normal_set.to_u8(),
+            self.addition_set.to_u8(),
+            self.index,
+            self.volume,
+            self.filename.as_deref().unwrap_or("")
+        )
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct HitSound {
     pub normal: bool,
@@ -75,6 +136,17 @@ pub struct HitSound {
     pub clap: bool,
 }
 
+/// Written as the bitflags stored in the .osu file.
+impl std::fmt::Display for HitSound {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let bits = self.normal as u8
+            | (self.whistle as u8) << 1
+            | (self.finish as u8) << 2
+            | (self.clap as u8) << 3;
+        write!(f, "{bits}")
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum OverlayPosition {
     NoChange,
@@ -90,6 +162,30 @@ pub enum SampleSet {
     Drum,
 }
 
+impl SampleSet {
+    /// The number used for the sample set in timing points and hit samples.
+    pub fn to_u8(&self) -> u8 {
+        match self {
+            SampleSet::Default => 0,
+            SampleSet::Normal => 1,
+            SampleSet::Soft => 2,
+            SampleSet::Drum => 3,
+        }
+    }
+}
+
+/// Written as the name used in the [General] section.
+impl std::fmt::Display for SampleSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SampleSet::Default => write!(f, "Default"),
+            SampleSet::Normal => write!(f, "Normal"),
+            SampleSet::Soft => write!(f, "Soft"),
+            SampleSet::Drum => write!(f, "Drum"),
+        }
+    }
+}
+
 /// The Type struct is a direct representation of the type field in the .osu file.
 // TODO: Decide whether to only use this as an internal type. Reason: it sucks
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -99,6 +195,20 @@ pub struct Type {
     pub color_skip: u8,          // 4-6 -- Actually a 3 bit big-endian uint
 }
 
+/// Written as the bitflags stored in the .osu file.
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let object_type: u8 = match self.object_type {
+            ObjectType::Circle => 1,
+            ObjectType::Slider => 2,
+            ObjectType::Spinner => 8,
+            ObjectType::ManiaHold => 128,
+        };
+        let bits = object_type | (self.new_combo as u8) << 2 | (self.color_skip & 0b111) << 4;
+        write!(f, "{bits}")
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum ObjectType {
     Circle,
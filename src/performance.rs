@@ -0,0 +1,45 @@
+mod catch;
+mod mania;
+mod osu;
+mod taiko;
+
+pub use catch::*;
+pub use mania::*;
+pub use osu::*;
+pub use taiko::*;
+
+use crate::mods::Mods;
+pub use crate::score::Judgements;
+
+/// A play to calculate performance for.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Play {
+    pub mods: Mods,
+    pub judgements: Judgements,
+    /// The highest combo reached.
+    pub combo: i64,
+}
+
+// Splits the objects that weren't missed between judgements worth `weights`
+// points each, best first and worst last, to get as close to `accuracy` as possible.
+fn distribute(accuracy: f64, total: i64, misses: i64, weights: &[i64]) -> Vec<i64> {
+    let mut hits = (total - misses).max(0);
+    let worst = weights[weights.len() - 1];
+    let target = (accuracy.clamp(0.0, 1.0) * (total * weights[0]) as f64).round() as i64;
+    // Everything starts as the worst judgement, and the best are upgraded first.
+    let mut remaining = (target - hits * worst).max(0);
+    let mut counts = Vec::with_capacity(weights.len());
+    for &weight in weights[..weights.len() - 1].iter() {
+        let count = (remaining / (weight - worst)).min(hits);
+        remaining -= count * (weight - worst);
+        hits -= count;
+        counts.push(count);
+    }
+    counts.push(hits);
+    counts
+}
+
+// Goes from 0 at `start` to 1 at `end`.
+fn reverse_lerp(x: f64, start: f64, end: f64) -> f64 {
+    ((x - start) / (end - start)).clamp(0.0, 1.0)
+}
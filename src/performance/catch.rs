@@ -0,0 +1,129 @@
+use crate::chart::Mode;
+use crate::mods::Mods;
+use crate::performance::Judgements;
+use crate::performance::Play;
+use crate::rating::CatchAttributes;
+
+/// The performance points an osu!catch play is worth, as calculated by osu!lazer.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CatchPerformance {
+    pub total: f64,
+}
+
+impl CatchAttributes {
+    /// The judgements of a play with `accuracy` and `misses`,
+    /// where misses are fruits first and only tiny droplets are missed otherwise.
+    pub fn judgements(&self, accuracy: f64, misses: i64) -> Judgements {
+        let misses = misses.clamp(0, self.max_combo);
+        let missed_fruits = misses.min(self.fruit_count);
+        let missed_droplets = misses - missed_fruits;
+        let total = self.max_combo + self.tiny_droplet_count;
+        let caught = (accuracy.clamp(0.0, 1.0) * total as f64).round() as i64;
+        let tiny_droplets = (caught - (self.max_combo - misses)).clamp(0, self.tiny_droplet_count);
+        Judgements {
+            count_300: self.fruit_count - missed_fruits,
+            count_100: self.droplet_count - missed_droplets,
+            count_50: tiny_droplets,
+            count_katu: self.tiny_droplet_count - tiny_droplets,
+            count_miss: misses,
+            ..Default::default()
+        }
+    }
+}
+
+impl CatchPerformance {
+    /// Calculates the performance of a play on a beatmap with the given difficulty.
+    ///
+    /// The attributes must have been calculated with the play's mods.
+    pub fn calculate(attributes: &CatchAttributes, play: &Play) -> CatchPerformance {
+        let mods = play.mods;
+        let judgements = &play.judgements;
+        let misses = judgements.count_miss as f64;
+        let mut value = (5.0 * (attributes.star_rating / 0.0049).max(1.0) - 4.0).powi(2) / 100000.0;
+
+        // Tiny droplets don't count towards length.
+        let combo_hits = (judgements.count_300 + judgements.count_100) as f64 + misses;
+        let mut length_bonus = 0.95 + 0.3 * (combo_hits / 2500.0).min(1.0);
+        if combo_hits > 2500.0 {
+            length_bonus += (combo_hits / 2500.0).log10() * 0.475;
+        }
+        value *= length_bonus;
+        value *= 0.97f64.powf(misses);
+        if attributes.max_combo > 0 {
+            value *= (play.combo as f64 / attributes.max_combo as f64)
+                .powf(0.8)
+                .min(1.0);
+        }
+
+        let approach_rate = attributes.approach_rate;
+        let mut approach_rate_factor = 1.0;
+        if approach_rate > 9.0 {
+            approach_rate_factor += 0.1 * (approach_rate - 9.0);
+        }
+        if approach_rate > 10.0 {
+            approach_rate_factor += 0.1 * (approach_rate - 10.0);
+        } else if approach_rate < 8.0 {
+            approach_rate_factor += 0.025 * (8.0 - approach_rate);
+        }
+        value *= approach_rate_factor;
+
+        if mods.contains(Mods::HIDDEN) {
+            // Hidden is barely noticeable at high approach rates.
+            value *= match approach_rate <= 10.0 {
+                true => 1.05 + 0.075 * (10.0 - approach_rate),
+                false => 1.01 + 0.04 * (11.0 - approach_rate.min(11.0)),
+            };
+        }
+        if mods.contains(Mods::FLASHLIGHT) {
+            value *= 1.35 * length_bonus;
+        }
+        value *= judgements.accuracy(Mode::Catch).powf(5.5);
+        if mods.contains(Mods::NO_FAIL) {
+            value *= (1.0 - 0.02 * misses).max(0.9);
+        }
+        CatchPerformance { total: value }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::fixture;
+
+    const TOLERANCE: f64 = 0.01;
+
+    fn performance(mods: Mods, accuracy: f64, misses: i64) -> f64 {
+        let fixture = fixture("catch.osu");
+        let adjusted = mods
+            .apply(&fixture.chart, &fixture.difficulty, None)
+            .unwrap();
+        let attributes = CatchAttributes::calculate(&adjusted, fixture.filedata.file_format);
+        let combo = match misses {
+            0 => attributes.max_combo,
+            _ => attributes.max_combo / 2,
+        };
+        let play = Play {
+            mods,
+            judgements: attributes.judgements(accuracy, misses),
+            combo,
+        };
+        CatchPerformance::calculate(&attributes, &play).total
+    }
+
+    #[test]
+    fn fixture_performance() {
+        let mods = Mods::HIDDEN | Mods::HARD_ROCK | Mods::DOUBLE_TIME;
+        for (mods, accuracy, misses, expected) in [
+            (Mods::NONE, 1.0, 0, 214.309547),
+            (Mods::NONE, 0.97, 2, 97.25802),
+            (mods, 1.0, 0, 633.570485),
+            (mods, 0.97, 2, 287.52714),
+        ] {
+            let performance = performance(mods, accuracy, misses);
+            assert!(
+                (performance - expected).abs() < TOLERANCE,
+                "{mods:?} {accuracy} {misses}: {performance} != {expected}"
+            );
+        }
+    }
+}
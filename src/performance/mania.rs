@@ -0,0 +1,103 @@
+use crate::chart::Mode;
+use crate::mods::Mods;
+use crate::performance::distribute;
+use crate::performance::Judgements;
+use crate::performance::Play;
+use crate::rating::ManiaAttributes;
+
+/// The performance points an osu!mania play is worth, as calculated by osu!lazer.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ManiaPerformance {
+    pub total: f64,
+    pub difficulty: f64,
+}
+
+impl ManiaAttributes {
+    /// The judgements of a play with `accuracy` and `misses`, using as many 320s as possible.
+    ///
+    /// Holds are judged once like in stable.
+    pub fn judgements(&self, accuracy: f64, misses: i64) -> Judgements {
+        let total = self.note_count + self.hold_count;
+        let counts = distribute(accuracy, total, misses, &[6, 4, 2, 1]);
+        Judgements {
+            count_geki: counts[0],
+            count_katu: counts[1],
+            count_100: counts[2],
+            count_50: counts[3],
+            count_miss: misses.min(total),
+            ..Default::default()
+        }
+    }
+}
+
+impl ManiaPerformance {
+    /// Calculates the performance of a play on a beatmap with the given difficulty.
+    pub fn calculate(attributes: &ManiaAttributes, play: &Play) -> ManiaPerformance {
+        let mods = play.mods;
+        let judgements = &play.judgements;
+        let total_hits = judgements.total(Mode::Mania) as f64;
+        let mut multiplier = 8.0;
+        if mods.contains(Mods::NO_FAIL) {
+            multiplier *= 0.75;
+        }
+        if mods.contains(Mods::EASY) {
+            multiplier *= 0.5;
+        }
+
+        // Like stable's accuracy, but 320s are worth more than 300s.
+        let custom_accuracy = match total_hits > 0.0 {
+            true => judgements.weighted_accuracy(Mode::Mania, 320.0),
+            false => 0.0,
+        };
+        let difficulty = (attributes.star_rating - 0.15).max(0.05).powf(2.2)
+            * (5.0 * custom_accuracy - 4.0).max(0.0)
+            * (1.0 + 0.1 * (total_hits / 1500.0).min(1.0));
+        ManiaPerformance {
+            total: difficulty * multiplier,
+            difficulty,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::fixture;
+
+    const TOLERANCE: f64 = 0.01;
+
+    fn performance(mods: Mods, accuracy: f64, misses: i64) -> f64 {
+        let fixture = fixture("mania.osu");
+        let adjusted = mods
+            .apply(&fixture.chart, &fixture.difficulty, None)
+            .unwrap();
+        let attributes = ManiaAttributes::calculate(&adjusted, &[]);
+        let combo = match misses {
+            0 => attributes.max_combo,
+            _ => attributes.max_combo / 2,
+        };
+        let play = Play {
+            mods,
+            judgements: attributes.judgements(accuracy, misses),
+            combo,
+        };
+        ManiaPerformance::calculate(&attributes, &play).total
+    }
+
+    #[test]
+    fn fixture_performance() {
+        let mods = Mods::HIDDEN | Mods::HARD_ROCK | Mods::DOUBLE_TIME;
+        for (mods, accuracy, misses, expected) in [
+            (Mods::NONE, 1.0, 0, 53.444694),
+            (Mods::NONE, 0.97, 2, 45.415143),
+            (mods, 1.0, 0, 92.945242),
+            (mods, 0.97, 2, 78.981113),
+        ] {
+            let performance = performance(mods, accuracy, misses);
+            assert!(
+                (performance - expected).abs() < TOLERANCE,
+                "{mods:?} {accuracy} {misses}: {performance} != {expected}"
+            );
+        }
+    }
+}
@@ -0,0 +1,467 @@
+use crate::chart::Mode;
+use crate::mods::Mods;
+use crate::performance::distribute;
+use crate::performance::reverse_lerp;
+use crate::performance::Judgements;
+use crate::performance::Play;
+use crate::rating::difficulty_to_performance;
+use crate::rating::OsuAttributes;
+use crate::utility::difficulty_range;
+use crate::utility::erf;
+use crate::utility::erf_inv;
+use std::f64::consts::PI;
+
+const PERFORMANCE_BASE_MULTIPLIER: f64 = 1.15;
+
+/// The performance points an osu!standard play is worth, as calculated by osu!lazer.
+///
+/// Slider heads and ends aren't judged for accuracy, like in stable.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct OsuPerformance {
+    pub total: f64,
+    pub aim: f64,
+    pub speed: f64,
+    pub accuracy: f64,
+    pub flashlight: f64,
+    /// Misses along with the sliderbreaks the combo suggests.
+    pub effective_miss_count: f64,
+    /// An estimate of the standard deviation of hit errors on speed notes,
+    /// in rate adjusted milliseconds. `None` without any hits.
+    pub speed_deviation: Option<f64>,
+}
+
+// Rate adjusted hit windows in milliseconds.
+struct HitWindows {
+    great: f64,
+    ok: f64,
+    meh: f64,
+}
+
+impl OsuAttributes {
+    /// The judgements of a play with `accuracy` and `misses`,
+    /// using as many 300s as possible.
+    pub fn judgements(&self, accuracy: f64, misses: i64) -> Judgements {
+        let total = self.circle_count + self.slider_count + self.spinner_count;
+        let counts = distribute(accuracy, total, misses, &[6, 2, 1]);
+        Judgements {
+            count_300: counts[0],
+            count_100: counts[1],
+            count_50: counts[2],
+            count_miss: misses.min(total),
+            ..Default::default()
+        }
+    }
+}
+
+impl OsuPerformance {
+    /// Calculates the performance of a play on a beatmap with the given difficulty.
+    ///
+    /// The attributes must have been calculated with the play's mods.
+    pub fn calculate(attributes: &OsuAttributes, play: &Play) -> OsuPerformance {
+        let mods = play.mods;
+        let judgements = &play.judgements;
+        let total_hits = (judgements.count_300
+            + judgements.count_100
+            + judgements.count_50
+            + judgements.count_miss) as f64;
+        let mut performance = OsuPerformance {
+            total: 0.0,
+            aim: 0.0,
+            speed: 0.0,
+            accuracy: 0.0,
+            flashlight: 0.0,
+            effective_miss_count: 0.0,
+            speed_deviation: None,
+        };
+        if total_hits == 0.0 {
+            return performance;
+        }
+
+        // The rate adjusted overall difficulty hides the rate, which the windows are scaled by.
+        let clock_rate = mods.clock_rate();
+        let od = attributes.overall_difficulty;
+        let raw_od = (80.0 - (80.0 - 6.0 * od) * clock_rate) / 6.0;
+        let windows = HitWindows {
+            great: (80.0 - 6.0 * od).max(0.0),
+            ok: difficulty_range(raw_od, 140.0, 100.0, 60.0) / clock_rate,
+            meh: difficulty_range(raw_od, 200.0, 150.0, 100.0) / clock_rate,
+        };
+
+        let mut effective_miss_count = effective_miss_count(attributes, play, total_hits);
+        let mut multiplier = PERFORMANCE_BASE_MULTIPLIER;
+        if mods.contains(Mods::NO_FAIL) {
+            multiplier *= (1.0 - 0.02 * effective_miss_count).max(0.9);
+        }
+        if mods.contains(Mods::SPUN_OUT) {
+            multiplier *= 1.0 - (attributes.spinner_count as f64 / total_hits).powf(0.85);
+        }
+        if mods.contains(Mods::RELAX) {
+            // Relax only misses on purpose, so other inaccurate hits are counted as misses.
+            // The higher the overall difficulty, the less likely they were on purpose.
+            let scale = |exponent: f64| match od > 0.0 {
+                true => (1.0 - (od / 13.33).powf(exponent)).max(0.0),
+                false => 1.0,
+            };
+            effective_miss_count = (effective_miss_count
+                + judgements.count_100 as f64 * scale(1.8)
+                + judgements.count_50 as f64 * scale(5.0))
+            .min(total_hits);
+        }
+        performance.effective_miss_count = effective_miss_count;
+        performance.speed_deviation = speed_deviation(attributes, judgements, total_hits, &windows);
+
+        let accuracy = judgements.accuracy(Mode::Osu);
+        performance.aim = aim_value(attributes, play, total_hits, accuracy, effective_miss_count);
+        performance.speed = speed_value(
+            attributes,
+            play,
+            total_hits,
+            accuracy,
+            effective_miss_count,
+            performance.speed_deviation,
+        );
+        performance.accuracy = accuracy_value(attributes, play, total_hits);
+        performance.flashlight =
+            flashlight_value(attributes, play, total_hits, accuracy, effective_miss_count);
+        performance.total = (performance.aim.powf(1.1)
+            + performance.speed.powf(1.1)
+            + performance.accuracy.powf(1.1)
+            + performance.flashlight.powf(1.1))
+        .powf(1.0 / 1.1)
+            * multiplier;
+        performance
+    }
+}
+
+// Guesses sliderbreaks from how far the combo is from a full combo.
+fn effective_miss_count(attributes: &OsuAttributes, play: &Play, total_hits: f64) -> f64 {
+    let judgements = &play.judgements;
+    let mut combo_based_miss_count = 0.0;
+    if attributes.slider_count > 0 {
+        // Dropping slider ends only costs a little combo.
+        let full_combo_threshold =
+            attributes.max_combo as f64 - 0.1 * attributes.slider_count as f64;
+        if (play.combo as f64) < full_combo_threshold {
+            combo_based_miss_count = full_combo_threshold / (play.combo as f64).max(1.0);
+        }
+        // Every sliderbreak has to come with a 100 or 50 on the slider.
+        combo_based_miss_count = combo_based_miss_count
+            .min((judgements.count_100 + judgements.count_50 + judgements.count_miss) as f64);
+    }
+    combo_based_miss_count
+        .max(judgements.count_miss as f64)
+        .min(total_hits)
+}
+
+fn length_bonus(total_hits: f64) -> f64 {
+    let mut bonus = 0.95 + 0.4 * (total_hits / 2000.0).min(1.0);
+    if total_hits > 2000.0 {
+        bonus += (total_hits / 2000.0).log10() * 0.5;
+    }
+    bonus
+}
+
+// Misses matter less on maps where most of the difficulty is spread out.
+fn miss_penalty(miss_count: f64, difficult_strain_count: f64) -> f64 {
+    0.96 / (miss_count / (4.0 * difficult_strain_count.ln().powf(0.94)) + 1.0)
+}
+
+fn hidden_bonus(attributes: &OsuAttributes, mods: Mods) -> f64 {
+    match mods.contains(Mods::HIDDEN) {
+        true => 1.0 + 0.04 * (12.0 - attributes.approach_rate),
+        false => 1.0,
+    }
+}
+
+fn aim_value(
+    attributes: &OsuAttributes,
+    play: &Play,
+    total_hits: f64,
+    accuracy: f64,
+    effective_miss_count: f64,
+) -> f64 {
+    let mods = play.mods;
+    if mods.contains(Mods::AUTOPILOT) {
+        return 0.0;
+    }
+    let judgements = &play.judgements;
+    let mut aim_difficulty = attributes.aim;
+    if attributes.slider_count > 0 && attributes.aim_difficult_slider_count > 0.0 {
+        // Any combo lost without a miss is assumed to come from dropped difficult sliders.
+        let maximum_dropped_sliders =
+            (judgements.count_100 + judgements.count_50 + judgements.count_miss) as f64;
+        let estimate_dropped_sliders = maximum_dropped_sliders
+            .min((attributes.max_combo - play.combo) as f64)
+            .clamp(0.0, attributes.aim_difficult_slider_count);
+        let slider_nerf_factor = (1.0 - attributes.slider_factor)
+            * (1.0 - estimate_dropped_sliders / attributes.aim_difficult_slider_count).powi(3)
+            + attributes.slider_factor;
+        aim_difficulty *= slider_nerf_factor;
+    }
+
+    let mut value = difficulty_to_performance(aim_difficulty);
+    let length_bonus = length_bonus(total_hits);
+    value *= length_bonus;
+    if effective_miss_count > 0.0 {
+        value *= miss_penalty(effective_miss_count, attributes.aim_difficult_strain_count);
+    }
+
+    let approach_rate = attributes.approach_rate;
+    let mut approach_rate_factor = 0.0;
+    if approach_rate > 10.33 {
+        approach_rate_factor = 0.3 * (approach_rate - 10.33);
+    } else if approach_rate < 8.0 {
+        approach_rate_factor = 0.05 * (8.0 - approach_rate);
+    }
+    if mods.contains(Mods::RELAX) {
+        approach_rate_factor = 0.0;
+    }
+    value *= 1.0 + approach_rate_factor * length_bonus;
+    value *= hidden_bonus(attributes, mods);
+
+    value *= accuracy;
+    value *= 0.98 + attributes.overall_difficulty.max(0.0).powi(2) / 2500.0;
+    value
+}
+
+fn speed_value(
+    attributes: &OsuAttributes,
+    play: &Play,
+    total_hits: f64,
+    accuracy: f64,
+    effective_miss_count: f64,
+    speed_deviation: Option<f64>,
+) -> f64 {
+    let mods = play.mods;
+    let speed_deviation = match speed_deviation {
+        Some(deviation) if !mods.contains(Mods::RELAX) => deviation,
+        _ => return 0.0,
+    };
+    let judgements = &play.judgements;
+    let mut value = difficulty_to_performance(attributes.speed);
+    let length_bonus = length_bonus(total_hits);
+    value *= length_bonus;
+    if effective_miss_count > 0.0 {
+        value *= miss_penalty(
+            effective_miss_count,
+            attributes.speed_difficult_strain_count,
+        );
+    }
+
+    let approach_rate = attributes.approach_rate;
+    let mut approach_rate_factor = 0.0;
+    if approach_rate > 10.33 && !mods.contains(Mods::AUTOPILOT) {
+        approach_rate_factor = 0.3 * (approach_rate - 10.33);
+    }
+    value *= 1.0 + approach_rate_factor * length_bonus;
+    value *= hidden_bonus(attributes, mods);
+    value *= speed_high_deviation_nerf(attributes, speed_deviation);
+
+    // Accuracy on the notes that speed is calculated from,
+    // assuming the worst judgements went to the easiest notes.
+    let speed_notes = attributes.speed_note_count;
+    let relevant_total_diff = (total_hits - speed_notes).max(0.0);
+    let (c300, c100, c50) = (
+        judgements.count_300 as f64,
+        judgements.count_100 as f64,
+        judgements.count_50 as f64,
+    );
+    let relevant_300 = (c300 - relevant_total_diff).max(0.0);
+    let relevant_100 = (c100 - (relevant_total_diff - c300).max(0.0)).max(0.0);
+    let relevant_50 = (c50 - (relevant_total_diff - c300 - c100).max(0.0)).max(0.0);
+    let relevant_accuracy = match speed_notes == 0.0 {
+        true => 0.0,
+        false => (relevant_300 * 6.0 + relevant_100 * 2.0 + relevant_50) / (speed_notes * 6.0),
+    };
+    let od = attributes.overall_difficulty;
+    value *= ((accuracy + relevant_accuracy) / 2.0).powf((14.5 - od) / 2.0);
+    value
+}
+
+// Speed is worth less when the hit errors suggest the notes were mashed.
+fn speed_high_deviation_nerf(attributes: &OsuAttributes, speed_deviation: f64) -> f64 {
+    let value = difficulty_to_performance(attributes.speed);
+    // Values above this are only reachable by hitting notes on time.
+    let excess_cutoff = 100.0 + 220.0 * (22.0 / speed_deviation).powf(6.5);
+    if value <= excess_cutoff {
+        return 1.0;
+    }
+    const SCALE: f64 = 50.0;
+    let adjusted = SCALE * (((value - excess_cutoff) / SCALE).ln_1p() + excess_cutoff / SCALE);
+    let t = 1.0 - reverse_lerp(speed_deviation, 22.0, 27.0);
+    let adjusted = adjusted + (value - adjusted) * t;
+    adjusted / value
+}
+
+fn accuracy_value(attributes: &OsuAttributes, play: &Play, total_hits: f64) -> f64 {
+    let mods = play.mods;
+    if mods.contains(Mods::RELAX) {
+        return 0.0;
+    }
+    let judgements = &play.judgements;
+    // Only circles are judged on timing in stable.
+    let circles = attributes.circle_count as f64;
+    let better_accuracy = match circles > 0.0 {
+        true => {
+            ((judgements.count_300 as f64 - (total_hits - circles)) * 6.0
+                + judgements.count_100 as f64 * 2.0
+                + judgements.count_50 as f64)
+                / (circles * 6.0)
+        }
+        false => 0.0,
+    }
+    .max(0.0);
+
+    let mut value =
+        1.52163f64.powf(attributes.overall_difficulty) * better_accuracy.powi(24) * 2.83;
+    value *= (circles / 1000.0).powf(0.3).min(1.15);
+    if mods.contains(Mods::HIDDEN) {
+        value *= 1.08;
+    }
+    if mods.contains(Mods::FLASHLIGHT) {
+        value *= 1.02;
+    }
+    value
+}
+
+fn flashlight_value(
+    attributes: &OsuAttributes,
+    play: &Play,
+    total_hits: f64,
+    accuracy: f64,
+    effective_miss_count: f64,
+) -> f64 {
+    if !play.mods.contains(Mods::FLASHLIGHT) {
+        return 0.0;
+    }
+    let mut value = 25.0 * attributes.flashlight.powi(2);
+    if effective_miss_count > 0.0 {
+        value *= 0.97
+            * (1.0 - (effective_miss_count / total_hits).powf(0.775))
+                .powf(effective_miss_count.powf(0.875));
+    }
+    if attributes.max_combo > 0 {
+        value *= (play.combo as f64 / attributes.max_combo as f64)
+            .powf(0.8)
+            .min(1.0);
+    }
+    let mut length_bonus = 0.7 + 0.1 * (total_hits / 200.0).min(1.0);
+    if total_hits > 200.0 {
+        length_bonus += 0.2 * ((total_hits - 200.0) / 200.0).min(1.0);
+    }
+    value *= length_bonus;
+    value *= 0.5 + accuracy / 2.0;
+    value *= 0.98 + attributes.overall_difficulty.max(0.0).powi(2) / 2500.0;
+    value
+}
+
+// Estimates the deviation on speed notes, assuming the misses and worst judgements were on them.
+fn speed_deviation(
+    attributes: &OsuAttributes,
+    judgements: &Judgements,
+    total_hits: f64,
+    windows: &HitWindows,
+) -> Option<f64> {
+    let successful = judgements.count_300 + judgements.count_100 + judgements.count_50;
+    if successful == 0 {
+        return None;
+    }
+    // Some of the other notes are likely to need speed as well.
+    let speed_notes =
+        attributes.speed_note_count + (total_hits - attributes.speed_note_count) * 0.1;
+    let relevant_miss = (judgements.count_miss as f64).min(speed_notes);
+    let relevant_50 = (judgements.count_50 as f64).min(speed_notes - relevant_miss);
+    let relevant_100 = (judgements.count_100 as f64).min(speed_notes - relevant_miss - relevant_50);
+    let relevant_300 = (speed_notes - relevant_miss - relevant_50 - relevant_100).max(0.0);
+    deviation(
+        relevant_300,
+        relevant_100,
+        relevant_50,
+        relevant_miss,
+        windows,
+    )
+}
+
+// Estimates the deviation of hit errors, assuming they're normally distributed around 0.
+fn deviation(
+    count_300: f64,
+    count_100: f64,
+    count_50: f64,
+    count_miss: f64,
+    windows: &HitWindows,
+) -> Option<f64> {
+    if count_300 + count_100 + count_50 <= 0.0 {
+        return None;
+    }
+    let object_count = count_300 + count_100 + count_50 + count_miss;
+    // 50s and misses are assumed to come from something other than the deviation.
+    let n = (object_count - count_miss - count_50).max(1.0);
+    // The lower bound of the 99% confidence interval on the chance of a 300.
+    const Z: f64 = 2.32634787404;
+    let p = count_300 / n;
+    let p_lower = (n * p + Z * Z / 2.0) / (n + Z * Z)
+        - Z / (n + Z * Z) * (n * p * (1.0 - p) + Z * Z / 4.0).sqrt();
+    let mut deviation = windows.great / (2f64.sqrt() * erf_inv(p_lower));
+
+    // 100s are spread out evenly only when the deviation is large compared to the ok window.
+    let ok = windows.ok;
+    let random_value = (2.0 / PI).sqrt() * ok * (-0.5 * (ok / deviation).powi(2)).exp()
+        / (deviation * erf(ok / (2f64.sqrt() * deviation)));
+    deviation *= (1.0 - random_value).sqrt();
+    let limit_value = ok / 3f64.sqrt();
+    if p_lower == 0.0 || random_value >= 1.0 || deviation > limit_value {
+        deviation = limit_value;
+    }
+
+    // 50s are assumed to be evenly spread over their window.
+    let meh = windows.meh;
+    let meh_variance = (meh * meh + ok * meh + ok * ok) / 3.0;
+    Some(
+        (((count_300 + count_100) * deviation.powi(2) + count_50 * meh_variance)
+            / (count_300 + count_100 + count_50))
+            .sqrt(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::fixture;
+
+    const TOLERANCE: f64 = 0.01;
+
+    fn performance(mods: Mods, accuracy: f64, misses: i64) -> f64 {
+        let fixture = fixture("osu.osu");
+        let adjusted = mods
+            .apply(&fixture.chart, &fixture.difficulty, None)
+            .unwrap();
+        let attributes = OsuAttributes::calculate(&adjusted, fixture.filedata.file_format);
+        let combo = match misses {
+            0 => attributes.max_combo,
+            _ => attributes.max_combo / 2,
+        };
+        let play = Play {
+            mods,
+            judgements: attributes.judgements(accuracy, misses),
+            combo,
+        };
+        OsuPerformance::calculate(&attributes, &play).total
+    }
+
+    #[test]
+    fn fixture_performance() {
+        let mods = Mods::HIDDEN | Mods::HARD_ROCK | Mods::DOUBLE_TIME;
+        for (mods, accuracy, misses, expected) in [
+            (Mods::NONE, 1.0, 0, 105.382999),
+            (Mods::NONE, 0.97, 2, 61.964237),
+            (mods, 1.0, 0, 420.171263),
+            (mods, 0.97, 2, 259.307469),
+        ] {
+            let performance = performance(mods, accuracy, misses);
+            assert!(
+                (performance - expected).abs() < TOLERANCE,
+                "{mods:?} {accuracy} {misses}: {performance} != {expected}"
+            );
+        }
+    }
+}
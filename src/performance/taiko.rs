@@ -0,0 +1,263 @@
+use crate::chart::Mode;
+use crate::mods::Mods;
+use crate::performance::distribute;
+use crate::performance::Judgements;
+use crate::performance::Play;
+use crate::rating::TaikoAttributes;
+use crate::utility::erf;
+use crate::utility::erf_inv;
+
+/// The performance points an osu!taiko play is worth, as calculated by osu!lazer.
+///
+/// Both values scale with the unstable rate estimated from the play's greats,
+/// rather than with its accuracy.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TaikoPerformance {
+    pub total: f64,
+    pub difficulty: f64,
+    pub accuracy: f64,
+    /// Misses scaled up on short maps, where each one matters more.
+    pub effective_miss_count: f64,
+    /// The unstable rate the player most likely didn't exceed,
+    /// or `None` without any greats to estimate it from.
+    pub estimated_unstable_rate: Option<f64>,
+}
+
+impl TaikoAttributes {
+    /// The judgements of a play with `accuracy` and `misses`, using as many greats as possible.
+    pub fn judgements(&self, accuracy: f64, misses: i64) -> Judgements {
+        let total = self.max_combo;
+        let counts = distribute(accuracy, total, misses, &[2, 1]);
+        Judgements {
+            count_300: counts[0],
+            count_100: counts[1],
+            count_miss: misses.min(total),
+            ..Default::default()
+        }
+    }
+}
+
+impl TaikoPerformance {
+    /// Calculates the performance of a play on a beatmap with the given difficulty.
+    ///
+    /// The attributes must have been calculated with the play's mods.
+    pub fn calculate(attributes: &TaikoAttributes, play: &Play) -> TaikoPerformance {
+        let mods = play.mods;
+        let judgements = &play.judgements;
+        let total_hits = judgements.total(Mode::Taiko) as f64;
+        let successful_hits = (judgements.count_300 + judgements.count_100) as f64;
+
+        let estimated_unstable_rate =
+            deviation_upper_bound(attributes, judgements.count_300 as f64, total_hits)
+                .map(|deviation| deviation * 10.0);
+        let effective_miss_count = match successful_hits > 0.0 {
+            true => (1000.0 / successful_hits).max(1.0) * judgements.count_miss as f64,
+            false => 0.0,
+        };
+        let mut multiplier = 1.13;
+        if mods.contains(Mods::HIDDEN) && !attributes.is_convert {
+            multiplier *= 1.075;
+        }
+        if mods.contains(Mods::EASY) {
+            multiplier *= 0.95;
+        }
+
+        let difficulty = difficulty_value(
+            attributes,
+            mods,
+            total_hits,
+            effective_miss_count,
+            estimated_unstable_rate,
+        );
+        let accuracy = accuracy_value(attributes, mods, total_hits, estimated_unstable_rate);
+        TaikoPerformance {
+            total: (difficulty.powf(1.1) + accuracy.powf(1.1)).powf(1.0 / 1.1) * multiplier,
+            difficulty,
+            accuracy,
+            effective_miss_count,
+            estimated_unstable_rate,
+        }
+    }
+}
+
+fn difficulty_value(
+    attributes: &TaikoAttributes,
+    mods: Mods,
+    total_hits: f64,
+    effective_miss_count: f64,
+    estimated_unstable_rate: Option<f64>,
+) -> f64 {
+    let Some(unstable_rate) = estimated_unstable_rate else {
+        return 0.0;
+    };
+    let base = 5.0 * (attributes.star_rating / 0.115).max(1.0) - 4.0;
+    let mut value = (base.powi(3) / 69052.988).min(base.powf(2.25) / 1250.0);
+    value *= 1.0 + 0.1 * (attributes.star_rating - 10.0).max(0.0);
+    let length_bonus = 1.0 + 0.1 * (total_hits / 1500.0).min(1.0);
+    value *= length_bonus;
+    value *= 0.986f64.powf(effective_miss_count);
+    if mods.contains(Mods::EASY) {
+        value *= 0.9;
+    }
+    if mods.contains(Mods::HIDDEN) {
+        value *= 1.025;
+    }
+    if mods.contains(Mods::FLASHLIGHT) {
+        value *= (1.05 - (attributes.mono_stamina_factor / 50.0).min(1.0) * length_bonus).max(1.0);
+    }
+
+    // Accuracy matters more on maps that are mostly long streaks of one colour.
+    let exponent = 2.0 + attributes.mono_stamina_factor;
+    let shift = 500.0 - 100.0 * (attributes.mono_stamina_factor * 3.0);
+    value * erf(shift / (2f64.sqrt() * unstable_rate)).powf(exponent)
+}
+
+fn accuracy_value(
+    attributes: &TaikoAttributes,
+    mods: Mods,
+    total_hits: f64,
+    estimated_unstable_rate: Option<f64>,
+) -> f64 {
+    let Some(unstable_rate) = estimated_unstable_rate else {
+        return 0.0;
+    };
+    let mut value = (70.0 / unstable_rate).powf(1.1) * attributes.star_rating.powf(0.4) * 100.0;
+    let length_bonus = (total_hits / 1500.0).powf(0.3).min(1.15);
+    // Reading is harder with both, as notes only show up briefly.
+    if mods.contains(Mods::HIDDEN) && mods.contains(Mods::FLASHLIGHT) && !attributes.is_convert {
+        value *= (1.05 * length_bonus).max(1.0);
+    }
+    value
+}
+
+// The hit error deviation the player is 99% likely not to exceed, given the share of greats.
+fn deviation_upper_bound(attributes: &TaikoAttributes, greats: f64, n: f64) -> Option<f64> {
+    if greats == 0.0 || attributes.great_hit_window <= 0.0 {
+        return None;
+    }
+    const Z: f64 = 2.32634787404;
+    let p = greats / n;
+    let p_lower = (n * p + Z * Z / 2.0) / (n + Z * Z)
+        - Z / (n + Z * Z) * (n * p * (1.0 - p) + Z * Z / 4.0).sqrt();
+    Some(attributes.great_hit_window / (2f64.sqrt() * erf_inv(p_lower)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::fixture;
+
+    const TOLERANCE: f64 = 0.01;
+
+    fn performance(mods: Mods, accuracy: f64, misses: i64) -> f64 {
+        let fixture = fixture("taiko.osu");
+        let adjusted = mods
+            .apply(&fixture.chart, &fixture.difficulty, None)
+            .unwrap();
+        let attributes = TaikoAttributes::calculate(&adjusted, fixture.filedata.file_format);
+        let combo = match misses {
+            0 => attributes.max_combo,
+            _ => attributes.max_combo / 2,
+        };
+        let play = Play {
+            mods,
+            judgements: attributes.judgements(accuracy, misses),
+            combo,
+        };
+        TaikoPerformance::calculate(&attributes, &play).total
+    }
+
+    #[test]
+    fn fixture_performance() {
+        let mods = Mods::HIDDEN | Mods::HARD_ROCK | Mods::DOUBLE_TIME;
+        for (mods, accuracy, misses, expected) in [
+            (Mods::NONE, 1.0, 0, 185.076325),
+            (Mods::NONE, 0.97, 2, 142.241411),
+            (mods, 1.0, 0, 389.564953),
+            (mods, 0.97, 2, 303.177311),
+        ] {
+            let performance = performance(mods, accuracy, misses);
+            assert!(
+                (performance - expected).abs() < TOLERANCE,
+                "{mods:?} {accuracy} {misses}: {performance} != {expected}"
+            );
+        }
+    }
+
+    fn attributes() -> TaikoAttributes {
+        TaikoAttributes {
+            star_rating: 5.0,
+            stamina: 0.0,
+            rhythm: 0.0,
+            colour: 0.0,
+            peak: 0.0,
+            mono_stamina_factor: 0.0,
+            great_hit_window: 30.0,
+            max_combo: 1000,
+            is_convert: false,
+        }
+    }
+
+    fn play(mods: Mods, count_300: i64, count_100: i64, count_miss: i64) -> Play {
+        Play {
+            mods,
+            judgements: Judgements {
+                count_300,
+                count_100,
+                count_miss,
+                ..Default::default()
+            },
+            combo: count_300 + count_100,
+        }
+    }
+
+    #[test]
+    fn unstable_rate_is_estimated_from_the_greats() {
+        let attributes = attributes();
+        // With only greats, the lower bound of their share is n / (n + z²).
+        let z: f64 = 2.32634787404;
+        let expected = 10.0 * 30.0 / (2f64.sqrt() * erf_inv(1000.0 / (1000.0 + z * z)));
+        let performance = TaikoPerformance::calculate(&attributes, &play(Mods::NONE, 1000, 0, 0));
+        let unstable_rate = performance.estimated_unstable_rate.unwrap();
+        assert!((unstable_rate - expected).abs() < 1e-9, "{unstable_rate}");
+
+        let worse = TaikoPerformance::calculate(&attributes, &play(Mods::NONE, 900, 100, 0));
+        assert!(worse.estimated_unstable_rate.unwrap() > unstable_rate);
+        assert!(worse.difficulty < performance.difficulty);
+        assert!(worse.accuracy < performance.accuracy);
+    }
+
+    #[test]
+    fn plays_without_greats_are_worth_nothing() {
+        let performance = TaikoPerformance::calculate(&attributes(), &play(Mods::NONE, 0, 990, 10));
+        assert_eq!(performance.estimated_unstable_rate, None);
+        assert_eq!(performance.total, 0.0);
+    }
+
+    #[test]
+    fn hidden_is_worth_less_on_converts() {
+        let native = attributes();
+        let convert = TaikoAttributes {
+            is_convert: true,
+            ..native
+        };
+        let play = play(Mods::HIDDEN, 1000, 0, 0);
+        let native = TaikoPerformance::calculate(&native, &play);
+        let convert = TaikoPerformance::calculate(&convert, &play);
+        assert!((native.total / convert.total - 1.075).abs() < 1e-9);
+    }
+
+    #[test]
+    fn mono_colour_maps_scale_accuracy_harder() {
+        let mixed = attributes();
+        let mono = TaikoAttributes {
+            mono_stamina_factor: 1.0,
+            ..mixed
+        };
+        let play = play(Mods::NONE, 950, 50, 0);
+        let mixed = TaikoPerformance::calculate(&mixed, &play);
+        let mono = TaikoPerformance::calculate(&mono, &play);
+        assert!(mono.difficulty < mixed.difficulty);
+        assert_eq!(mono.accuracy, mixed.accuracy);
+    }
+}
@@ -0,0 +1,257 @@
+use crate::chart::Chart;
+use crate::collections::HitObject;
+use crate::customization::Customization;
+use crate::editor::Editor;
+use crate::filedata::Filedata;
+use crate::metadata::Metadata;
+use crate::parse::parse_str;
+use crate::parse::ParseError;
+use crate::write::write_str;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RateError {
+    #[error("invalid rate {rate}, it has to be a positive number")]
+    InvalidRate { rate: f64 },
+    #[error("invalid time in event {line:?}")]
+    InvalidEvent { line: String },
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+}
+
+/// How the rate is written in difficulty names and audio filenames, such as `1.25x`.
+pub fn rate_label(rate: f64) -> String {
+    format!("{}x", (rate * 100.0).round() / 100.0)
+}
+
+// Times in the .osu file are whole milliseconds.
+fn scale_time(time: i64, rate: f64) -> i64 {
+    (time as f64 / rate).round() as i64
+}
+
+// Scales the times in a video, storyboard or other unmodelled [Events] line.
+// Lines without times, like sprite declarations, are kept as they are.
+fn scale_event(line: &str, rate: f64) -> Option<String> {
+    let content = line.trim_start_matches([' ', '_']);
+    let indent = &line[..line.len() - content.len()];
+    let mut tokens: Vec<String> = content.split(',').map(String::from).collect();
+    // Indented lines are storyboard commands, and their times are relative inside loops.
+    let (times, durations): (&[usize], &[usize]) = match (indent.is_empty(), tokens[0].trim()) {
+        (false, "L") => (&[1], &[]),
+        (false, _) => (&[2, 3], &[]),
+        (true, "Video" | "1" | "Sample" | "5" | "3") => (&[1], &[]),
+        // The delay between animation frames.
+        (true, "Animation" | "6") => (&[], &[7]),
+        (true, _) => (&[], &[]),
+    };
+    for &index in times.iter().chain(durations) {
+        let Some(token) = tokens.get_mut(index) else {
+            continue;
+        };
+        // Commands can leave their end time empty.
+        if token.trim().is_empty() {
+            continue;
+        }
+        let value: f64 = token.trim().parse().ok()?;
+        *token = match times.contains(&index) {
+            true => ((value / rate).round() as i64).to_string(),
+            false => (value / rate).to_string(),
+        };
+    }
+    Some(format!("{indent}{}", tokens.join(",")))
+}
+
+impl Chart {
+    /// A copy of the chart played `rate` times as fast.
+    ///
+    /// Red lines get shorter beats, so slider durations follow
+    /// without touching slider lengths or green lines.
+    pub fn with_rate(&self, rate: f64) -> Chart {
+        let mut chart = self.clone();
+        for timing_point in chart.timing_points.iter_mut() {
+            timing_point.time = scale_time(timing_point.time, rate);
+            if timing_point.uninherited {
+                timing_point.beat_length /= rate;
+            }
+        }
+        for object in chart.hit_objects.iter_mut() {
+            match object {
+                HitObject::Circle(circle) => circle.time = scale_time(circle.time, rate),
+                HitObject::Slider(slider) => slider.time = scale_time(slider.time, rate),
+                HitObject::Spinner(spinner) => {
+                    spinner.time = scale_time(spinner.time, rate);
+                    spinner.end_time = scale_time(spinner.end_time, rate);
+                }
+                HitObject::ManiaHold(hold) => {
+                    hold.time = scale_time(hold.time, rate);
+                    hold.end_time = scale_time(hold.end_time, rate);
+                }
+            }
+        }
+        chart
+    }
+}
+
+impl Customization {
+    /// A copy with break, video and storyboard times scaled for a chart played
+    /// `rate` times as fast.
+    ///
+    /// Fails on events whose times aren't plain numbers, like ones using
+    /// storyboard variables. A separate .osb storyboard isn't changed.
+    pub fn with_rate(&self, rate: f64) -> Result<Customization, RateError> {
+        let mut customization = self.clone();
+        for event in customization.breaks.iter_mut() {
+            event.start_time = scale_time(event.start_time, rate);
+            event.end_time = scale_time(event.end_time, rate);
+        }
+        for event in customization.other_events.iter_mut() {
+            *event = scale_event(event, rate).ok_or_else(|| RateError::InvalidEvent {
+                line: event.clone(),
+            })?;
+        }
+        Ok(customization)
+    }
+}
+
+impl Editor {
+    /// A copy with bookmarks scaled for a chart played `rate` times as fast.
+    pub fn with_rate(&self, rate: f64) -> Editor {
+        let mut editor = self.clone();
+        for bookmark in editor.bookmarks.iter_mut() {
+            *bookmark = scale_time(*bookmark, rate);
+        }
+        editor
+    }
+}
+
+impl Filedata {
+    /// A copy pointing at a rate-changed audio file, such as `audio 1.25x.mp3`.
+    ///
+    /// The audio itself has to be stretched elsewhere, so the old hash is dropped.
+    /// Audio lead-in is real time before the first object and stays the same.
+    pub fn with_rate(&self, rate: f64) -> Filedata {
+        let mut filedata = self.clone();
+        let label = rate_label(rate);
+        filedata.audio_filename = match self.audio_filename.rsplit_once('.') {
+            Some((stem, extension)) => format!("{stem} {label}.{extension}"),
+            None => format!("{} {label}", self.audio_filename),
+        };
+        filedata.audio_hash = None;
+        // -1 means no preview time was set.
+        if filedata.preview_time != -1 {
+            filedata.preview_time = scale_time(filedata.preview_time, rate);
+        }
+        filedata
+    }
+}
+
+impl Metadata {
+    /// A copy named as a new difficulty, such as `Insane 1.25x`.
+    ///
+    /// The new difficulty isn't submitted, so it has no beatmap ID.
+    pub fn with_rate(&self, rate: f64) -> Metadata {
+        let mut metadata = self.clone();
+        let label = rate_label(rate);
+        metadata.version = match &self.version {
+            Some(version) => Some(format!("{version} {label}")),
+            None => Some(label),
+        };
+        metadata.beatmap_id = None;
+        metadata
+    }
+}
+
+/// Rewrites a .osu file as a new difficulty played `rate` times as fast.
+///
+/// `rate` has to be a positive number.
+pub fn with_rate_str(s: &str, rate: f64) -> Result<String, RateError> {
+    if !(rate > 0.0 && rate.is_finite()) {
+        return Err(RateError::InvalidRate { rate });
+    }
+    let (chart, customization, difficulty, editor, filedata, metadata) =
+        parse_str(s, true, true, true, true, true, true)?;
+    // Every collection was requested, so every one is present.
+    Ok(write_str(
+        &chart.unwrap().with_rate(rate),
+        &customization.unwrap().with_rate(rate)?,
+        &difficulty.unwrap(),
+        &editor.unwrap().with_rate(rate),
+        &filedata.unwrap().with_rate(rate),
+        &metadata.unwrap().with_rate(rate),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::fixture_text;
+
+    const STORYBOARD: &str = "Video,500,\"video.mp4\"
+2,20000,22000
+Sprite,Foreground,Centre,\"sb/dot.png\",320,240
+ F,0,1500,3000,0,1
+ L,6000,4
+  M,0,0,300,320,240,330,250
+ S,0,4500,,1
+Animation,Background,Centre,\"sb/anim.png\",320,240,4,50,LoopForever
+Sample,9000,0,\"sb/hit.wav\",60";
+
+    fn with_events(events: &str) -> String {
+        fixture_text("osu.osu").replace(
+            "//Storyboard Layer 0 (Background)",
+            &format!("//Storyboard Layer 0 (Background)\r\n{events}"),
+        )
+    }
+
+    fn events(text: &str) -> Vec<String> {
+        let (_, customization, ..) =
+            parse_str(text, false, true, false, false, false, false).unwrap();
+        customization.unwrap().other_events
+    }
+
+    #[test]
+    fn storyboard_and_video_events_are_scaled() {
+        let text = with_events(STORYBOARD);
+        assert_eq!(events(&text).len(), 8);
+        let rate_changed = with_rate_str(&text, 1.5).unwrap();
+        assert_eq!(
+            events(&rate_changed),
+            [
+                "Video,333,\"video.mp4\"",
+                "Sprite,Foreground,Centre,\"sb/dot.png\",320,240",
+                " F,0,1000,2000,0,1",
+                " L,4000,4",
+                "  M,0,0,200,320,240,330,250",
+                " S,0,3000,,1",
+                "Animation,Background,Centre,\"sb/anim.png\",320,240,4,33.333333333333336,LoopForever",
+                "Sample,6000,0,\"sb/hit.wav\",60",
+            ]
+        );
+        let (_, customization, ..) =
+            parse_str(&rate_changed, false, true, false, false, false, false).unwrap();
+        let breaks = customization.unwrap().breaks;
+        assert_eq!((breaks[0].start_time, breaks[0].end_time), (13333, 14667));
+    }
+
+    #[test]
+    fn events_with_variables_are_an_error() {
+        let text =
+            with_events("Sprite,Foreground,Centre,\"a.png\",320,240\r\n F,0,$start,3000,0,1");
+        assert!(matches!(
+            with_rate_str(&text, 1.5),
+            Err(RateError::InvalidEvent { .. })
+        ));
+    }
+
+    #[test]
+    fn invalid_rates_are_an_error() {
+        let text = fixture_text("osu.osu");
+        for rate in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                with_rate_str(&text, rate),
+                Err(RateError::InvalidRate { .. })
+            ));
+        }
+        assert!(with_rate_str(&text, 0.75).is_ok());
+    }
+}
@@ -0,0 +1,10 @@
+mod catch;
+mod mania;
+mod osu;
+mod strain;
+mod taiko;
+
+pub use catch::*;
+pub use mania::*;
+pub use osu::*;
+pub use taiko::*;
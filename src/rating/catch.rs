@@ -0,0 +1,168 @@
+use crate::chart::CatchKind;
+use crate::chart::CatchObject;
+use crate::mods::Adjusted;
+use crate::mods::Mods;
+use crate::rating::strain::*;
+use crate::utility::ratio_to_f64;
+
+const STAR_SCALING_FACTOR: f64 = 0.153;
+// Positions are scaled as if every catcher was this wide either side.
+const NORMALISED_HITOBJECT_RADIUS: f64 = 41.0;
+const ABSOLUTE_PLAYER_POSITIONING_ERROR: f64 = 16.0;
+const DIRECTION_CHANGE_BONUS: f64 = 21.0;
+
+const MOVEMENT_SKILL_MULTIPLIER: f64 = 900.0;
+const MOVEMENT_STRAIN_DECAY_BASE: f64 = 0.2;
+const MOVEMENT_DECAY_WEIGHT: f64 = 0.94;
+const MOVEMENT_SECTION_LENGTH: f64 = 750.0;
+
+/// The osu!catch difficulty of a beatmap, as calculated by osu!lazer.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CatchAttributes {
+    pub star_rating: f64,
+    /// Approach rate as it feels once the rate is applied.
+    pub approach_rate: f64,
+    /// Fruits and droplets. Tiny droplets and bananas don't give combo.
+    pub max_combo: i64,
+    pub fruit_count: i64,
+    pub droplet_count: i64,
+    pub tiny_droplet_count: i64,
+}
+
+// A fruit or droplet along with the one before it.
+// Positions are normalised and times rate adjusted.
+struct DifficultyObject {
+    start_time: f64,
+    delta_time: f64,
+    strain_time: f64,
+    position: f64,
+    last_position: f64,
+    last_hyper_dash: bool,
+    last_distance_to_hyper_dash: f64,
+}
+
+impl CatchAttributes {
+    /// Calculates the difficulty of a beatmap with mods applied.
+    ///
+    /// The chart is played as osu!catch whatever its mode,
+    /// see [`Chart::catch_objects`](crate::chart::Chart::catch_objects),
+    /// which `file_format` is passed to.
+    pub fn calculate(adjusted: &Adjusted, file_format: u8) -> CatchAttributes {
+        let difficulty = &adjusted.difficulty;
+        let clock_rate = adjusted.clock_rate;
+        let hard_rock_offsets = adjusted.mods.contains(Mods::HARD_ROCK);
+        let all_objects = adjusted
+            .chart
+            .catch_objects(difficulty, hard_rock_offsets, file_format);
+        let count = |kind: CatchKind| all_objects.iter().filter(|o| o.kind == kind).count() as i64;
+        let objects: Vec<&CatchObject> = all_objects.iter().filter(|o| o.is_combo()).collect();
+
+        let mut attributes = CatchAttributes {
+            star_rating: 0.0,
+            approach_rate: adjusted.approach_rate,
+            max_combo: objects.len() as i64,
+            fruit_count: count(CatchKind::Fruit),
+            droplet_count: count(CatchKind::Droplet),
+            tiny_droplet_count: count(CatchKind::TinyDroplet),
+        };
+        if objects.is_empty() {
+            return attributes;
+        }
+
+        // The catcher is treated as smaller on high circle sizes to account for imperfect play.
+        let circle_size = ratio_to_f64(difficulty.circle_size);
+        let half_catcher_width =
+            difficulty.catcher_width() / 2.0 * (1.0 - (circle_size - 5.5).max(0.0) * 0.0625);
+        let scaling_factor = NORMALISED_HITOBJECT_RADIUS / half_catcher_width;
+
+        let difficulty_objects: Vec<DifficultyObject> = objects
+            .windows(2)
+            .map(|pair| {
+                let delta_time = (pair[1].time - pair[0].time) / clock_rate;
+                DifficultyObject {
+                    start_time: pair[1].time / clock_rate,
+                    delta_time,
+                    // Capped at the equivalent of streaming at 375 BPM.
+                    strain_time: delta_time.max(40.0),
+                    position: pair[1].x * scaling_factor,
+                    last_position: pair[0].x * scaling_factor,
+                    last_hyper_dash: pair[0].hyper_dash,
+                    last_distance_to_hyper_dash: pair[0].distance_to_hyper_dash,
+                }
+            })
+            .collect();
+
+        let movement = movement_skill(&difficulty_objects, clock_rate);
+        let difficulty_value = weighted_sum(movement.peaks(), MOVEMENT_DECAY_WEIGHT);
+        attributes.star_rating = difficulty_value.sqrt() * STAR_SCALING_FACTOR;
+        attributes
+    }
+}
+
+// The rate also changes how fast the catcher moves.
+fn movement_skill(objects: &[DifficultyObject], catcher_speed_multiplier: f64) -> StrainPeaks {
+    let decay = |ms: f64| MOVEMENT_STRAIN_DECAY_BASE.powf(ms / 1000.0);
+    let mut peaks = StrainPeaks::new(MOVEMENT_SECTION_LENGTH);
+    let mut current_strain = 0.0;
+    let mut last_player_position: Option<f64> = None;
+    let mut last_distance_moved: f64 = 0.0;
+    let mut last_strain_time: f64 = 0.0;
+    for (i, object) in objects.iter().enumerate() {
+        let previous_start = i.checked_sub(1).map_or(0.0, |j| objects[j].start_time);
+        peaks.start_object(i, object.start_time, |time| {
+            current_strain * decay(time - previous_start)
+        });
+
+        // The player is assumed to move as little as possible to catch each object.
+        let last_position = *last_player_position.get_or_insert(object.last_position);
+        let leeway = NORMALISED_HITOBJECT_RADIUS - ABSOLUTE_PLAYER_POSITIONING_ERROR;
+        let mut player_position =
+            last_position.clamp(object.position - leeway, object.position + leeway);
+        let distance_moved = player_position - last_position;
+        let weighted_strain_time = object.strain_time + 13.0 + 3.0 / catcher_speed_multiplier;
+        let mut distance_addition = distance_moved.abs().powf(1.3) / 510.0;
+        let sqrt_strain = weighted_strain_time.sqrt();
+
+        if distance_moved.abs() > 0.1 {
+            if last_distance_moved.abs() > 0.1
+                && distance_moved.signum() != last_distance_moved.signum()
+            {
+                let bonus_factor = distance_moved.abs().min(50.0) / 50.0;
+                let antiflow_factor = (last_distance_moved.abs().min(70.0) / 70.0).max(0.38);
+                distance_addition += DIRECTION_CHANGE_BONUS / (last_strain_time + 16.0).sqrt()
+                    * bonus_factor
+                    * antiflow_factor
+                    * (1.0 - (weighted_strain_time / 1000.0).powi(3)).max(0.0);
+            }
+            // Every movement is worth something, giving some weight to streams.
+            distance_addition += 12.5 * distance_moved.abs().min(NORMALISED_HITOBJECT_RADIUS * 2.0)
+                / (NORMALISED_HITOBJECT_RADIUS * 6.0)
+                / sqrt_strain;
+        }
+
+        // Edge dashes are harder, and easier the faster they are.
+        if object.last_distance_to_hyper_dash <= 20.0 {
+            let mut edge_dash_bonus = 0.0;
+            if !object.last_hyper_dash {
+                edge_dash_bonus += 5.7;
+            } else {
+                // A hyperdash always lands the catcher in the right place.
+                player_position = object.position;
+            }
+            distance_addition *= 1.0
+                + edge_dash_bonus
+                    * ((20.0 - object.last_distance_to_hyper_dash) / 20.0)
+                    * ((object.strain_time * catcher_speed_multiplier).min(265.0) / 265.0)
+                        .powf(1.5);
+        }
+
+        last_player_position = Some(player_position);
+        last_distance_moved = distance_moved;
+        last_strain_time = object.strain_time;
+
+        current_strain *= decay(object.delta_time);
+        current_strain += distance_addition / weighted_strain_time * MOVEMENT_SKILL_MULTIPLIER;
+        peaks.add_strain(current_strain);
+    }
+    peaks
+}
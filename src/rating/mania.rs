@@ -0,0 +1,255 @@
+use crate::chart::ManiaKind;
+use crate::chart::ManiaObject;
+use crate::chart::Mode;
+use crate::customization::Break;
+use crate::mods::Adjusted;
+use crate::mods::Mods;
+use crate::rating::strain::*;
+use crate::utility::legacy_sort;
+use crate::utility::ratio_to_f64;
+
+const DIFFICULTY_MULTIPLIER: f64 = 0.018;
+const INDIVIDUAL_DECAY_BASE: f64 = 0.125;
+const OVERALL_DECAY_BASE: f64 = 0.30;
+// Releasing several holds together is as easy as releasing one.
+const RELEASE_THRESHOLD: f64 = 30.0;
+
+/// The osu!mania difficulty of a beatmap, as calculated by osu!lazer.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ManiaAttributes {
+    pub star_rating: f64,
+    /// The great hit window in milliseconds once the rate is applied.
+    /// Rounded like stable, where the rate doesn't change hit windows.
+    pub great_hit_window: f64,
+    pub max_combo: i64,
+    pub note_count: i64,
+    pub hold_count: i64,
+}
+
+// Times are rate adjusted.
+struct DifficultyObject {
+    column: usize,
+    start_time: f64,
+    end_time: f64,
+    delta_time: f64,
+}
+
+impl ManiaAttributes {
+    /// Calculates the difficulty of a beatmap with mods applied.
+    ///
+    /// Charts from other modes are converted first, see
+    /// [`Chart::convert_to_mania`](crate::chart::Chart::convert_to_mania),
+    /// which is the only thing `breaks` is used for.
+    pub fn calculate(adjusted: &Adjusted, breaks: &[Break]) -> ManiaAttributes {
+        let chart = &adjusted.chart;
+        let clock_rate = adjusted.clock_rate;
+        let original = &adjusted.original_difficulty;
+        let key_count = chart.mania_key_count(original, adjusted.mods);
+        let objects = chart.convert_to_mania(original, breaks, key_count);
+        // Converts use the beatmap's own overall difficulty, which Hard Rock and Easy would change.
+        let od = match chart.mode {
+            Mode::Mania => ratio_to_f64(adjusted.difficulty.overall_difficulty),
+            _ => ratio_to_f64(original.overall_difficulty),
+        };
+        let count = |kind: ManiaKind| objects.iter().filter(|o| o.kind == kind).count() as i64;
+
+        let mut attributes = ManiaAttributes {
+            star_rating: 0.0,
+            great_hit_window: great_hit_window(od, adjusted.mods, chart.mode == Mode::Mania),
+            max_combo: objects.iter().map(max_combo).sum(),
+            note_count: count(ManiaKind::Note),
+            hold_count: count(ManiaKind::Hold),
+        };
+        if objects.is_empty() {
+            return attributes;
+        }
+
+        let mut sorted = objects;
+        legacy_sort(&mut sorted, |a, b| {
+            (a.time.round_ties_even() as i32 - b.time.round_ties_even() as i32).cmp(&0)
+        });
+        let difficulty_objects: Vec<DifficultyObject> = sorted
+            .windows(2)
+            .map(|pair| DifficultyObject {
+                column: pair[1].column as usize,
+                start_time: pair[1].time / clock_rate,
+                end_time: pair[1].end_time / clock_rate,
+                delta_time: (pair[1].time - pair[0].time) / clock_rate,
+            })
+            .collect();
+
+        let strain = strain_skill(&difficulty_objects, key_count as usize);
+        attributes.star_rating = weighted_sum(strain.peaks(), 0.9) * DIFFICULTY_MULTIPLIER;
+        attributes
+    }
+}
+
+// Holds give combo at the head and every 100 milliseconds after.
+fn max_combo(object: &ManiaObject) -> i64 {
+    match object.kind {
+        ManiaKind::Note => 1,
+        ManiaKind::Hold => 1 + ((object.end_time - object.time) / 100.0) as i64,
+    }
+}
+
+// Stable calculated this from overall difficulty differently for converts,
+// and the rate doesn't change it there.
+fn great_hit_window(od: f64, mods: Mods, is_mania: bool) -> f64 {
+    let mut window = if is_mania {
+        34.0 + 3.0 * (10.0 - od).clamp(0.0, 10.0)
+    } else if od.round_ties_even() > 4.0 {
+        34.0
+    } else {
+        47.0
+    };
+    if mods.contains(Mods::HARD_ROCK) {
+        window /= 1.4;
+    } else if mods.contains(Mods::EASY) {
+        window *= 1.4;
+    }
+    let clock_rate = mods.clock_rate();
+    window *= clock_rate;
+    ((window as i32) as f64 / clock_rate).ceil()
+}
+
+fn apply_decay(value: f64, delta_time: f64, decay_base: f64) -> f64 {
+    value * decay_base.powf(delta_time / 1000.0)
+}
+
+// Whether `a` is more than a millisecond after `b`.
+fn definitely_after(a: f64, b: f64) -> bool {
+    a - 1.0 > b
+}
+
+// Each column builds up strain from its own notes, and the whole chart from all of them.
+fn strain_skill(objects: &[DifficultyObject], key_count: usize) -> StrainPeaks {
+    let mut peaks = StrainPeaks::new(400.0);
+    let mut start_times = vec![0.0; key_count];
+    let mut end_times = vec![0.0; key_count];
+    let mut individual_strains = vec![0.0; key_count];
+    let mut individual_strain = 0.0;
+    let mut overall_strain = 1.0;
+    for (i, object) in objects.iter().enumerate() {
+        let previous_start = i.checked_sub(1).map_or(0.0, |j| objects[j].start_time);
+        peaks.start_object(i, object.start_time, |time| {
+            apply_decay(
+                individual_strain,
+                time - previous_start,
+                INDIVIDUAL_DECAY_BASE,
+            ) + apply_decay(overall_strain, time - previous_start, OVERALL_DECAY_BASE)
+        });
+
+        let (start_time, end_time, column) = (object.start_time, object.end_time, object.column);
+        let mut is_overlapping = false;
+        let mut closest_end_time = (end_time - start_time).abs();
+        // Everything is harder while something else is held.
+        let mut hold_factor = 1.0;
+        let mut hold_addition = 0.0;
+        for j in 0..key_count {
+            // A hold is overlapped when an earlier note ends during it.
+            is_overlapping |= definitely_after(end_times[j], start_time)
+                && definitely_after(end_time, end_times[j])
+                && definitely_after(start_time, start_times[j]);
+            if definitely_after(end_times[j], end_time)
+                && definitely_after(start_time, start_times[j])
+            {
+                hold_factor = 1.25;
+            }
+            closest_end_time = closest_end_time.min((end_time - end_times[j]).abs());
+        }
+        // Releasing an overlapped hold is awkward, unless another note ends at about the same time.
+        if is_overlapping {
+            hold_addition = 1.0 / (1.0 + (0.27 * (RELEASE_THRESHOLD - closest_end_time)).exp());
+        }
+
+        individual_strains[column] = apply_decay(
+            individual_strains[column],
+            start_time - start_times[column],
+            INDIVIDUAL_DECAY_BASE,
+        );
+        individual_strains[column] += 2.0 * hold_factor;
+        // Chords take the hardest column.
+        individual_strain = match object.delta_time <= 1.0 {
+            true => f64::max(individual_strain, individual_strains[column]),
+            false => individual_strains[column],
+        };
+
+        overall_strain = apply_decay(overall_strain, object.delta_time, OVERALL_DECAY_BASE);
+        overall_strain += (1.0 + hold_addition) * hold_factor;
+
+        start_times[column] = start_time;
+        end_times[column] = end_time;
+        peaks.add_strain(individual_strain + overall_strain);
+    }
+    peaks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::{beatmap, DIFFICULTY};
+
+    fn assert_near(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-12, "{actual} != {expected}");
+    }
+
+    fn attributes(hit_objects: &str, mods: Mods) -> ManiaAttributes {
+        let fixture = beatmap(3, DIFFICULTY, "0,500,4,2,0,100,1,0", hit_objects);
+        let adjusted = mods
+            .apply(&fixture.chart, &fixture.difficulty, None)
+            .unwrap();
+        ManiaAttributes::calculate(&adjusted, &[])
+    }
+
+    // The expected values are worked out by hand from the strain formulas,
+    // as the first object only starts the strain and every later one adds to it.
+    #[test]
+    fn strain_of_two_notes() {
+        let objects = "64,192,1000,1,0,0:0:0:0:\n192,192,1500,1,0,0:0:0:0:";
+        let nomod = attributes(objects, Mods::NONE);
+        assert_near(nomod.star_rating, (2.0 + 0.3f64.powf(0.5) + 1.0) * 0.018);
+        assert_eq!(
+            (nomod.note_count, nomod.hold_count, nomod.max_combo),
+            (2, 0, 2)
+        );
+
+        let double_time = attributes(objects, Mods::DOUBLE_TIME);
+        let delta = 500.0 / 1.5 / 1000.0;
+        assert_near(
+            double_time.star_rating,
+            (2.0 + 0.3f64.powf(delta) + 1.0) * 0.018,
+        );
+    }
+
+    #[test]
+    fn notes_during_holds_are_harder() {
+        let objects = "320,192,500,1,0,0:0:0:0:\n\
+                       64,192,1000,128,0,2000:0:0:0:0:\n\
+                       192,192,1500,1,0,0:0:0:0:";
+        let attributes = attributes(objects, Mods::NONE);
+        let decay = |value: f64, time: f64, base: f64| value * base.powf(time / 1000.0);
+        let overall_hold = decay(1.0, 500.0, 0.3) + 1.0;
+        let hold_strain = 2.0 + overall_hold;
+        // Held over, the note's column and the overall strain get 1.25 times as much.
+        let note_strain = 2.5 + decay(overall_hold, 500.0, 0.3) + 1.25;
+        assert_near(
+            attributes.star_rating,
+            (note_strain + 0.9 * hold_strain) * 0.018,
+        );
+        // A hold's combo is its head and every 100 milliseconds after.
+        assert_eq!(attributes.max_combo, 1 + 11 + 1);
+        assert_eq!(attributes.hold_count, 1);
+    }
+
+    #[test]
+    fn great_hit_window_follows_overall_difficulty() {
+        let attributes = attributes("64,192,1000,1,0,0:0:0:0:", Mods::NONE);
+        assert_eq!(attributes.great_hit_window, 40.0);
+        assert_eq!(attributes.star_rating, 0.0);
+        // Truncated like stable, so 40 / 1.4 is 28 rather than 29.
+        assert_eq!(great_hit_window(8.0, Mods::HARD_ROCK, true), 28.0);
+        assert_eq!(great_hit_window(8.0, Mods::DOUBLE_TIME, true), 40.0);
+        assert_eq!(great_hit_window(8.0, Mods::NONE, false), 34.0);
+        assert_eq!(great_hit_window(4.0, Mods::NONE, false), 47.0);
+    }
+}
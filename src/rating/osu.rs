@@ -0,0 +1,1008 @@
+use crate::chart::Chart;
+use crate::chart::Mode;
+use crate::collections::HitObject;
+use crate::collections::NestedKind;
+use crate::collections::Slider;
+use crate::collections::TimingMap;
+use crate::mods::Adjusted;
+use crate::mods::Mods;
+use crate::rating::strain::*;
+use crate::utility::ratio_to_f64;
+use std::f64::consts::PI;
+
+// Distances are scaled as if every circle had this radius.
+const NORMALISED_RADIUS: f64 = 50.0;
+const NORMALISED_DIAMETER: f64 = NORMALISED_RADIUS * 2.0;
+const MIN_DELTA_TIME: f64 = 25.0;
+const MAXIMUM_SLIDER_RADIUS: f64 = NORMALISED_RADIUS * 2.4;
+const ASSUMED_SLIDER_RADIUS: f64 = NORMALISED_RADIUS * 1.8;
+// The player only needs to hold a slider until this long before its tail.
+const TAIL_LENIENCY: f64 = -36.0;
+
+const DIFFICULTY_MULTIPLIER: f64 = 0.0675;
+const PERFORMANCE_BASE_MULTIPLIER: f64 = 1.15;
+
+const AIM_SKILL_MULTIPLIER: f64 = 25.18;
+const AIM_STRAIN_DECAY_BASE: f64 = 0.15;
+const SPEED_SKILL_MULTIPLIER: f64 = 1.430;
+const SPEED_STRAIN_DECAY_BASE: f64 = 0.3;
+const FLASHLIGHT_SKILL_MULTIPLIER: f64 = 0.05512;
+const FLASHLIGHT_STRAIN_DECAY_BASE: f64 = 0.15;
+
+/// The osu!standard difficulty of a beatmap, as calculated by osu!lazer.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct OsuAttributes {
+    pub star_rating: f64,
+    pub aim: f64,
+    pub speed: f64,
+    /// Only calculated with Flashlight enabled, 0 otherwise.
+    pub flashlight: f64,
+    /// Aim without slider paths compared to aim with them.
+    pub slider_factor: f64,
+    pub aim_difficult_slider_count: f64,
+    pub aim_difficult_strain_count: f64,
+    pub speed_difficult_strain_count: f64,
+    /// Roughly how many objects are relevant to speed.
+    pub speed_note_count: f64,
+    /// Approach rate and overall difficulty as they feel once the rate is applied.
+    pub approach_rate: f64,
+    pub overall_difficulty: f64,
+    pub drain_rate: f64,
+    pub max_combo: i64,
+    pub circle_count: i64,
+    pub slider_count: i64,
+    pub spinner_count: i64,
+}
+
+// A hit object reduced to what the skills look at.
+// Positions include the stack offset, times aren't rate adjusted.
+struct BaseObject {
+    is_slider: bool,
+    is_spinner: bool,
+    start_time: f64,
+    position: (f64, f64),
+    end_position: (f64, f64),
+    // Where the cursor ends up when following a slider as lazily as possible.
+    lazy_end_position: (f64, f64),
+    lazy_travel_distance: f64,
+    lazy_travel_time: f64,
+    repeat_count: i64,
+}
+
+// An object along with how it's reached from the objects before it.
+struct DifficultyObject<'a> {
+    base: &'a BaseObject,
+    start_time: f64,
+    delta_time: f64,
+    strain_time: f64,
+    lazy_jump_distance: f64,
+    minimum_jump_distance: f64,
+    minimum_jump_time: f64,
+    travel_distance: f64,
+    travel_time: f64,
+    angle: Option<f64>,
+    hit_window_great: f64,
+}
+
+impl OsuAttributes {
+    /// Calculates the difficulty of a beatmap with mods applied.
+    ///
+    /// The chart is treated as osu!standard whatever its mode.
+    /// `file_format` decides how objects stack and how slider ticks are spaced,
+    /// see [`Chart::stacking`] and [`Slider::tick_distance`].
+    pub fn calculate(adjusted: &Adjusted, file_format: u8) -> OsuAttributes {
+        let chart = &adjusted.chart;
+        let difficulty = &adjusted.difficulty;
+        let mods = adjusted.mods;
+        let clock_rate = adjusted.clock_rate;
+        let timing = chart.timing_map();
+
+        let mut attributes = OsuAttributes {
+            star_rating: 0.0,
+            aim: 0.0,
+            speed: 0.0,
+            flashlight: 0.0,
+            slider_factor: 1.0,
+            aim_difficult_slider_count: 0.0,
+            aim_difficult_strain_count: 0.0,
+            speed_difficult_strain_count: 0.0,
+            speed_note_count: 0.0,
+            approach_rate: adjusted.approach_rate,
+            overall_difficulty: adjusted.overall_difficulty,
+            drain_rate: ratio_to_f64(difficulty.hpdrain_rate),
+            max_combo: 0,
+            circle_count: 0,
+            slider_count: 0,
+            spinner_count: 0,
+        };
+        for object in chart.hit_objects.iter() {
+            match object {
+                HitObject::Slider(slider) => {
+                    attributes.slider_count += 1;
+                    attributes.max_combo += combo_objects(slider, chart, &timing, file_format);
+                }
+                HitObject::Spinner(_) => {
+                    attributes.spinner_count += 1;
+                    attributes.max_combo += 1;
+                }
+                _ => {
+                    attributes.circle_count += 1;
+                    attributes.max_combo += 1;
+                }
+            }
+        }
+        if chart.hit_objects.is_empty() {
+            return attributes;
+        }
+
+        let radius = difficulty.circle_radius();
+        let stacks = chart.stacking(difficulty, file_format);
+        let bases: Vec<BaseObject> = chart
+            .hit_objects
+            .iter()
+            .zip(stacks.iter())
+            .map(|(object, stack)| {
+                base_object(object, chart, &timing, file_format, stack.offset, radius)
+            })
+            .collect();
+        let great = difficulty
+            .hit_windows(Mode::Osu)
+            .expect("osu!standard has hit windows")
+            .great;
+        let objects = difficulty_objects(&bases, clock_rate, radius, 2.0 * great / clock_rate);
+
+        let preempt = difficulty.preempt();
+        let fade_in = match mods.contains(Mods::HIDDEN) {
+            true => preempt * 0.4,
+            false => difficulty.fade_in(),
+        };
+        let opacity = Opacity {
+            preempt,
+            fade_in,
+            hidden: mods.contains(Mods::HIDDEN),
+        };
+
+        let aim = aim_skill(&objects, true);
+        let aim_value = reduced_weighted_sum(aim.0.peaks(), 10, 0.75, 0.9);
+        let mut aim_rating = aim_value.sqrt() * DIFFICULTY_MULTIPLIER;
+        let aim_difficult_strain_count =
+            count_top_weighted_strains(aim.0.object_strains(), aim_value);
+        let difficult_sliders = relevant_count(&aim.1);
+
+        let aim_no_sliders = aim_skill(&objects, false);
+        let aim_no_sliders_value = reduced_weighted_sum(aim_no_sliders.0.peaks(), 10, 0.75, 0.9);
+        let aim_rating_no_sliders = aim_no_sliders_value.sqrt() * DIFFICULTY_MULTIPLIER;
+        let slider_factor = match aim_rating > 0.0 {
+            true => aim_rating_no_sliders / aim_rating,
+            false => 1.0,
+        };
+
+        let speed = speed_skill(&objects);
+        let speed_value = reduced_weighted_sum(speed.peaks(), 5, 0.75, 0.9);
+        let mut speed_rating = speed_value.sqrt() * DIFFICULTY_MULTIPLIER;
+        let speed_note_count = relevant_count(speed.object_strains());
+        let speed_difficult_strain_count =
+            count_top_weighted_strains(speed.object_strains(), speed_value);
+
+        let mut flashlight_rating = match mods.contains(Mods::FLASHLIGHT) {
+            true => {
+                let flashlight = flashlight_skill(&objects, radius, &opacity);
+                flashlight.peaks().iter().sum::<f64>().sqrt() * DIFFICULTY_MULTIPLIER
+            }
+            false => 0.0,
+        };
+
+        if mods.contains(Mods::TOUCH_DEVICE) {
+            aim_rating = aim_rating.powf(0.8);
+            flashlight_rating = flashlight_rating.powf(0.8);
+        }
+        if mods.contains(Mods::RELAX) {
+            aim_rating *= 0.9;
+            speed_rating = 0.0;
+            flashlight_rating *= 0.7;
+        } else if mods.contains(Mods::AUTOPILOT) {
+            speed_rating *= 0.5;
+            aim_rating = 0.0;
+            flashlight_rating *= 0.4;
+        }
+
+        let base_aim_performance = difficulty_to_performance(aim_rating);
+        let base_speed_performance = difficulty_to_performance(speed_rating);
+        let base_flashlight_performance = match mods.contains(Mods::FLASHLIGHT) {
+            true => 25.0 * flashlight_rating.powi(2),
+            false => 0.0,
+        };
+        let base_performance = (base_aim_performance.powf(1.1)
+            + base_speed_performance.powf(1.1)
+            + base_flashlight_performance.powf(1.1))
+        .powf(1.0 / 1.1);
+        attributes.star_rating = match base_performance > 0.00001 {
+            true => {
+                PERFORMANCE_BASE_MULTIPLIER.cbrt()
+                    * 0.027
+                    * ((100000.0 / 2f64.powf(1.0 / 1.1) * base_performance).cbrt() + 4.0)
+            }
+            false => 0.0,
+        };
+
+        attributes.aim = aim_rating;
+        attributes.speed = speed_rating;
+        attributes.flashlight = flashlight_rating;
+        attributes.slider_factor = slider_factor;
+        attributes.aim_difficult_slider_count = difficult_sliders;
+        attributes.aim_difficult_strain_count = aim_difficult_strain_count;
+        attributes.speed_difficult_strain_count = speed_difficult_strain_count;
+        attributes.speed_note_count = speed_note_count;
+        attributes
+    }
+}
+
+/// Converts an aim or speed rating into the performance it's worth.
+pub fn difficulty_to_performance(difficulty: f64) -> f64 {
+    (5.0 * (difficulty / 0.0675).max(1.0) - 4.0).powi(3) / 100000.0
+}
+
+// Heads, ticks, repeats and tails each give a combo.
+fn combo_objects(slider: &Slider, chart: &Chart, timing: &TimingMap, file_format: u8) -> i64 {
+    slider
+        .nested_objects(
+            timing,
+            ratio_to_f64(chart.slider_multiplier),
+            ratio_to_f64(chart.slider_tick_rate),
+            file_format,
+        )
+        .iter()
+        .filter(|nested| nested.kind != NestedKind::LegacyLastTick)
+        .count() as i64
+}
+
+fn add(a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    (a.0 + b.0, a.1 + b.1)
+}
+
+fn sub(a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+fn scale(a: (f64, f64), factor: f64) -> (f64, f64) {
+    (a.0 * factor, a.1 * factor)
+}
+
+fn length(a: (f64, f64)) -> f64 {
+    (a.0 * a.0 + a.1 * a.1).sqrt()
+}
+
+fn base_object(
+    object: &HitObject,
+    chart: &Chart,
+    timing: &TimingMap,
+    file_format: u8,
+    offset: (f64, f64),
+    radius: f64,
+) -> BaseObject {
+    let position = add(object.position(), offset);
+    let mut base = BaseObject {
+        is_slider: false,
+        is_spinner: matches!(object, HitObject::Spinner(_)),
+        start_time: object.start_time(),
+        position,
+        end_position: add(object.end_position(), offset),
+        lazy_end_position: position,
+        lazy_travel_distance: 0.0,
+        lazy_travel_time: 0.0,
+        repeat_count: 0,
+    };
+    if let HitObject::Slider(slider) = object {
+        base.is_slider = true;
+        base.repeat_count = slider.slides.max(1) - 1;
+        lazy_slider_cursor(
+            &mut base,
+            slider,
+            chart,
+            timing,
+            file_format,
+            offset,
+            radius,
+        );
+    }
+    base
+}
+
+// Follows the slider with the least movement the player can get away with.
+fn lazy_slider_cursor(
+    base: &mut BaseObject,
+    slider: &Slider,
+    chart: &Chart,
+    timing: &TimingMap,
+    file_format: u8,
+    offset: (f64, f64),
+    radius: f64,
+) {
+    let slider_multiplier = ratio_to_f64(chart.slider_multiplier);
+    let tick_rate = ratio_to_f64(chart.slider_tick_rate);
+    let start_time = slider.time as f64;
+    let duration = slider.duration(timing, slider_multiplier);
+    let span_duration = slider.span_duration(timing, slider_multiplier);
+    let mut tracking_end_time =
+        (start_time + duration + TAIL_LENIENCY).max(start_time + duration / 2.0);
+
+    let mut nested: Vec<_> = slider
+        .nested_objects(timing, slider_multiplier, tick_rate, file_format)
+        .into_iter()
+        .filter(|nested| nested.kind != NestedKind::LegacyLastTick)
+        .collect();
+    // When the last tick comes after the tracking end time, it's moved to the end.
+    // This is counter to how a player sees the slider, but matches lazer.
+    if let Some(index) = nested.iter().rposition(|n| n.kind == NestedKind::Tick) {
+        if nested[index].time > tracking_end_time {
+            tracking_end_time = nested[index].time;
+            let tick = nested.remove(index);
+            nested.push(tick);
+        }
+    }
+
+    base.lazy_travel_time = tracking_end_time - start_time;
+    let mut end_time_min = match span_duration > 0.0 {
+        true => base.lazy_travel_time / span_duration,
+        false => 0.0,
+    };
+    if end_time_min % 2.0 >= 1.0 {
+        end_time_min = 1.0 - end_time_min % 1.0;
+    } else {
+        end_time_min %= 1.0;
+    }
+    let lazy_end_position = add(slider.path().position_at(end_time_min), offset);
+
+    let mut cursor = base.position;
+    // The thresholds assume a normalised radius.
+    let scaling_factor = NORMALISED_RADIUS / radius;
+    let last = nested.len().saturating_sub(1);
+    for (i, object) in nested.iter().enumerate().skip(1) {
+        let mut movement = sub(add(object.position, offset), cursor);
+        let mut movement_length = scaling_factor * length(movement);
+        let mut required_movement = ASSUMED_SLIDER_RADIUS;
+        if i == last {
+            // The player takes whichever of the lazy end and the real end is closer.
+            let lazy_movement = sub(lazy_end_position, cursor);
+            if length(lazy_movement) < length(movement) {
+                movement = lazy_movement;
+            }
+            movement_length = scaling_factor * length(movement);
+        } else if object.kind == NestedKind::Repeat {
+            // Repeats need tighter movement.
+            required_movement = NORMALISED_RADIUS;
+        }
+        if movement_length > required_movement {
+            let factor = (movement_length - required_movement) / movement_length;
+            cursor = add(cursor, scale(movement, factor));
+            movement_length *= factor;
+            base.lazy_travel_distance += movement_length;
+        }
+    }
+    base.lazy_end_position = match nested.len() > 1 {
+        true => cursor,
+        false => lazy_end_position,
+    };
+}
+
+fn difficulty_objects(
+    bases: &[BaseObject],
+    clock_rate: f64,
+    radius: f64,
+    hit_window_great: f64,
+) -> Vec<DifficultyObject<'_>> {
+    // Distances are scaled so every circle size is treated the same,
+    // with a bonus for very small circles.
+    let mut scaling_factor = NORMALISED_RADIUS / radius;
+    if radius < 30.0 {
+        let small_circle_bonus = (30.0 - radius).min(5.0) / 50.0;
+        scaling_factor *= 1.0 + small_circle_bonus;
+    }
+
+    let mut objects = Vec::with_capacity(bases.len());
+    for i in 1..bases.len() {
+        let current = &bases[i];
+        let last = &bases[i - 1];
+        let last_last = match i > 1 {
+            true => Some(&bases[i - 2]),
+            false => None,
+        };
+        let delta_time = (current.start_time - last.start_time) / clock_rate;
+        let strain_time = delta_time.max(MIN_DELTA_TIME);
+        let mut object = DifficultyObject {
+            base: current,
+            start_time: current.start_time / clock_rate,
+            delta_time,
+            strain_time,
+            lazy_jump_distance: 0.0,
+            minimum_jump_distance: 0.0,
+            minimum_jump_time: 0.0,
+            travel_distance: 0.0,
+            travel_time: 0.0,
+            angle: None,
+            hit_window_great,
+        };
+
+        if current.is_slider {
+            // Bonus for repeat sliders until there's a better system for nested objects.
+            object.travel_distance = current.lazy_travel_distance
+                * (1.0 + current.repeat_count as f64 / 2.5).powf(1.0 / 2.5);
+            object.travel_time = (current.lazy_travel_time / clock_rate).max(MIN_DELTA_TIME);
+        }
+        if current.is_spinner || last.is_spinner {
+            objects.push(object);
+            continue;
+        }
+
+        let last_cursor = last.lazy_end_position;
+        object.lazy_jump_distance = length(sub(
+            scale(current.position, scaling_factor),
+            scale(last_cursor, scaling_factor),
+        ));
+        object.minimum_jump_time = strain_time;
+        object.minimum_jump_distance = object.lazy_jump_distance;
+        if last.is_slider {
+            let last_travel_time = (last.lazy_travel_time / clock_rate).max(MIN_DELTA_TIME);
+            object.minimum_jump_time = (strain_time - last_travel_time).max(MIN_DELTA_TIME);
+            // The player either jumps from the lazy end or from the tail, whichever is shorter.
+            let tail_jump_distance =
+                length(sub(last.end_position, current.position)) * scaling_factor;
+            object.minimum_jump_distance = (object.lazy_jump_distance
+                - (MAXIMUM_SLIDER_RADIUS - ASSUMED_SLIDER_RADIUS))
+                .min(tail_jump_distance - MAXIMUM_SLIDER_RADIUS)
+                .max(0.0);
+        }
+        if let Some(last_last) = last_last.filter(|object| !object.is_spinner) {
+            let v1 = sub(last_last.lazy_end_position, last.position);
+            let v2 = sub(current.position, last_cursor);
+            let dot = v1.0 * v2.0 + v1.1 * v2.1;
+            let det = v1.0 * v2.1 - v1.1 * v2.0;
+            object.angle = Some(det.atan2(dot).abs());
+        }
+        objects.push(object);
+    }
+    objects
+}
+
+impl DifficultyObject<'_> {
+    // How likely the object is to be doubletapped along with the next one.
+    fn doubletapness(&self, next: Option<&DifficultyObject>) -> f64 {
+        let Some(next) = next else {
+            return 0.0;
+        };
+        let current_delta_time = self.delta_time.max(1.0);
+        let next_delta_time = next.delta_time.max(1.0);
+        let delta_difference = (next_delta_time - current_delta_time).abs();
+        let speed_ratio = current_delta_time / current_delta_time.max(delta_difference);
+        let window_ratio = (current_delta_time / self.hit_window_great)
+            .min(1.0)
+            .powi(2);
+        1.0 - speed_ratio.powf(1.0 - window_ratio)
+    }
+}
+
+// Returns the previous object `back` objects before `index`, counting from 0.
+fn previous<'a, 'b>(
+    objects: &'a [DifficultyObject<'b>],
+    index: usize,
+    back: usize,
+) -> Option<&'a DifficultyObject<'b>> {
+    index.checked_sub(back + 1).map(|i| &objects[i])
+}
+
+fn aim_skill(objects: &[DifficultyObject], with_sliders: bool) -> (StrainPeaks, Vec<f64>) {
+    let decay = |ms: f64| AIM_STRAIN_DECAY_BASE.powf(ms / 1000.0);
+    let mut peaks = StrainPeaks::new(400.0);
+    let mut slider_strains = Vec::new();
+    let mut current_strain = 0.0;
+    for (i, object) in objects.iter().enumerate() {
+        let previous_start = previous(objects, i, 0).map_or(0.0, |o| o.start_time);
+        peaks.start_object(i, object.start_time, |time| {
+            current_strain * decay(time - previous_start)
+        });
+        current_strain *= decay(object.delta_time);
+        current_strain += evaluate_aim(objects, i, with_sliders) * AIM_SKILL_MULTIPLIER;
+        if object.base.is_slider {
+            slider_strains.push(current_strain);
+        }
+        peaks.add_strain(current_strain);
+    }
+    (peaks, slider_strains)
+}
+
+fn speed_skill(objects: &[DifficultyObject]) -> StrainPeaks {
+    let decay = |ms: f64| SPEED_STRAIN_DECAY_BASE.powf(ms / 1000.0);
+    let mut peaks = StrainPeaks::new(400.0);
+    let mut current_strain = 0.0;
+    let mut current_rhythm = 0.0;
+    for (i, object) in objects.iter().enumerate() {
+        let previous_start = previous(objects, i, 0).map_or(0.0, |o| o.start_time);
+        peaks.start_object(i, object.start_time, |time| {
+            current_strain * current_rhythm * decay(time - previous_start)
+        });
+        current_strain *= decay(object.strain_time);
+        current_strain += evaluate_speed(objects, i) * SPEED_SKILL_MULTIPLIER;
+        current_rhythm = evaluate_rhythm(objects, i);
+        peaks.add_strain(current_strain * current_rhythm);
+    }
+    peaks
+}
+
+fn flashlight_skill(objects: &[DifficultyObject], radius: f64, opacity: &Opacity) -> StrainPeaks {
+    let decay = |ms: f64| FLASHLIGHT_STRAIN_DECAY_BASE.powf(ms / 1000.0);
+    let mut peaks = StrainPeaks::new(400.0);
+    let mut current_strain = 0.0;
+    for (i, object) in objects.iter().enumerate() {
+        let previous_start = previous(objects, i, 0).map_or(0.0, |o| o.start_time);
+        peaks.start_object(i, object.start_time, |time| {
+            current_strain * decay(time - previous_start)
+        });
+        current_strain *= decay(object.delta_time);
+        current_strain +=
+            evaluate_flashlight(objects, i, radius, opacity) * FLASHLIGHT_SKILL_MULTIPLIER;
+        peaks.add_strain(current_strain);
+    }
+    peaks
+}
+
+fn wide_angle_bonus(angle: f64) -> f64 {
+    (3.0 / 4.0 * (angle.clamp(PI / 6.0, 5.0 / 6.0 * PI) - PI / 6.0))
+        .sin()
+        .powi(2)
+}
+
+fn acute_angle_bonus(angle: f64) -> f64 {
+    1.0 - wide_angle_bonus(angle)
+}
+
+fn evaluate_aim(objects: &[DifficultyObject], index: usize, with_sliders: bool) -> f64 {
+    const WIDE_ANGLE_MULTIPLIER: f64 = 1.5;
+    const ACUTE_ANGLE_MULTIPLIER: f64 = 1.95;
+    const SLIDER_MULTIPLIER: f64 = 1.35;
+    const VELOCITY_CHANGE_MULTIPLIER: f64 = 0.75;
+
+    let current = &objects[index];
+    let (Some(last), Some(last_last)) = (previous(objects, index, 0), previous(objects, index, 1))
+    else {
+        return 0.0;
+    };
+    if current.base.is_spinner || last.base.is_spinner {
+        return 0.0;
+    }
+
+    // Velocity to the current object, assuming the last one is a circle.
+    let mut current_velocity = current.lazy_jump_distance / current.strain_time;
+    // If it's a slider, the velocity through it carries into the jump.
+    if last.base.is_slider && with_sliders {
+        let travel_velocity = last.travel_distance / last.travel_time;
+        let movement_velocity = current.minimum_jump_distance / current.minimum_jump_time;
+        current_velocity = current_velocity.max(movement_velocity + travel_velocity);
+    }
+    let mut previous_velocity = last.lazy_jump_distance / last.strain_time;
+    if last_last.base.is_slider && with_sliders {
+        let travel_velocity = last_last.travel_distance / last_last.travel_time;
+        let movement_velocity = last.minimum_jump_distance / last.minimum_jump_time;
+        previous_velocity = previous_velocity.max(movement_velocity + travel_velocity);
+    }
+
+    let mut wide_bonus = 0.0;
+    let mut acute_bonus = 0.0;
+    let mut slider_bonus = 0.0;
+    let mut velocity_change_bonus = 0.0;
+    let mut aim_strain = current_velocity;
+
+    // Only when the rhythm stays the same.
+    if current.strain_time.max(last.strain_time) < 1.25 * current.strain_time.min(last.strain_time)
+    {
+        if let (Some(current_angle), Some(last_angle), Some(last_last_angle)) =
+            (current.angle, last.angle, last_last.angle)
+        {
+            // Angles are rewarded based on the smaller velocity.
+            let angle_bonus = current_velocity.min(previous_velocity);
+            wide_bonus = wide_angle_bonus(current_angle);
+            acute_bonus = acute_angle_bonus(current_angle);
+            // Only buff delta times beyond 300 bpm 1/2.
+            if current.strain_time > 100.0 {
+                acute_bonus = 0.0;
+            } else {
+                acute_bonus *= acute_angle_bonus(last_angle)
+                    * angle_bonus.min(NORMALISED_DIAMETER * 1.25 / current.strain_time)
+                    * (PI / 2.0 * ((100.0 - current.strain_time) / 25.0).min(1.0))
+                        .sin()
+                        .powi(2)
+                    * (PI / 2.0
+                        * (current
+                            .lazy_jump_distance
+                            .clamp(NORMALISED_RADIUS, NORMALISED_DIAMETER)
+                            - NORMALISED_RADIUS)
+                        / NORMALISED_RADIUS)
+                        .sin()
+                        .powi(2);
+            }
+            // Repeated wide angles are penalised, less so as the last angle gets more acute.
+            wide_bonus *=
+                angle_bonus * (1.0 - wide_bonus.min(wide_angle_bonus(last_angle).powi(3)));
+            // Repeated acute angles are penalised, less so as the angle before gets wider.
+            acute_bonus *=
+                0.5 + 0.5 * (1.0 - acute_bonus.min(acute_angle_bonus(last_last_angle).powi(3)));
+        }
+    }
+
+    if previous_velocity.max(current_velocity) != 0.0 {
+        // Differences use the average velocity over the whole object.
+        previous_velocity =
+            (last.lazy_jump_distance + last_last.travel_distance) / last.strain_time;
+        current_velocity =
+            (current.lazy_jump_distance + last.travel_distance) / current.strain_time;
+        let distance_ratio = (PI / 2.0 * (previous_velocity - current_velocity).abs()
+            / previous_velocity.max(current_velocity))
+        .sin()
+        .powi(2);
+        // Overlaps where the velocity still changes are rewarded up to 125 / strain time.
+        let overlap_velocity_buff = (NORMALISED_DIAMETER * 1.25
+            / current.strain_time.min(last.strain_time))
+        .min((previous_velocity - current_velocity).abs());
+        velocity_change_bonus = overlap_velocity_buff * distance_ratio;
+        // Rhythm changes are penalised.
+        velocity_change_bonus *= (current.strain_time.min(last.strain_time)
+            / current.strain_time.max(last.strain_time))
+        .powi(2);
+    }
+
+    if last.base.is_slider {
+        slider_bonus = last.travel_distance / last.travel_time;
+    }
+
+    aim_strain += (acute_bonus * ACUTE_ANGLE_MULTIPLIER).max(
+        wide_bonus * WIDE_ANGLE_MULTIPLIER + velocity_change_bonus * VELOCITY_CHANGE_MULTIPLIER,
+    );
+    if with_sliders {
+        aim_strain += slider_bonus * SLIDER_MULTIPLIER;
+    }
+    aim_strain
+}
+
+fn evaluate_speed(objects: &[DifficultyObject], index: usize) -> f64 {
+    // 1.25 circles between centers.
+    const SINGLE_SPACING_THRESHOLD: f64 = NORMALISED_DIAMETER * 1.25;
+    // 200 bpm 1/4.
+    const MIN_SPEED_BONUS: f64 = 75.0;
+    const SPEED_BALANCING_FACTOR: f64 = 40.0;
+    const DISTANCE_MULTIPLIER: f64 = 0.94;
+
+    let current = &objects[index];
+    if current.base.is_spinner {
+        return 0.0;
+    }
+    let mut strain_time = current.strain_time;
+    let doubletapness = 1.0 - current.doubletapness(objects.get(index + 1));
+
+    // Cap delta time to the great hit window.
+    // 0.93 keeps 260bpm OD8 streams from being nerfed harshly, while 0.92 limits the cap.
+    strain_time /= (strain_time / current.hit_window_great / 0.93).clamp(0.92, 1.0);
+
+    // Streams and bursts faster than 200bpm get a scaling bonus.
+    let speed_bonus = match strain_time < MIN_SPEED_BONUS {
+        true => 0.75 * ((MIN_SPEED_BONUS - strain_time) / SPEED_BALANCING_FACTOR).powi(2),
+        false => 0.0,
+    };
+    let travel_distance = previous(objects, index, 0).map_or(0.0, |o| o.travel_distance);
+    let distance = (travel_distance + current.minimum_jump_distance).min(SINGLE_SPACING_THRESHOLD);
+    let distance_bonus = (distance / SINGLE_SPACING_THRESHOLD).powf(3.95) * DISTANCE_MULTIPLIER;
+
+    (1.0 + speed_bonus + distance_bonus) * 1000.0 / strain_time * doubletapness
+}
+
+// A run of notes with the same delta time, used to find repeated rhythms.
+#[derive(Copy, Clone, PartialEq)]
+struct Island {
+    delta: i64,
+    delta_count: i64,
+}
+
+impl Island {
+    const EMPTY: Island = Island {
+        delta: i64::MAX,
+        delta_count: 0,
+    };
+
+    fn new(delta: i64) -> Island {
+        Island {
+            delta: delta.max(MIN_DELTA_TIME as i64),
+            delta_count: 1,
+        }
+    }
+
+    fn add_delta(&mut self, delta: i64) {
+        if self.delta == i64::MAX {
+            self.delta = delta.max(MIN_DELTA_TIME as i64);
+        }
+        self.delta_count += 1;
+    }
+
+    fn is_similar_polarity(&self, other: &Island) -> bool {
+        self.delta_count % 2 == other.delta_count % 2
+    }
+
+    fn equals(&self, other: &Island, epsilon: f64) -> bool {
+        ((self.delta - other.delta) as f64).abs() < epsilon && self.delta_count == other.delta_count
+    }
+}
+
+fn evaluate_rhythm(objects: &[DifficultyObject], index: usize) -> f64 {
+    const HISTORY_TIME_MAX: f64 = 5000.0;
+    const HISTORY_OBJECTS_MAX: usize = 32;
+    const RHYTHM_OVERALL_MULTIPLIER: f64 = 0.95;
+    const RHYTHM_RATIO_MULTIPLIER: f64 = 12.0;
+
+    let current = &objects[index];
+    if current.base.is_spinner {
+        return 0.0;
+    }
+    let epsilon = current.hit_window_great * 0.3;
+    let mut island = Island::EMPTY;
+    let mut previous_island = Island::EMPTY;
+    let mut island_counts: Vec<(Island, i64)> = Vec::new();
+    // The ratio at the start of the current island, to buff tighter rhythms.
+    let mut start_ratio = 0.0;
+    let mut first_delta_switch = false;
+    let historical_note_count = index.min(HISTORY_OBJECTS_MAX);
+
+    let mut rhythm_start = 0;
+    while rhythm_start + 2 < historical_note_count
+        && current.start_time - objects[index - rhythm_start - 1].start_time < HISTORY_TIME_MAX
+    {
+        rhythm_start += 1;
+    }
+    if rhythm_start == 0 {
+        return 1.0;
+    }
+
+    let mut rhythm_complexity_sum = 0.0;
+    let mut previous_object = &objects[index - rhythm_start - 1];
+    let mut last_object = &objects[index - rhythm_start - 2];
+    // From the furthest object back towards the current one.
+    for i in (1..=rhythm_start).rev() {
+        let current_object = &objects[index - i];
+        // Decays from the history to now.
+        let time_decay = (HISTORY_TIME_MAX - (current.start_time - current_object.start_time))
+            / HISTORY_TIME_MAX;
+        let note_decay = (historical_note_count - i) as f64 / historical_note_count as f64;
+        let historical_decay = note_decay.min(time_decay);
+
+        let current_delta = current_object.strain_time;
+        let previous_delta = previous_object.strain_time;
+        let last_delta = last_object.strain_time;
+
+        // Deltas that are multiples of each other get less of a bonus.
+        let delta_difference_ratio =
+            previous_delta.min(current_delta) / previous_delta.max(current_delta);
+        let current_ratio =
+            1.0 + RHYTHM_RATIO_MULTIPLIER * (PI / delta_difference_ratio).sin().powi(2).min(0.5);
+        // Large delta differences get less of a bonus.
+        let fraction = (previous_delta / current_delta).max(current_delta / previous_delta);
+        let fraction_multiplier = (2.0 - fraction / 8.0).clamp(0.0, 1.0);
+        let window_penalty =
+            (((previous_delta - current_delta).abs() - epsilon).max(0.0) / epsilon).min(1.0);
+        let mut effective_ratio = window_penalty * current_ratio * fraction_multiplier;
+
+        if first_delta_switch {
+            if (previous_delta - current_delta).abs() < epsilon {
+                // The island keeps going.
+                island.add_delta(current_delta as i64);
+            } else {
+                // Speed changes into a slider have an easy accuracy window.
+                if current_object.base.is_slider {
+                    effective_ratio *= 0.125;
+                }
+                // Speed changes from a slider are usually easier than between circles.
+                if previous_object.base.is_slider {
+                    effective_ratio *= 0.3;
+                }
+                // Repeated island polarity, like 2 then 4.
+                if island.is_similar_polarity(&previous_island) {
+                    effective_ratio *= 0.5;
+                }
+                // The previous increase happened a note ago, like 1/1 to 1/2 to 1/4.
+                if last_delta > previous_delta + epsilon && previous_delta > current_delta + epsilon
+                {
+                    effective_ratio *= 0.125;
+                }
+                // Repeated island size, like triplet then triplet.
+                if previous_island.delta_count == island.delta_count {
+                    effective_ratio *= 0.5;
+                }
+
+                match island_counts
+                    .iter()
+                    .position(|(counted, _)| counted.equals(&island, epsilon))
+                {
+                    Some(count_index) => {
+                        // Only counted when the islands follow each other.
+                        if previous_island.equals(&island, epsilon) {
+                            island_counts[count_index].1 += 1;
+                        }
+                        let count = island_counts[count_index].1 as f64;
+                        let power = logistic(island.delta as f64, 58.33, 0.24, 2.75);
+                        effective_ratio *= (3.0 / count).min((1.0 / count).powf(power));
+                    }
+                    None => island_counts.push((island, 1)),
+                }
+
+                // Doubletappable objects are easier.
+                let doubletapness = previous_object.doubletapness(Some(current_object));
+                effective_ratio *= 1.0 - doubletapness * 0.75;
+
+                rhythm_complexity_sum += (effective_ratio * start_ratio).sqrt() * historical_decay;
+                start_ratio = effective_ratio;
+                previous_island = island;
+                // Slowing down stops the island, speeding up keeps counting it.
+                if previous_delta + epsilon < current_delta {
+                    first_delta_switch = false;
+                }
+                island = Island::new(current_delta as i64);
+            }
+        } else if previous_delta > current_delta + epsilon {
+            // Speeding up starts an island until the speed changes again.
+            first_delta_switch = true;
+            if current_object.base.is_slider {
+                effective_ratio *= 0.6;
+            }
+            if previous_object.base.is_slider {
+                effective_ratio *= 0.6;
+            }
+            start_ratio = effective_ratio;
+            island = Island::new(current_delta as i64);
+        }
+
+        last_object = previous_object;
+        previous_object = current_object;
+    }
+
+    // Ranges from 1 upwards.
+    (4.0 + rhythm_complexity_sum * RHYTHM_OVERALL_MULTIPLIER).sqrt() / 2.0
+}
+
+// How visible objects are, in unscaled map time.
+struct Opacity {
+    preempt: f64,
+    fade_in: f64,
+    hidden: bool,
+}
+
+impl Opacity {
+    fn at(&self, object_time: f64, time: f64) -> f64 {
+        // Objects count as invisible once their time has passed.
+        if time > object_time {
+            return 0.0;
+        }
+        let fade_in_start_time = object_time - self.preempt;
+        let fade_in = ((time - fade_in_start_time) / self.fade_in).clamp(0.0, 1.0);
+        if self.hidden {
+            let fade_out_start_time = object_time - self.preempt + self.fade_in;
+            let fade_out_duration = self.preempt * 0.3;
+            return fade_in
+                .min(1.0 - ((time - fade_out_start_time) / fade_out_duration).clamp(0.0, 1.0));
+        }
+        fade_in
+    }
+}
+
+fn evaluate_flashlight(
+    objects: &[DifficultyObject],
+    index: usize,
+    radius: f64,
+    opacity: &Opacity,
+) -> f64 {
+    const MAX_OPACITY_BONUS: f64 = 0.4;
+    const HIDDEN_BONUS: f64 = 0.2;
+    const MIN_VELOCITY: f64 = 0.5;
+    const SLIDER_MULTIPLIER: f64 = 1.3;
+    const MIN_ANGLE_MULTIPLIER: f64 = 0.2;
+
+    let current = &objects[index];
+    if current.base.is_spinner {
+        return 0.0;
+    }
+    let scaling_factor = 52.0 / radius;
+    let mut small_distance_nerf = 1.0;
+    let mut cumulative_strain_time = 0.0;
+    let mut result = 0.0;
+    let mut last_object = current;
+    let mut angle_repeat_count = 0.0;
+
+    // Going backwards in time from the current object.
+    for i in 0..index.min(10) {
+        let object = &objects[index - i - 1];
+        cumulative_strain_time += last_object.strain_time;
+        if !object.base.is_spinner {
+            let jump_distance = length(sub(current.base.position, object.base.end_position));
+            // Objects easily seen within the flashlight radius are nerfed.
+            if i == 0 {
+                small_distance_nerf = (jump_distance / 75.0).min(1.0);
+            }
+            // Only the first object of a stack counts.
+            let stack_nerf = (object.lazy_jump_distance / scaling_factor / 25.0).min(1.0);
+            // Bonus based on how visible the object is.
+            let opacity_bonus = 1.0
+                + MAX_OPACITY_BONUS
+                    * (1.0 - opacity.at(current.base.start_time, object.base.start_time));
+            result += stack_nerf * opacity_bonus * scaling_factor * jump_distance
+                / cumulative_strain_time;
+            if let (Some(angle), Some(current_angle)) = (object.angle, current.angle) {
+                // Objects further back count less.
+                if (angle - current_angle).abs() < 0.02 {
+                    angle_repeat_count += (1.0 - 0.1 * i as f64).max(0.0);
+                }
+            }
+        }
+        last_object = object;
+    }
+
+    result = (small_distance_nerf * result).powi(2);
+    // No approach circles with Hidden.
+    if opacity.hidden {
+        result *= 1.0 + HIDDEN_BONUS;
+    }
+    // Repeated angles are nerfed.
+    result *= MIN_ANGLE_MULTIPLIER + (1.0 - MIN_ANGLE_MULTIPLIER) / (angle_repeat_count + 1.0);
+
+    let mut slider_bonus = 0.0;
+    if current.base.is_slider {
+        // The real travel distance, whatever the circle size.
+        let pixel_travel_distance = current.base.lazy_travel_distance / scaling_factor;
+        slider_bonus = (pixel_travel_distance / current.travel_time - MIN_VELOCITY)
+            .max(0.0)
+            .sqrt();
+        // Longer sliders need more memorisation, repeats need less.
+        slider_bonus *= pixel_travel_distance;
+        if current.base.repeat_count > 0 {
+            slider_bonus /= (current.base.repeat_count + 1) as f64;
+        }
+    }
+    result + slider_bonus * SLIDER_MULTIPLIER
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::fixture;
+
+    const TOLERANCE: f64 = 0.001;
+
+    fn star_rating(name: &str, mods: Mods) -> f64 {
+        let fixture = fixture(name);
+        let adjusted = mods
+            .apply(&fixture.chart, &fixture.difficulty, None)
+            .unwrap();
+        OsuAttributes::calculate(&adjusted, fixture.filedata.file_format).star_rating
+    }
+
+    #[test]
+    fn fixture_star_ratings() {
+        for (mods, expected) in [
+            (Mods::NONE, 4.287801),
+            (Mods::DOUBLE_TIME, 6.075944),
+            (Mods::HARD_ROCK, 4.421152),
+            (Mods::HALF_TIME | Mods::EASY, 3.129581),
+        ] {
+            let star_rating = star_rating("osu.osu", mods);
+            assert!(
+                (star_rating - expected).abs() < TOLERANCE,
+                "{mods:?}: {star_rating} != {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn converted_charts_use_osu_hit_windows() {
+        let fixture = fixture("taiko.osu");
+        let mut as_osu = fixture.chart.clone();
+        as_osu.mode = Mode::Osu;
+        let rate = |chart: &Chart| {
+            let adjusted = Mods::NONE.apply(chart, &fixture.difficulty, None).unwrap();
+            OsuAttributes::calculate(&adjusted, fixture.filedata.file_format)
+        };
+        assert_eq!(rate(&fixture.chart), rate(&as_osu));
+    }
+}
@@ -0,0 +1,125 @@
+/// Tracks the highest strain in each fixed-length section of the map,
+/// as every strain-based skill in osu! does.
+pub struct StrainPeaks {
+    section_length: f64,
+    section_end: f64,
+    section_peak: f64,
+    peaks: Vec<f64>,
+    object_strains: Vec<f64>,
+}
+
+impl StrainPeaks {
+    pub fn new(section_length: f64) -> Self {
+        Self {
+            section_length,
+            section_end: 0.0,
+            section_peak: 0.0,
+            peaks: Vec::new(),
+            object_strains: Vec::new(),
+        }
+    }
+
+    /// Closes every section that ends before `start_time`.
+    ///
+    /// `initial_strain` gives the strain decayed to the start of a new section,
+    /// since a section's peak doesn't start at zero.
+    pub fn start_object(
+        &mut self,
+        index: usize,
+        start_time: f64,
+        initial_strain: impl Fn(f64) -> f64,
+    ) {
+        // The first object doesn't generate a strain, so we begin with an incremented section end.
+        if index == 0 {
+            self.section_end = (start_time / self.section_length).ceil() * self.section_length;
+        }
+        while start_time > self.section_end {
+            self.peaks.push(self.section_peak);
+            self.section_peak = initial_strain(self.section_end);
+            self.section_end += self.section_length;
+        }
+    }
+
+    pub fn add_strain(&mut self, strain: f64) {
+        self.section_peak = self.section_peak.max(strain);
+        self.object_strains.push(strain);
+    }
+
+    /// The peak of every section, including the one still open.
+    pub fn peaks(&self) -> Vec<f64> {
+        let mut peaks = self.peaks.clone();
+        peaks.push(self.section_peak);
+        peaks
+    }
+
+    pub fn object_strains(&self) -> &[f64] {
+        &self.object_strains
+    }
+}
+
+/// Sums the peaks from highest to lowest, each weighted less than the last.
+pub fn weighted_sum(mut peaks: Vec<f64>, decay_weight: f64) -> f64 {
+    peaks.sort_by(|a, b| b.total_cmp(a));
+    let mut difficulty = 0.0;
+    let mut weight = 1.0;
+    for peak in peaks {
+        difficulty += peak * weight;
+        weight *= decay_weight;
+    }
+    difficulty
+}
+
+/// Like [`weighted_sum`], but the highest peaks are reduced first
+/// to account for extreme difficulty spikes.
+pub fn reduced_weighted_sum(
+    peaks: Vec<f64>,
+    reduced_section_count: usize,
+    reduced_strain_baseline: f64,
+    decay_weight: f64,
+) -> f64 {
+    // Sections with no strain don't contribute.
+    let mut peaks: Vec<f64> = peaks.into_iter().filter(|&peak| peak > 0.0).collect();
+    peaks.sort_by(|a, b| b.total_cmp(a));
+    for (i, peak) in peaks.iter_mut().take(reduced_section_count).enumerate() {
+        let progress = (i as f32 / reduced_section_count as f32).clamp(0.0, 1.0) as f64;
+        let scale = lerp(1.0, 10.0, progress).log10();
+        *peak *= lerp(reduced_strain_baseline, 1.0, scale);
+    }
+    weighted_sum(peaks, decay_weight)
+}
+
+/// Roughly how many objects are as hard as the hardest ones.
+pub fn count_top_weighted_strains(object_strains: &[f64], difficulty_value: f64) -> f64 {
+    if object_strains.is_empty() {
+        return 0.0;
+    }
+    // What the top strain would be if every strain was the same.
+    let consistent_top_strain = difficulty_value / 10.0;
+    if consistent_top_strain == 0.0 {
+        return object_strains.len() as f64;
+    }
+    object_strains
+        .iter()
+        .map(|strain| 1.1 / (1.0 + (-10.0 * (strain / consistent_top_strain - 0.88)).exp()))
+        .sum()
+}
+
+/// How many strains are relevant compared to the highest one.
+pub fn relevant_count(strains: &[f64]) -> f64 {
+    let max_strain = strains.iter().copied().fold(0.0, f64::max);
+    if max_strain == 0.0 {
+        return 0.0;
+    }
+    strains
+        .iter()
+        .map(|strain| 1.0 / (1.0 + (-(strain / max_strain * 12.0 - 6.0)).exp()))
+        .sum()
+}
+
+pub fn lerp(start: f64, end: f64, amount: f64) -> f64 {
+    start + (end - start) * amount
+}
+
+pub fn logistic(x: f64, midpoint_offset: f64, multiplier: f64, max_value: f64) -> f64 {
+    max_value / (1.0 + (multiplier * (midpoint_offset - x)).exp())
+}
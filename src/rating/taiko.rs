@@ -0,0 +1,575 @@
+use crate::chart::Mode;
+use crate::chart::TaikoKind;
+use crate::chart::TaikoObject;
+use crate::mods::Adjusted;
+use crate::rating::strain::*;
+use crate::utility::difficulty_range;
+use crate::utility::ratio_to_f64;
+
+const DIFFICULTY_MULTIPLIER: f64 = 1.35;
+const FINAL_MULTIPLIER: f64 = 0.0625;
+const RHYTHM_SKILL_MULTIPLIER: f64 = 0.2 * FINAL_MULTIPLIER;
+const COLOUR_SKILL_MULTIPLIER: f64 = 0.375 * FINAL_MULTIPLIER;
+const STAMINA_SKILL_MULTIPLIER: f64 = 0.375 * FINAL_MULTIPLIER;
+
+const COLOUR_STRAIN_MULTIPLIER: f64 = 0.12;
+// Only the first note of each colour pattern has any difficulty,
+// so colour decays slower to let it build up on slower maps.
+const COLOUR_STRAIN_DECAY_BASE: f64 = 0.8;
+const RHYTHM_STRAIN_MULTIPLIER: f64 = 10.0;
+const RHYTHM_STRAIN_DECAY: f64 = 0.96;
+const RHYTHM_HISTORY_LENGTH: usize = 8;
+const STAMINA_STRAIN_MULTIPLIER: f64 = 1.1;
+const STAMINA_STRAIN_DECAY_BASE: f64 = 0.4;
+const MAX_REPETITION_INTERVAL: usize = 16;
+
+/// The osu!taiko difficulty of a beatmap, as calculated by osu!lazer.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TaikoAttributes {
+    pub star_rating: f64,
+    pub stamina: f64,
+    pub rhythm: f64,
+    pub colour: f64,
+    /// The three skills combined section by section, before rescaling.
+    pub peak: f64,
+    /// How much of the stamina comes from long streaks of one colour, from 0 to 1.
+    pub mono_stamina_factor: f64,
+    /// The great hit window in milliseconds once the rate is applied.
+    pub great_hit_window: f64,
+    /// Whether the beatmap was converted from osu!standard.
+    pub is_convert: bool,
+    pub max_combo: i64,
+}
+
+// A ratio between the current and previous gaps between notes,
+// and how hard it is to change to it.
+struct CommonRhythm {
+    ratio: f64,
+    difficulty: f64,
+}
+
+const COMMON_RHYTHMS: [CommonRhythm; 9] = [
+    CommonRhythm::new(1, 1, 0.0),
+    CommonRhythm::new(2, 1, 0.3),
+    CommonRhythm::new(1, 2, 0.5),
+    CommonRhythm::new(3, 1, 0.3),
+    CommonRhythm::new(1, 3, 0.35),
+    // Higher because full alternating players have to switch hands.
+    CommonRhythm::new(3, 2, 0.6),
+    CommonRhythm::new(2, 3, 0.4),
+    CommonRhythm::new(5, 4, 0.5),
+    CommonRhythm::new(4, 5, 0.7),
+];
+
+impl CommonRhythm {
+    const fn new(numerator: i64, denominator: i64, difficulty: f64) -> CommonRhythm {
+        CommonRhythm {
+            ratio: numerator as f64 / denominator as f64,
+            difficulty,
+        }
+    }
+}
+
+// An object with its rhythm and where it sits in the colour patterns.
+// Times are rate adjusted.
+struct DifficultyObject {
+    kind: TaikoKind,
+    start_time: f64,
+    delta_time: f64,
+    rhythm: usize,
+    // Index among dons or kats, whichever this is.
+    mono_index: Option<usize>,
+    mono_streak: usize,
+}
+
+// Notes of the same colour in a row.
+struct MonoStreak {
+    objects: Vec<usize>,
+    kind: TaikoKind,
+    parent: usize,
+    index: usize,
+}
+
+// Mono streaks of the same length in a row, such as `kddkkddkk`.
+struct AlternatingMonoPattern {
+    mono_streaks: Vec<usize>,
+    parent: usize,
+    index: usize,
+}
+
+// Alternating patterns that repeat, such as `ddkkddkk` followed by `dkdk`.
+struct RepeatingHitPatterns {
+    alternating: Vec<usize>,
+    repetition_interval: usize,
+}
+
+struct ColourPatterns {
+    mono_streaks: Vec<MonoStreak>,
+    alternating: Vec<AlternatingMonoPattern>,
+    repeating: Vec<RepeatingHitPatterns>,
+}
+
+impl TaikoAttributes {
+    /// Calculates the difficulty of a beatmap with mods applied.
+    ///
+    /// Charts from other modes are converted first, see [`Chart::taiko_objects`](crate::chart::Chart::taiko_objects).
+    pub fn calculate(adjusted: &Adjusted, file_format: u8) -> TaikoAttributes {
+        let chart = &adjusted.chart;
+        let clock_rate = adjusted.clock_rate;
+        let objects = chart.taiko_objects(&adjusted.difficulty, file_format);
+        let od = ratio_to_f64(adjusted.difficulty.overall_difficulty);
+
+        let mut attributes = TaikoAttributes {
+            star_rating: 0.0,
+            stamina: 0.0,
+            rhythm: 0.0,
+            colour: 0.0,
+            peak: 0.0,
+            mono_stamina_factor: 0.0,
+            great_hit_window: difficulty_range(od, 50.0, 35.0, 20.0) / clock_rate,
+            is_convert: chart.mode != Mode::Taiko,
+            max_combo: objects.iter().filter(|object| object.is_hit()).count() as i64,
+        };
+        if objects.is_empty() {
+            return attributes;
+        }
+
+        let mut difficulty_objects = difficulty_objects(&objects, clock_rate);
+        let patterns = colour_patterns(&mut difficulty_objects);
+        let colour = colour_skill(&difficulty_objects, &patterns);
+        let rhythm = rhythm_skill(&difficulty_objects);
+        let stamina = stamina_skill(&difficulty_objects, &patterns, false);
+        let mono_stamina = stamina_skill(&difficulty_objects, &patterns, true);
+
+        attributes.colour =
+            weighted_sum(colour.peaks(), 0.9) * COLOUR_SKILL_MULTIPLIER * DIFFICULTY_MULTIPLIER;
+        attributes.rhythm =
+            weighted_sum(rhythm.peaks(), 0.9) * RHYTHM_SKILL_MULTIPLIER * DIFFICULTY_MULTIPLIER;
+        attributes.stamina =
+            weighted_sum(stamina.peaks(), 0.9) * STAMINA_SKILL_MULTIPLIER * DIFFICULTY_MULTIPLIER;
+        let mono_stamina = weighted_sum(mono_stamina.peaks(), 0.9)
+            * STAMINA_SKILL_MULTIPLIER
+            * DIFFICULTY_MULTIPLIER;
+        attributes.mono_stamina_factor = match attributes.stamina {
+            0.0 => 1.0,
+            stamina => (mono_stamina / stamina).powi(5),
+        };
+
+        // Each section's peaks are combined before weighting.
+        let peaks: Vec<f64> = colour
+            .peaks()
+            .into_iter()
+            .zip(rhythm.peaks())
+            .zip(stamina.peaks())
+            .map(|((colour, rhythm), stamina)| {
+                let peak = norm(
+                    1.5,
+                    colour * COLOUR_SKILL_MULTIPLIER,
+                    stamina * STAMINA_SKILL_MULTIPLIER,
+                );
+                norm(2.0, peak, rhythm * RHYTHM_SKILL_MULTIPLIER)
+            })
+            .filter(|&peak| peak > 0.0)
+            .collect();
+        attributes.peak = weighted_sum(peaks, 0.9) * DIFFICULTY_MULTIPLIER;
+
+        let mut star_rating = rescale(attributes.peak * 1.4);
+        // Converts can often be played with more than two keys per side,
+        // which the skills don't account for.
+        if chart.mode == Mode::Osu {
+            star_rating *= 0.925;
+            // Especially when there's little colour change and a lot of stamina.
+            if attributes.colour < 2.0 && attributes.stamina > 8.0 {
+                star_rating *= 0.8;
+            }
+        }
+        attributes.star_rating = star_rating;
+        attributes
+    }
+}
+
+fn norm(p: f64, a: f64, b: f64) -> f64 {
+    (a.powf(p) + b.powf(p)).powf(1.0 / p)
+}
+
+fn rescale(star_rating: f64) -> f64 {
+    if star_rating < 0.0 {
+        return star_rating;
+    }
+    10.43 * (star_rating / 8.0 + 1.0).ln()
+}
+
+// The first two objects have nothing to compare their rhythm to, so they're skipped.
+fn difficulty_objects(objects: &[TaikoObject], clock_rate: f64) -> Vec<DifficultyObject> {
+    let mut difficulty_objects = Vec::with_capacity(objects.len().saturating_sub(2));
+    let mut don_count = 0;
+    let mut kat_count = 0;
+    for i in 2..objects.len() {
+        let delta_time = (objects[i].time - objects[i - 1].time) / clock_rate;
+        let previous_delta_time = (objects[i - 1].time - objects[i - 2].time) / clock_rate;
+        let ratio = delta_time / previous_delta_time;
+        // The first closest rhythm wins, including when the ratio isn't a number.
+        let mut rhythm = 0;
+        for (j, common) in COMMON_RHYTHMS.iter().enumerate() {
+            if (common.ratio - ratio).abs() < (COMMON_RHYTHMS[rhythm].ratio - ratio).abs() {
+                rhythm = j;
+            }
+        }
+        let mono_index = match objects[i].kind {
+            TaikoKind::Don => {
+                don_count += 1;
+                Some(don_count - 1)
+            }
+            TaikoKind::Kat => {
+                kat_count += 1;
+                Some(kat_count - 1)
+            }
+            _ => None,
+        };
+        difficulty_objects.push(DifficultyObject {
+            kind: objects[i].kind,
+            start_time: objects[i].time / clock_rate,
+            delta_time,
+            rhythm,
+            mono_index,
+            mono_streak: 0,
+        });
+    }
+    difficulty_objects
+}
+
+fn is_hit(kind: TaikoKind) -> bool {
+    matches!(kind, TaikoKind::Don | TaikoKind::Kat)
+}
+
+// Groups the objects into mono streaks, then alternating patterns, then repeating patterns.
+fn colour_patterns(objects: &mut [DifficultyObject]) -> ColourPatterns {
+    let mut mono_streaks: Vec<MonoStreak> = Vec::new();
+    let mut previous_note: Option<TaikoKind> = None;
+    for (i, object) in objects.iter_mut().enumerate() {
+        // Drumrolls and swells always start a streak of their own,
+        // but the next note is still compared to the note before them.
+        let starts_streak = match is_hit(object.kind) {
+            true => previous_note != Some(object.kind),
+            false => true,
+        };
+        if starts_streak || mono_streaks.is_empty() {
+            mono_streaks.push(MonoStreak {
+                objects: Vec::new(),
+                kind: object.kind,
+                parent: 0,
+                index: 0,
+            });
+        }
+        mono_streaks.last_mut().unwrap().objects.push(i);
+        object.mono_streak = mono_streaks.len() - 1;
+        if is_hit(object.kind) {
+            previous_note = Some(object.kind);
+        }
+    }
+
+    let mut alternating: Vec<AlternatingMonoPattern> = Vec::new();
+    for i in 0..mono_streaks.len() {
+        if i == 0 || mono_streaks[i].objects.len() != mono_streaks[i - 1].objects.len() {
+            alternating.push(AlternatingMonoPattern {
+                mono_streaks: Vec::new(),
+                parent: 0,
+                index: 0,
+            });
+        }
+        let pattern = alternating.len() - 1;
+        mono_streaks[i].parent = pattern;
+        mono_streaks[i].index = alternating[pattern].mono_streaks.len();
+        alternating[pattern].mono_streaks.push(i);
+    }
+
+    let first_length = |pattern: &AlternatingMonoPattern| -> usize {
+        mono_streaks[pattern.mono_streaks[0]].objects.len()
+    };
+    let is_repetition = |a: &AlternatingMonoPattern, b: &AlternatingMonoPattern| {
+        first_length(a) == first_length(b)
+            && a.mono_streaks.len() == b.mono_streaks.len()
+            && mono_streaks[a.mono_streaks[0]].kind == mono_streaks[b.mono_streaks[0]].kind
+    };
+    let is_coupled =
+        |i: usize| i + 2 < alternating.len() && is_repetition(&alternating[i], &alternating[i + 2]);
+    let mut repeating: Vec<RepeatingHitPatterns> = Vec::new();
+    let mut i = 0;
+    while i < alternating.len() {
+        let mut patterns = Vec::new();
+        if is_coupled(i) {
+            while is_coupled(i) {
+                patterns.push(i);
+                i += 1;
+            }
+            patterns.push(i);
+            patterns.push(i + 1);
+            i += 1;
+        } else {
+            patterns.push(i);
+        }
+        repeating.push(RepeatingHitPatterns {
+            alternating: patterns,
+            repetition_interval: MAX_REPETITION_INTERVAL + 1,
+        });
+        i += 1;
+    }
+
+    // How many patterns back the same pattern was last seen.
+    let is_repetition_of = |a: &RepeatingHitPatterns, b: &RepeatingHitPatterns| {
+        a.alternating.len() == b.alternating.len()
+            && a.alternating
+                .iter()
+                .zip(b.alternating.iter())
+                .take(2)
+                .all(|(&x, &y)| first_length(&alternating[x]) == first_length(&alternating[y]))
+    };
+    for i in 0..repeating.len() {
+        let interval = (1..MAX_REPETITION_INTERVAL)
+            .take_while(|&interval| interval <= i)
+            .find(|&interval| is_repetition_of(&repeating[i], &repeating[i - interval]));
+        repeating[i].repetition_interval = interval.unwrap_or(MAX_REPETITION_INTERVAL + 1);
+    }
+    for (i, pattern) in repeating.iter().enumerate() {
+        for (index, &j) in pattern.alternating.iter().enumerate() {
+            alternating[j].parent = i;
+            alternating[j].index = index;
+        }
+    }
+
+    ColourPatterns {
+        mono_streaks,
+        alternating,
+        repeating,
+    }
+}
+
+fn sigmoid(value: f64, center: f64, width: f64, middle: f64, height: f64) -> f64 {
+    let sigmoid = (std::f64::consts::E * -(value - center) / width).tanh();
+    sigmoid * (height / 2.0) + middle
+}
+
+// Only the first object of each pattern is given its difficulty.
+fn evaluate_colour(patterns: &ColourPatterns, index: usize, object: &DifficultyObject) -> f64 {
+    let repeating_difficulty = |i: usize| {
+        2.0 * (1.0
+            - sigmoid(
+                patterns.repeating[i].repetition_interval as f64,
+                2.0,
+                2.0,
+                0.5,
+                1.0,
+            ))
+    };
+    let alternating_difficulty = |i: usize| {
+        let pattern = &patterns.alternating[i];
+        sigmoid(pattern.index as f64, 2.0, 2.0, 0.5, 1.0) * repeating_difficulty(pattern.parent)
+    };
+
+    let streak = &patterns.mono_streaks[object.mono_streak];
+    let alternating = &patterns.alternating[streak.parent];
+    let repeating = &patterns.repeating[alternating.parent];
+    let first_of_alternating = |pattern: &AlternatingMonoPattern| {
+        patterns.mono_streaks[pattern.mono_streaks[0]].objects[0]
+    };
+
+    let mut difficulty = 0.0;
+    if streak.objects[0] == index {
+        difficulty += sigmoid(streak.index as f64, 2.0, 2.0, 0.5, 1.0)
+            * alternating_difficulty(streak.parent)
+            * 0.5;
+    }
+    if first_of_alternating(alternating) == index {
+        difficulty += alternating_difficulty(streak.parent);
+    }
+    if first_of_alternating(&patterns.alternating[repeating.alternating[0]]) == index {
+        difficulty += repeating_difficulty(alternating.parent);
+    }
+    difficulty
+}
+
+fn colour_skill(objects: &[DifficultyObject], patterns: &ColourPatterns) -> StrainPeaks {
+    let decay = |ms: f64| COLOUR_STRAIN_DECAY_BASE.powf(ms / 1000.0);
+    let mut peaks = StrainPeaks::new(400.0);
+    let mut current_strain = 0.0;
+    for (i, object) in objects.iter().enumerate() {
+        let previous_start = i.checked_sub(1).map_or(0.0, |j| objects[j].start_time);
+        peaks.start_object(i, object.start_time, |time| {
+            current_strain * decay(time - previous_start)
+        });
+        current_strain *= decay(object.delta_time);
+        current_strain += evaluate_colour(patterns, i, object) * COLOUR_STRAIN_MULTIPLIER;
+        peaks.add_strain(current_strain);
+    }
+    peaks
+}
+
+// Rhythm changes are harder when they come after a few notes of the same rhythm,
+// at higher speeds, and when the same change hasn't happened recently.
+fn rhythm_skill(objects: &[DifficultyObject]) -> StrainPeaks {
+    let mut peaks = StrainPeaks::new(400.0);
+    // The strain skills share a decay, but rhythm's is instant,
+    // so this only builds up between objects at the same time.
+    let mut skill_strain = 0.0;
+    let mut current_strain = 0.0;
+    let mut notes_since_rhythm_change = 0;
+    let mut history: Vec<usize> = Vec::with_capacity(RHYTHM_HISTORY_LENGTH);
+    for (i, object) in objects.iter().enumerate() {
+        let previous_start = i.checked_sub(1).map_or(0.0, |j| objects[j].start_time);
+        peaks.start_object(i, object.start_time, |time| {
+            skill_strain * 0f64.powf((time - previous_start) / 1000.0)
+        });
+
+        let value = 'value: {
+            // Drumrolls and swells reset the rhythm.
+            if !is_hit(object.kind) {
+                current_strain = 0.0;
+                notes_since_rhythm_change = 0;
+                break 'value 0.0;
+            }
+            current_strain *= RHYTHM_STRAIN_DECAY;
+            notes_since_rhythm_change += 1;
+            let rhythm = &COMMON_RHYTHMS[object.rhythm];
+            if rhythm.difficulty == 0.0 {
+                break 'value 0.0;
+            }
+
+            if history.len() == RHYTHM_HISTORY_LENGTH {
+                history.remove(0);
+            }
+            history.push(i);
+            let mut repetition_penalty = 1.0;
+            for compared in 2..=RHYTHM_HISTORY_LENGTH / 2 {
+                let recent = history.len().saturating_sub(compared);
+                for start in (0..history.len().saturating_sub(compared)).rev() {
+                    let same_pattern = (0..compared).all(|j| {
+                        objects[history[start + j]].rhythm == objects[history[recent + j]].rhythm
+                    });
+                    if same_pattern {
+                        let notes_since = i - history[start];
+                        repetition_penalty *= (0.032 * notes_since as f64).min(1.0);
+                        break;
+                    }
+                }
+            }
+
+            let short_pattern_penalty = (0.15 * notes_since_rhythm_change as f64).min(1.0);
+            let long_pattern_penalty =
+                (2.5 - 0.15 * notes_since_rhythm_change as f64).clamp(0.0, 1.0);
+            let length_penalty = short_pattern_penalty.min(long_pattern_penalty);
+
+            let speed_penalty = match object.delta_time {
+                delta_time if delta_time < 80.0 => 1.0,
+                delta_time if delta_time < 210.0 => (1.4 - 0.005 * delta_time).max(0.0),
+                // Slow notes reset the rhythm too.
+                _ => {
+                    current_strain = 0.0;
+                    0.0
+                }
+            };
+
+            let object_strain =
+                rhythm.difficulty * repetition_penalty * length_penalty * speed_penalty;
+            notes_since_rhythm_change = 0;
+            current_strain += object_strain;
+            current_strain
+        };
+
+        skill_strain *= 0f64.powf(object.delta_time / 1000.0);
+        skill_strain += value * RHYTHM_STRAIN_MULTIPLIER;
+        peaks.add_strain(skill_strain);
+    }
+    peaks
+}
+
+// Each note is played by one of two keys of its colour,
+// so the gap that matters is from two notes of the same colour before.
+// With `single_colour`, only the strain from deep into a mono streak counts.
+fn stamina_skill(
+    objects: &[DifficultyObject],
+    patterns: &ColourPatterns,
+    single_colour: bool,
+) -> StrainPeaks {
+    let decay = |ms: f64| STAMINA_STRAIN_DECAY_BASE.powf(ms / 1000.0);
+    let mut peaks = StrainPeaks::new(400.0);
+    let mut current_strain = 0.0;
+    let mut dons: Vec<f64> = Vec::new();
+    let mut kats: Vec<f64> = Vec::new();
+    for (i, object) in objects.iter().enumerate() {
+        let previous_start = i.checked_sub(1).map_or(0.0, |j| objects[j].start_time);
+        peaks.start_object(i, object.start_time, |time| match single_colour {
+            true => 0.0,
+            false => current_strain * decay(time - previous_start),
+        });
+
+        let same_colour = match object.kind {
+            TaikoKind::Don => Some(&mut dons),
+            TaikoKind::Kat => Some(&mut kats),
+            _ => None,
+        };
+        let value = match same_colour {
+            Some(same_colour) => {
+                let key_previous = object
+                    .mono_index
+                    .and_then(|index| index.checked_sub(2))
+                    .map(|index| same_colour[index]);
+                same_colour.push(object.start_time);
+                match key_previous {
+                    // Intervals are capped to avoid infinite values.
+                    Some(previous) => 0.5 + 30.0 / (object.start_time - previous).max(1.0),
+                    None => 0.0,
+                }
+            }
+            None => 0.0,
+        };
+
+        current_strain *= decay(object.delta_time);
+        current_strain += value * STAMINA_STRAIN_MULTIPLIER;
+        if single_colour {
+            let streak = &patterns.mono_streaks[object.mono_streak].objects;
+            let index = streak.iter().position(|&j| j == i).unwrap_or(0) as f64;
+            peaks.add_strain(current_strain / (1.0 + (-(index - 10.0) / 2.0).exp()));
+        } else {
+            peaks.add_strain(current_strain);
+        }
+    }
+    peaks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mods::Mods;
+    use crate::utility::{beatmap, DIFFICULTY};
+
+    // Notes every 100 milliseconds, with kats where `is_kat` says so.
+    fn mono_stamina_factor(is_kat: impl Fn(usize) -> bool) -> f64 {
+        let hit_objects: Vec<String> = (0..200)
+            .map(|i| {
+                let hitsound = if is_kat(i) { 2 } else { 0 };
+                format!("256,192,{},1,{hitsound},0:0:0:0:", 1000 + i * 100)
+            })
+            .collect();
+        let fixture = beatmap(
+            1,
+            DIFFICULTY,
+            "0,400,4,2,0,100,1,0",
+            &hit_objects.join("\n"),
+        );
+        let adjusted = Mods::NONE
+            .apply(&fixture.chart, &fixture.difficulty, None)
+            .unwrap();
+        TaikoAttributes::calculate(&adjusted, fixture.filedata.file_format).mono_stamina_factor
+    }
+
+    #[test]
+    fn mono_stamina_factor_follows_streak_length() {
+        let mono = mono_stamina_factor(|_| false);
+        let alternating = mono_stamina_factor(|i| i % 2 == 1);
+        let streaks_of_four = mono_stamina_factor(|i| i / 4 % 2 == 1);
+        assert!(mono > 0.9, "{mono}");
+        // Notes early in their streak count for 1 / (1 + e^5) at most.
+        assert!(alternating < 1e-10, "{alternating}");
+        assert!(alternating < streaks_of_four && streaks_of_four < mono);
+    }
+}
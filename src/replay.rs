@@ -0,0 +1,79 @@
+mod analysis;
+mod autoplay;
+mod read;
+mod write;
+
+pub use analysis::*;
+pub use autoplay::*;
+
+use crate::chart::Mode;
+use crate::gameplay::Frame;
+use crate::health::HealthPoint;
+use crate::mods::Mods;
+use crate::mods::ModsError;
+use crate::score::Judgements;
+use thiserror::Error;
+
+// Replays from this version on store a 64 bit online ID.
+const LONG_ONLINE_ID_VERSION: i32 = 20140721;
+// The time of the last frame when it holds the random seed instead of input.
+const SEED_FRAME_TIME: i64 = -12345;
+
+/// A play recorded in an .osr file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub mode: Mode,
+    /// The version of the game that recorded it, like 20140721.
+    pub version: i32,
+    /// MD5 hash of the .osu file, as lowercase hex.
+    pub beatmap_hash: String,
+    pub player: String,
+    /// MD5 hash of the replay, as lowercase hex.
+    pub replay_hash: String,
+    pub judgements: Judgements,
+    pub score: i64,
+    pub max_combo: i64,
+    /// No misses and no slider breaks.
+    pub perfect: bool,
+    pub mods: Mods,
+    /// Health over the play as stable drew it, sampled every couple of seconds.
+    pub life_bar: Vec<HealthPoint>,
+    /// When the play was set, in .NET ticks of 100 nanoseconds since 0001-01-01.
+    pub timestamp: i64,
+    /// Cursor and buttons over the play, in the beatmap's time.
+    ///
+    /// osu!mania stores the held columns in the cursor's x as a bit field,
+    /// and osu!catch only uses x. The frame giving the random seed isn't included.
+    pub frames: Vec<Frame>,
+    /// The random seed stable used for the play, which decides some visuals.
+    pub seed: Option<i32>,
+    /// 0 for plays that weren't submitted.
+    pub online_id: i64,
+    /// Target Practice's accuracy, only stored when that mod was on.
+    pub target_practice_accuracy: Option<f64>,
+}
+
+#[derive(Error, Debug)]
+pub enum ReplayError {
+    #[error("unexpected end of replay data")]
+    UnexpectedEnd,
+    #[error("invalid game mode {mode} in replay")]
+    InvalidMode { mode: u8 },
+    #[error("invalid string in replay")]
+    InvalidString,
+    #[error("invalid frame {frame:?} in replay")]
+    InvalidFrame { frame: String },
+    #[error("replays in {mode:?} can't be analysed")]
+    UnsupportedMode { mode: Mode },
+    #[error(transparent)]
+    Mods(#[from] ModsError),
+    #[error(transparent)]
+    Lzma(#[from] lzma_rs::error::Error),
+}
+
+impl Replay {
+    /// Reads a replay from the contents of an .osr file.
+    pub fn parse(bytes: &[u8]) -> Result<Replay, ReplayError> {
+        read::read_replay(bytes)
+    }
+}
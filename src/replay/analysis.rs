@@ -0,0 +1,99 @@
+use super::Replay;
+use super::ReplayError;
+use crate::chart::Chart;
+use crate::chart::Mode;
+use crate::collections::HitObject;
+use crate::difficulty::Difficulty;
+use crate::gameplay::judge_play;
+use crate::gameplay::JudgedPlay;
+use crate::score::Judgement;
+
+/// How the objects of a replay were hit.
+///
+/// Times are in real time, so they're scaled by the clock rate of Double Time and Half Time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayAnalysis {
+    /// The play judged from the replay's input.
+    pub play: JudgedPlay,
+    /// The index of each object that was hit, and how late it was hit in milliseconds.
+    /// Negative errors are early.
+    pub hit_errors: Vec<(usize, f64)>,
+    /// The average hit error, 0 when nothing was hit.
+    pub mean_error: f64,
+    /// Ten times the standard deviation of the hit errors.
+    pub unstable_rate: f64,
+    /// How many objects were hit early.
+    pub early_hits: usize,
+    /// How many objects were hit late.
+    pub late_hits: usize,
+    /// Indexes of the objects that were missed entirely.
+    pub misses: Vec<usize>,
+    /// Indexes of the sliders that were hit but broke combo on the way.
+    pub slider_breaks: Vec<usize>,
+}
+
+impl Replay {
+    /// Judges the replay's input against the beatmap it was played on.
+    ///
+    /// `chart` and `difficulty` are the beatmap's own, and the replay's mods are applied
+    /// to them, so Hard Rock's flip lines up with the recorded cursor.
+    /// Only osu!standard can be analysed.
+    pub fn analyse(
+        &self,
+        chart: &Chart,
+        difficulty: &Difficulty,
+        file_format: u8,
+    ) -> Result<ReplayAnalysis, ReplayError> {
+        if self.mode != Mode::Osu {
+            return Err(ReplayError::UnsupportedMode { mode: self.mode });
+        }
+        let adjusted = self.mods.apply(chart, difficulty, None)?;
+        let play = judge_play(&adjusted, file_format, &self.frames);
+
+        let clock_rate = adjusted.clock_rate;
+        let hit_errors: Vec<(usize, f64)> = play
+            .objects
+            .iter()
+            .filter_map(|object| Some((object.index, object.hit_error? / clock_rate)))
+            .collect();
+        let count = hit_errors.len() as f64;
+        let (mean_error, unstable_rate) = match hit_errors.is_empty() {
+            true => (0.0, 0.0),
+            false => {
+                let mean = hit_errors.iter().map(|(_, error)| error).sum::<f64>() / count;
+                let variance = hit_errors
+                    .iter()
+                    .map(|(_, error)| (error - mean).powi(2))
+                    .sum::<f64>()
+                    / count;
+                (mean, variance.sqrt() * 10.0)
+            }
+        };
+        let misses = play
+            .objects
+            .iter()
+            .filter(|object| object.judgement == Judgement::Miss)
+            .map(|object| object.index)
+            .collect();
+        let slider_breaks = play
+            .objects
+            .iter()
+            .filter(|object| {
+                matches!(chart.hit_objects[object.index], HitObject::Slider(_))
+                    && object.judgement != Judgement::Miss
+                    && object.broke_combo
+            })
+            .map(|object| object.index)
+            .collect();
+        Ok(ReplayAnalysis {
+            early_hits: hit_errors.iter().filter(|(_, error)| *error < 0.0).count(),
+            late_hits: hit_errors.iter().filter(|(_, error)| *error > 0.0).count(),
+            play,
+            hit_errors,
+            mean_error,
+            unstable_rate,
+            misses,
+            slider_breaks,
+        })
+    }
+}
@@ -0,0 +1,553 @@
+use super::Replay;
+use crate::chart::CatchKind;
+use crate::chart::ManiaKind;
+use crate::chart::ManiaObject;
+use crate::chart::Mode;
+use crate::chart::TaikoKind;
+use crate::collections::HitObject;
+use crate::customization::Break;
+use crate::gameplay::judge_play;
+use crate::gameplay::Buttons;
+use crate::gameplay::Frame;
+use crate::mods::Adjusted;
+use crate::mods::Mods;
+use crate::score::Judgements;
+use crate::score::ScoreV1;
+use crate::utility::ratio_to_f64;
+
+// Time between frames while the cursor moves, like a 60 fps recording.
+const FRAME_INTERVAL: f64 = 1000.0 / 60.0;
+// How long a key is held for objects that don't need holding.
+const KEY_UP_DELAY: f64 = 50.0;
+// Objects closer together than this are hit with alternating keys.
+const ALTERNATE_THRESHOLD: f64 = 266.0;
+// The playfield's centre, where spinners are spun around.
+const SPINNER_CENTRE: (f64, f64) = (256.0, 192.0);
+const SPIN_RADIUS: f64 = 50.0;
+// Radians per millisecond of real time, just under the 477 RPM stable counts at most.
+const SPIN_SPEED: f64 = 0.049;
+// How fast the catcher walks in osu!pixels per millisecond. Faster moves need a dash.
+const CATCHER_WALK_SPEED: f64 = 0.5;
+// The version autoplay replays are written as.
+const AUTOPLAY_VERSION: i32 = 20240101;
+// .NET ticks at the Unix epoch.
+const UNIX_EPOCH_TICKS: i64 = 621_355_968_000_000_000;
+
+/// Generates the input of a perfect play, like the Autoplay mod does.
+///
+/// In osu!standard the cursor moves to each object's stacked position, follows slider
+/// balls and spins spinners around the centre. osu!taiko alternates the drum keys,
+/// osu!catch moves the catcher under every fruit and dashes when walking is too slow,
+/// and osu!mania presses the columns in the cursor's x as a bit field, see [`Replay::frames`].
+///
+/// `mode` is the mode played in, which converts the chart when it differs.
+/// `breaks` are needed for the osu!mania conversion. `file_format` decides how objects
+/// stack and how slider ticks are spaced, see [`Chart::stacking`](crate::chart::Chart::stacking)
+/// and [`Slider::tick_distance`](crate::collections::Slider::tick_distance).
+pub fn autoplay_frames(
+    adjusted: &Adjusted,
+    mode: Mode,
+    breaks: &[Break],
+    file_format: u8,
+) -> Vec<Frame> {
+    match mode {
+        Mode::Osu => osu_frames(adjusted, file_format),
+        Mode::Taiko => taiko_frames(adjusted, file_format),
+        Mode::Catch => catch_frames(adjusted, file_format),
+        Mode::Mania => mania_frames(adjusted, breaks),
+    }
+}
+
+impl Replay {
+    /// A replay of [`autoplay_frames`], with the judgements and score of a perfect play.
+    ///
+    /// `beatmap_hash` is left empty and has to be set to the .osu file's MD5 hash
+    /// for the game to find the beatmap.
+    pub fn autoplay(adjusted: &Adjusted, mode: Mode, breaks: &[Break], file_format: u8) -> Replay {
+        let frames = autoplay_frames(adjusted, mode, breaks, file_format);
+        let (judgements, max_combo) =
+            perfect_judgements(adjusted, mode, breaks, file_format, &frames);
+        let score = ScoreV1::new(
+            mode,
+            &adjusted.chart,
+            &adjusted.original_difficulty,
+            breaks,
+            adjusted.mods,
+        )
+        .total(&judgements, max_combo);
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |since| since.as_micros() as i64 * 10)
+            + UNIX_EPOCH_TICKS;
+        Replay {
+            mode,
+            version: AUTOPLAY_VERSION,
+            beatmap_hash: String::new(),
+            player: "osu!".to_string(),
+            replay_hash: String::new(),
+            perfect: judgements.count_miss == 0,
+            judgements,
+            score,
+            max_combo,
+            mods: adjusted.mods,
+            life_bar: Vec::new(),
+            timestamp,
+            frames,
+            seed: None,
+            online_id: 0,
+            target_practice_accuracy: adjusted.mods.contains(Mods::TARGET).then_some(1.0),
+        }
+    }
+}
+
+// The counts and combo of a perfect play of `frames`.
+fn perfect_judgements(
+    adjusted: &Adjusted,
+    mode: Mode,
+    breaks: &[Break],
+    file_format: u8,
+    frames: &[Frame],
+) -> (Judgements, i64) {
+    let chart = &adjusted.chart;
+    let mut judgements = Judgements::default();
+    let combo = match mode {
+        Mode::Osu => {
+            let play = judge_play(adjusted, file_format, frames);
+            judgements = play.counts;
+            play.max_combo
+        }
+        Mode::Taiko => {
+            let objects = chart.taiko_objects(&adjusted.difficulty, file_format);
+            for object in objects.iter().filter(|object| object.is_hit()) {
+                judgements.count_300 += 1;
+                // Stable counts strong notes hit with both keys as gekis too.
+                if object.strong {
+                    judgements.count_geki += 1;
+                }
+            }
+            judgements.count_300
+        }
+        Mode::Catch => {
+            let hard_rock = adjusted.mods.contains(Mods::HARD_ROCK);
+            for object in chart.catch_objects(&adjusted.difficulty, hard_rock, file_format) {
+                match object.kind {
+                    CatchKind::Fruit => judgements.count_300 += 1,
+                    CatchKind::Droplet => judgements.count_100 += 1,
+                    CatchKind::TinyDroplet => judgements.count_50 += 1,
+                    CatchKind::Banana => {}
+                }
+            }
+            judgements.count_300 + judgements.count_100
+        }
+        Mode::Mania => {
+            judgements.count_geki = mania_objects(adjusted, breaks).len() as i64;
+            judgements.count_geki
+        }
+    };
+    (judgements, combo)
+}
+
+fn osu_frames(adjusted: &Adjusted, file_format: u8) -> Vec<Frame> {
+    let chart = &adjusted.chart;
+    let difficulty = &adjusted.difficulty;
+    let stacks = chart.stacking(difficulty, file_format);
+    let timing = chart.timing_map();
+    let slider_multiplier = ratio_to_f64(chart.slider_multiplier);
+    let mut cursor = Cursor::default();
+    let mut last_press: Option<(f64, Buttons)> = None;
+    let objects = &chart.hit_objects;
+    for (index, (object, stack)) in objects.iter().zip(stacks).enumerate() {
+        let time = object.start_time();
+        let next_time = objects.get(index + 1).map(|next| next.start_time());
+        let key = match last_press {
+            Some((last, key)) if time - last < ALTERNATE_THRESHOLD && key == left_key() => {
+                right_key()
+            }
+            _ => left_key(),
+        };
+        last_press = Some((time, key));
+        match object {
+            HitObject::Circle(_) => {
+                cursor.move_to(stack.position, time);
+                cursor.push(time, stack.position, key);
+                cursor.release(release_time(time, next_time));
+            }
+            HitObject::Slider(slider) => {
+                let path = slider.path();
+                let span_duration = slider.span_duration(&timing, slider_multiplier);
+                let end_time = time + span_duration * slider.slides.max(1) as f64;
+                let ball = |time| {
+                    let (x, y) = path.position_at(slider.progress_at(time, span_duration));
+                    (x + stack.offset.0, y + stack.offset.1)
+                };
+                cursor.move_to(stack.position, time);
+                let mut frame_time = time;
+                while frame_time < end_time {
+                    cursor.push(frame_time, ball(frame_time), key);
+                    frame_time += FRAME_INTERVAL;
+                }
+                cursor.push(end_time, ball(end_time), key);
+                cursor.release(end_time + FRAME_INTERVAL.min(release_gap(end_time, next_time)));
+            }
+            HitObject::Spinner(spinner) => {
+                let end_time = spinner.end_time as f64;
+                let spin = |time: f64| {
+                    // Counterclockwise on screen, starting above the centre.
+                    let angle = -(time - spinner.time as f64) * SPIN_SPEED / adjusted.clock_rate;
+                    (
+                        SPINNER_CENTRE.0 + SPIN_RADIUS * angle.sin(),
+                        SPINNER_CENTRE.1 - SPIN_RADIUS * angle.cos(),
+                    )
+                };
+                cursor.move_to(spin(time), time);
+                let mut frame_time = time;
+                while frame_time < end_time {
+                    cursor.push(frame_time, spin(frame_time), key);
+                    frame_time += FRAME_INTERVAL;
+                }
+                cursor.push(end_time, spin(end_time), key);
+                cursor.release(end_time + FRAME_INTERVAL.min(release_gap(end_time, next_time)));
+            }
+            HitObject::ManiaHold(_) => {}
+        }
+    }
+    cursor.frames
+}
+
+fn left_key() -> Buttons {
+    Buttons::MOUSE_1 | Buttons::KEY_1
+}
+
+fn right_key() -> Buttons {
+    Buttons::MOUSE_2 | Buttons::KEY_2
+}
+
+// Keys are let go after a short delay, or halfway to the next object when that's sooner.
+fn release_time(time: f64, next_time: Option<f64>) -> f64 {
+    time + KEY_UP_DELAY.min(release_gap(time, next_time))
+}
+
+fn release_gap(time: f64, next_time: Option<f64>) -> f64 {
+    next_time.map_or(f64::INFINITY, |next| (next - time) / 2.0)
+}
+
+// The osu!standard cursor, moving straight between objects.
+#[derive(Default)]
+struct Cursor {
+    frames: Vec<Frame>,
+}
+
+impl Cursor {
+    fn push(&mut self, time: f64, position: (f64, f64), buttons: Buttons) {
+        self.frames.push(Frame {
+            time,
+            position,
+            buttons,
+        });
+    }
+
+    fn position(&self) -> (f64, f64) {
+        self.frames
+            .last()
+            .map_or(SPINNER_CENTRE, |frame| frame.position)
+    }
+
+    fn release(&mut self, time: f64) {
+        let position = self.position();
+        self.push(time, position, Buttons::NONE);
+    }
+
+    // Moves without holding anything so the cursor reaches `target` at `time`.
+    fn move_to(&mut self, target: (f64, f64), time: f64) {
+        let (start, from) = match self.frames.last() {
+            Some(frame) => (frame.time, frame.position),
+            None => {
+                self.push(time - FRAME_INTERVAL, target, Buttons::NONE);
+                return;
+            }
+        };
+        let duration = time - start;
+        let mut frame_time = start + FRAME_INTERVAL;
+        while frame_time < time {
+            let progress = (frame_time - start) / duration;
+            let position = (
+                from.0 + (target.0 - from.0) * progress,
+                from.1 + (target.1 - from.1) * progress,
+            );
+            self.push(frame_time, position, Buttons::NONE);
+            frame_time += FRAME_INTERVAL;
+        }
+    }
+}
+
+fn taiko_frames(adjusted: &Adjusted, file_format: u8) -> Vec<Frame> {
+    let chart = &adjusted.chart;
+    // Stable's taiko keys: the mouse buttons are the left side, the keys the right side.
+    let centre = [Buttons::MOUSE_1, Buttons::KEY_1];
+    let rim = [Buttons::MOUSE_2, Buttons::KEY_2];
+    let timing = chart.timing_map();
+    let mut presses = Vec::new();
+    let mut side = 0;
+    let mut hit = |time: f64, keys: [Buttons; 2], strong: bool| {
+        let buttons = match strong {
+            true => keys[0] | keys[1],
+            false => keys[side],
+        };
+        side = 1 - side;
+        presses.push(Press::new(time, time + KEY_UP_DELAY, buttons));
+    };
+    for object in chart.taiko_objects(&adjusted.difficulty, file_format) {
+        match object.kind {
+            TaikoKind::Don => hit(object.time, centre, object.strong),
+            TaikoKind::Kat => hit(object.time, rim, object.strong),
+            TaikoKind::DrumRoll => {
+                // A hit every quarter of a beat covers the drum roll's ticks.
+                let interval = timing.beat_length_at(object.time) / 4.0;
+                let mut time = object.time;
+                while time <= object.end_time {
+                    hit(time, centre, object.strong);
+                    time += interval;
+                }
+            }
+            TaikoKind::Swell { required_hits } => {
+                let hits = required_hits.max(1);
+                let interval = (object.end_time - object.time) / hits as f64;
+                for i in 0..hits {
+                    let keys = match i % 2 {
+                        0 => centre,
+                        _ => rim,
+                    };
+                    hit(object.time + i as f64 * interval, keys, false);
+                }
+            }
+        }
+    }
+    held_over_time(presses)
+        .into_iter()
+        .map(|(time, buttons)| Frame {
+            time,
+            position: (0.0, 0.0),
+            buttons: Buttons(buttons as u8),
+        })
+        .collect()
+}
+
+fn catch_frames(adjusted: &Adjusted, file_format: u8) -> Vec<Frame> {
+    let hard_rock = adjusted.mods.contains(Mods::HARD_ROCK);
+    let objects = adjusted
+        .chart
+        .catch_objects(&adjusted.difficulty, hard_rock, file_format);
+    let mut targets: Vec<(f64, f64)> = objects
+        .iter()
+        .map(|object| (object.time, object.x))
+        .collect();
+    // Objects generated from a juice stream can come after the stream's own objects.
+    targets.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut frames: Vec<Frame> = Vec::new();
+    let mut catcher = Frame {
+        time: targets.first().map_or(0.0, |target| target.0) - FRAME_INTERVAL,
+        position: (SPINNER_CENTRE.0, 0.0),
+        buttons: Buttons::NONE,
+    };
+    frames.push(catcher);
+    for (time, x) in targets {
+        if time <= catcher.time {
+            continue;
+        }
+        let duration = time - catcher.time;
+        let from = catcher.position.0;
+        let dash = (x - from).abs() / duration > CATCHER_WALK_SPEED;
+        let buttons = match dash {
+            true => Buttons::MOUSE_1,
+            false => Buttons::NONE,
+        };
+        let mut frame_time = catcher.time + FRAME_INTERVAL;
+        while frame_time < time {
+            let progress = (frame_time - catcher.time) / duration;
+            frames.push(Frame {
+                time: frame_time,
+                position: (from + (x - from) * progress, 0.0),
+                buttons,
+            });
+            frame_time += FRAME_INTERVAL;
+        }
+        catcher = Frame {
+            time,
+            position: (x, 0.0),
+            buttons,
+        };
+        frames.push(catcher);
+    }
+    frames
+}
+
+fn mania_objects(adjusted: &Adjusted, breaks: &[Break]) -> Vec<ManiaObject> {
+    let chart = &adjusted.chart;
+    let key_count = chart.mania_key_count(&adjusted.original_difficulty, adjusted.mods);
+    chart.convert_to_mania(&adjusted.original_difficulty, breaks, key_count)
+}
+
+fn mania_frames(adjusted: &Adjusted, breaks: &[Break]) -> Vec<Frame> {
+    let presses = mania_objects(adjusted, breaks)
+        .into_iter()
+        .map(|object| Press {
+            time: object.time,
+            release: match object.kind {
+                ManiaKind::Note => object.time + KEY_UP_DELAY,
+                ManiaKind::Hold => object.end_time,
+            },
+            buttons: 1 << object.column,
+        })
+        .collect();
+    held_over_time(presses)
+        .into_iter()
+        .map(|(time, columns)| Frame {
+            time,
+            position: (columns as f64, 0.0),
+            buttons: Buttons::NONE,
+        })
+        .collect()
+}
+
+// Keys pressed at `time` and let go at `release`, as a bit field.
+struct Press {
+    time: f64,
+    release: f64,
+    buttons: u32,
+}
+
+impl Press {
+    fn new(time: f64, release: f64, buttons: Buttons) -> Self {
+        Press {
+            time,
+            release,
+            buttons: buttons.0 as u32,
+        }
+    }
+}
+
+// Which keys are held after each press and release, letting go of a key
+// just before it's pressed again.
+fn held_over_time(mut presses: Vec<Press>) -> Vec<(f64, u32)> {
+    presses.sort_by(|a, b| a.time.total_cmp(&b.time));
+    for i in 0..presses.len() {
+        let next = presses[i + 1..]
+            .iter()
+            .find(|next| next.buttons & presses[i].buttons != 0);
+        if let Some(next) = next {
+            let release = presses[i].release.min(next.time - 1.0);
+            presses[i].release = release.max(presses[i].time);
+        }
+    }
+    // Releases sort before presses, so a key let go and pressed together stays held.
+    let mut changes: Vec<(f64, bool, u32)> = presses
+        .iter()
+        .flat_map(|press| {
+            [
+                (press.time, true, press.buttons),
+                (press.release, false, press.buttons),
+            ]
+        })
+        .collect();
+    changes.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+    let mut held_counts = [0u32; 32];
+    let mut held = Vec::<(f64, u32)>::new();
+    for (time, pressed, buttons) in changes {
+        for (bit, count) in held_counts.iter_mut().enumerate() {
+            if buttons & (1 << bit) != 0 {
+                match pressed {
+                    true => *count += 1,
+                    false => *count = count.saturating_sub(1),
+                }
+            }
+        }
+        let bits = held_counts
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .fold(0, |bits, (bit, _)| bits | 1 << bit);
+        match held.last_mut() {
+            Some(last) if last.0 == time => last.1 = bits,
+            _ => held.push((time, bits)),
+        }
+    }
+    held
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rating::OsuAttributes;
+    use crate::score::Judgement;
+    use crate::utility::fixture;
+
+    #[test]
+    fn osu_autoplay_is_judged_perfect() {
+        let fixture = fixture("osu.osu");
+        let file_format = fixture.filedata.file_format;
+        for mods in [
+            Mods::NONE,
+            Mods::DOUBLE_TIME,
+            Mods::HALF_TIME,
+            Mods::HARD_ROCK,
+        ] {
+            let adjusted = mods
+                .apply(&fixture.chart, &fixture.difficulty, None)
+                .unwrap();
+            let frames = autoplay_frames(&adjusted, Mode::Osu, &[], file_format);
+            let play = judge_play(&adjusted, file_format, &frames);
+            assert_eq!(play.objects.len(), fixture.chart.hit_objects.len());
+            for object in play.objects.iter() {
+                assert_eq!(object.judgement, Judgement::Great, "{mods:?} {object:?}");
+                assert!(!object.broke_combo, "{mods:?} {object:?}");
+            }
+            let counts = play.counts;
+            assert_eq!(counts.count_300, fixture.chart.hit_objects.len() as i64);
+            assert_eq!(counts.count_100 + counts.count_50 + counts.count_miss, 0);
+            let attributes = OsuAttributes::calculate(&adjusted, file_format);
+            assert_eq!(play.max_combo, attributes.max_combo, "{mods:?}");
+        }
+    }
+
+    #[test]
+    fn autoplay_replays_round_trip() {
+        for (name, mode) in [
+            ("osu.osu", Mode::Osu),
+            ("taiko.osu", Mode::Taiko),
+            ("catch.osu", Mode::Catch),
+            ("mania.osu", Mode::Mania),
+        ] {
+            let fixture = fixture(name);
+            let adjusted = (Mods::HIDDEN | Mods::DOUBLE_TIME)
+                .apply(&fixture.chart, &fixture.difficulty, None)
+                .unwrap();
+            let mut replay = Replay::autoplay(&adjusted, mode, &[], fixture.filedata.file_format);
+            replay.beatmap_hash = "d41d8cd98f00b204e9800998ecf8427e".to_string();
+            replay.seed = Some(1234);
+            let parsed = Replay::parse(&replay.to_bytes()).expect("written replay parses");
+            // Only the frame times change, since they're written in whole milliseconds.
+            for frame in replay.frames.iter_mut() {
+                frame.time = frame.time.round();
+            }
+            assert_eq!(parsed, replay, "{name}");
+        }
+    }
+
+    #[test]
+    fn taiko_gekis_are_strong_notes() {
+        let fixture = fixture("taiko.osu");
+        let adjusted = Mods::NONE
+            .apply(&fixture.chart, &fixture.difficulty, None)
+            .unwrap();
+        let replay = Replay::autoplay(&adjusted, Mode::Taiko, &[], fixture.filedata.file_format);
+        let objects = fixture
+            .chart
+            .taiko_objects(&fixture.difficulty, fixture.filedata.file_format);
+        let strong = objects.iter().filter(|o| o.is_hit() && o.strong).count() as i64;
+        assert!(strong > 0);
+        assert_eq!(replay.judgements.count_geki, strong);
+        assert!(replay.judgements.count_geki < replay.judgements.count_300);
+        assert_eq!(replay.judgements.count_katu, 0);
+    }
+}
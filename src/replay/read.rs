@@ -0,0 +1,199 @@
+use super::Replay;
+use super::ReplayError;
+use super::LONG_ONLINE_ID_VERSION;
+use super::SEED_FRAME_TIME;
+use crate::chart::Mode;
+use crate::gameplay::Buttons;
+use crate::gameplay::Frame;
+use crate::health::HealthPoint;
+use crate::mods::Mods;
+use crate::score::Judgements;
+
+pub(super) fn read_replay(bytes: &[u8]) -> Result<Replay, ReplayError> {
+    let mut reader = Reader { bytes, position: 0 };
+    let mode = match reader.byte()? {
+        0 => Mode::Osu,
+        1 => Mode::Taiko,
+        2 => Mode::Catch,
+        3 => Mode::Mania,
+        mode => return Err(ReplayError::InvalidMode { mode }),
+    };
+    let version = reader.int()?;
+    let beatmap_hash = reader.string()?;
+    let player = reader.string()?;
+    let replay_hash = reader.string()?;
+    let count_300 = reader.short()? as i64;
+    let count_100 = reader.short()? as i64;
+    let count_50 = reader.short()? as i64;
+    let count_geki = reader.short()? as i64;
+    let count_katu = reader.short()? as i64;
+    let count_miss = reader.short()? as i64;
+    let judgements = Judgements {
+        count_300,
+        count_100,
+        count_50,
+        count_geki,
+        count_katu,
+        count_miss,
+    };
+    let score = reader.int()? as i64;
+    let max_combo = reader.short()? as i64;
+    let perfect = reader.byte()? != 0;
+    let mods = Mods(reader.int()? as u32);
+    let life_bar = life_bar(&reader.string()?)?;
+    let timestamp = reader.long()?;
+    let length = reader.int()?.max(0) as usize;
+    let compressed = reader.take(length)?;
+    let mut decompressed = Vec::new();
+    if !compressed.is_empty() {
+        lzma_rs::lzma_decompress(&mut &compressed[..], &mut decompressed)?;
+    }
+    let text = String::from_utf8(decompressed).map_err(|_| ReplayError::InvalidString)?;
+    let (frames, seed) = frames(&text)?;
+    let online_id = match version >= LONG_ONLINE_ID_VERSION {
+        true => reader.long()?,
+        false => reader.int()? as i64,
+    };
+    let target_practice_accuracy = match mods.contains(Mods::TARGET) {
+        true => Some(reader.double()?),
+        false => None,
+    };
+    Ok(Replay {
+        mode,
+        version,
+        beatmap_hash,
+        player,
+        replay_hash,
+        judgements,
+        score,
+        max_combo,
+        perfect,
+        mods,
+        life_bar,
+        timestamp,
+        frames,
+        seed,
+        online_id,
+        target_practice_accuracy,
+    })
+}
+
+// Pairs of time and health like `1200|0.95`, separated by commas.
+fn life_bar(s: &str) -> Result<Vec<HealthPoint>, ReplayError> {
+    s.split(',')
+        .filter(|point| !point.trim().is_empty())
+        .map(|point| {
+            let invalid = || ReplayError::InvalidFrame {
+                frame: point.to_string(),
+            };
+            let (time, health) = point.split_once('|').ok_or_else(invalid)?;
+            Ok(HealthPoint {
+                time: time.trim().parse().map_err(|_| invalid())?,
+                health: health.trim().parse().map_err(|_| invalid())?,
+            })
+        })
+        .collect()
+}
+
+// Frames like `16|256.5|192|1`: time since the previous frame, x, y and buttons.
+fn frames(s: &str) -> Result<(Vec<Frame>, Option<i32>), ReplayError> {
+    let mut frames = Vec::new();
+    let mut seed = None;
+    let mut time = 0;
+    for frame in s.split(',').filter(|frame| !frame.trim().is_empty()) {
+        let invalid = || ReplayError::InvalidFrame {
+            frame: frame.to_string(),
+        };
+        let fields: Vec<&str> = frame.split('|').map(str::trim).collect();
+        let [delta, x, y, buttons] = fields[..] else {
+            return Err(invalid());
+        };
+        let delta: i64 = delta.parse().map_err(|_| invalid())?;
+        if delta == SEED_FRAME_TIME {
+            seed = Some(buttons.parse().map_err(|_| invalid())?);
+            continue;
+        }
+        time += delta;
+        let buttons: i64 = buttons.parse().map_err(|_| invalid())?;
+        frames.push(Frame {
+            time: time as f64,
+            position: (
+                x.parse().map_err(|_| invalid())?,
+                y.parse().map_err(|_| invalid())?,
+            ),
+            buttons: Buttons(buttons as u8),
+        });
+    }
+    Ok((frames, seed))
+}
+
+// Reads the little endian values replays are made of.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], ReplayError> {
+        let end = self.position.checked_add(length);
+        let bytes = end
+            .and_then(|end| self.bytes.get(self.position..end))
+            .ok_or(ReplayError::UnexpectedEnd)?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ReplayError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn byte(&mut self) -> Result<u8, ReplayError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn short(&mut self) -> Result<u16, ReplayError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn int(&mut self) -> Result<i32, ReplayError> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+
+    fn long(&mut self) -> Result<i64, ReplayError> {
+        Ok(i64::from_le_bytes(self.array()?))
+    }
+
+    fn double(&mut self) -> Result<f64, ReplayError> {
+        Ok(f64::from_le_bytes(self.array()?))
+    }
+
+    fn uleb128(&mut self) -> Result<usize, ReplayError> {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as usize)
+                .checked_shl(shift)
+                .ok_or(ReplayError::InvalidString)?;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+            shift += 7;
+        }
+    }
+
+    // 0x00 for no string, or 0x0b followed by the length and UTF-8 bytes.
+    fn string(&mut self) -> Result<String, ReplayError> {
+        match self.byte()? {
+            0x00 => Ok(String::new()),
+            0x0b => {
+                let length = self.uleb128()?;
+                let bytes = self.take(length)?;
+                String::from_utf8(bytes.to_vec()).map_err(|_| ReplayError::InvalidString)
+            }
+            _ => Err(ReplayError::InvalidString),
+        }
+    }
+}
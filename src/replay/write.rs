@@ -0,0 +1,127 @@
+use super::Replay;
+use super::LONG_ONLINE_ID_VERSION;
+use super::SEED_FRAME_TIME;
+use crate::chart::Mode;
+use std::fmt::Write;
+
+impl Replay {
+    /// Writes the replay in the .osr format, the inverse of [`Replay::parse`].
+    ///
+    /// Frame times are rounded to whole milliseconds, like stable records them.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::default();
+        writer.byte(match self.mode {
+            Mode::Osu => 0,
+            Mode::Taiko => 1,
+            Mode::Catch => 2,
+            Mode::Mania => 3,
+        });
+        writer.int(self.version);
+        writer.string(&self.beatmap_hash);
+        writer.string(&self.player);
+        writer.string(&self.replay_hash);
+        let judgements = &self.judgements;
+        for count in [
+            judgements.count_300,
+            judgements.count_100,
+            judgements.count_50,
+            judgements.count_geki,
+            judgements.count_katu,
+            judgements.count_miss,
+        ] {
+            writer.short(count as u16);
+        }
+        writer.int(self.score as i32);
+        writer.short(self.max_combo as u16);
+        writer.byte(self.perfect as u8);
+        writer.int(self.mods.0 as i32);
+        writer.string(&self.life_bar_text());
+        writer.long(self.timestamp);
+        let mut compressed = Vec::new();
+        // Compressing into a Vec can't fail.
+        lzma_rs::lzma_compress(&mut self.frames_text().as_bytes(), &mut compressed).unwrap();
+        writer.int(compressed.len() as i32);
+        writer.bytes.extend(compressed);
+        match self.version >= LONG_ONLINE_ID_VERSION {
+            true => writer.long(self.online_id),
+            false => writer.int(self.online_id as i32),
+        }
+        if let Some(accuracy) = self.target_practice_accuracy {
+            writer.bytes.extend(accuracy.to_le_bytes());
+        }
+        writer.bytes
+    }
+
+    fn life_bar_text(&self) -> String {
+        let mut s = String::new();
+        for point in self.life_bar.iter() {
+            // Writing to a String can't fail.
+            write!(s, "{}|{},", point.time, point.health).unwrap();
+        }
+        s
+    }
+
+    fn frames_text(&self) -> String {
+        let mut s = String::new();
+        let mut time = 0;
+        for frame in self.frames.iter() {
+            // Deltas between rounded times, so rounding errors don't add up.
+            let frame_time = frame.time.round() as i64;
+            let (x, y) = frame.position;
+            // Writing to a String can't fail.
+            write!(s, "{}|{}|{}|{},", frame_time - time, x, y, frame.buttons.0).unwrap();
+            time = frame_time;
+        }
+        if let Some(seed) = self.seed {
+            write!(s, "{SEED_FRAME_TIME}|0|0|{seed},").unwrap();
+        }
+        s
+    }
+}
+
+// Writes the little endian values replays are made of.
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn byte(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn short(&mut self, value: u16) {
+        self.bytes.extend(value.to_le_bytes());
+    }
+
+    fn int(&mut self, value: i32) {
+        self.bytes.extend(value.to_le_bytes());
+    }
+
+    fn long(&mut self, value: i64) {
+        self.bytes.extend(value.to_le_bytes());
+    }
+
+    fn uleb128(&mut self, mut value: usize) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.byte(byte);
+                return;
+            }
+            self.byte(byte | 0x80);
+        }
+    }
+
+    // 0x00 for no string, or 0x0b followed by the length and UTF-8 bytes.
+    fn string(&mut self, s: &str) {
+        if s.is_empty() {
+            self.byte(0x00);
+            return;
+        }
+        self.byte(0x0b);
+        self.uleb128(s.len());
+        self.bytes.extend(s.as_bytes());
+    }
+}
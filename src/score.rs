@@ -0,0 +1,616 @@
+use crate::chart::Chart;
+use crate::chart::Mode;
+use crate::customization::Break;
+use crate::difficulty::Difficulty;
+use crate::mods::Mods;
+use crate::utility::ratio_to_f64;
+
+/// How many of each judgement a play got, counted like stable's scores.
+///
+/// What each count means depends on the mode:
+///
+/// | Count        | osu!standard | osu!taiko | osu!catch            | osu!mania |
+/// |--------------|--------------|-----------|----------------------|-----------|
+/// | `count_300`  | 300          | Great     | Fruits               | 300       |
+/// | `count_100`  | 100          | Ok        | Droplets             | 100       |
+/// | `count_50`   | 50           |           | Tiny droplets        | 50        |
+/// | `count_geki` |              |           |                      | 320       |
+/// | `count_katu` |              |           | Missed tiny droplets | 200       |
+/// | `count_miss` | Miss         | Miss      | Misses               | Miss      |
+///
+/// In osu!mania `count_katu` is the 200 judgement and `count_100` the 100 one,
+/// as stable stores them. Every calculation in this crate reads them that way.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Judgements {
+    pub count_300: i64,
+    pub count_100: i64,
+    pub count_50: i64,
+    pub count_geki: i64,
+    pub count_katu: i64,
+    pub count_miss: i64,
+}
+
+impl Judgements {
+    /// Every judgement that counts towards accuracy in the mode.
+    pub fn total(&self, mode: Mode) -> i64 {
+        let common = self.count_300 + self.count_100 + self.count_50 + self.count_miss;
+        match mode {
+            Mode::Osu => common,
+            Mode::Taiko => self.count_300 + self.count_100 + self.count_miss,
+            Mode::Catch => common + self.count_katu,
+            Mode::Mania => common + self.count_geki + self.count_katu,
+        }
+    }
+
+    /// Accuracy between 0 and 1, as stable shows it. 1 when nothing was judged.
+    pub fn accuracy(&self, mode: Mode) -> f64 {
+        self.weighted_accuracy(mode, 300.0)
+    }
+
+    /// The letter grade stable gives the play.
+    ///
+    /// Hidden and Flashlight, and Fade In in osu!mania, turn SS and S silver.
+    pub fn grade(&self, mode: Mode, mods: Mods) -> Grade {
+        let accuracy = self.accuracy(mode);
+        let grade = match mode {
+            _ if accuracy >= 1.0 => Grade::SS,
+            Mode::Osu | Mode::Taiko => {
+                let total = self.total(mode) as f64;
+                let ratio_300 = self.count_300 as f64 / total;
+                let ratio_50 = match mode {
+                    Mode::Osu => self.count_50 as f64 / total,
+                    _ => 0.0,
+                };
+                let full_combo = self.count_miss == 0;
+                if ratio_300 > 0.9 && ratio_50 <= 0.01 && full_combo {
+                    Grade::S
+                } else if (ratio_300 > 0.8 && full_combo) || ratio_300 > 0.9 {
+                    Grade::A
+                } else if (ratio_300 > 0.7 && full_combo) || ratio_300 > 0.8 {
+                    Grade::B
+                } else if ratio_300 > 0.6 {
+                    Grade::C
+                } else {
+                    Grade::D
+                }
+            }
+            Mode::Catch => grade_by_accuracy(accuracy, [0.98, 0.94, 0.9, 0.85]),
+            Mode::Mania => grade_by_accuracy(accuracy, [0.95, 0.9, 0.8, 0.7]),
+        };
+        let mut silver_mods = Mods::HIDDEN | Mods::FLASHLIGHT;
+        if mode == Mode::Mania {
+            silver_mods.insert(Mods::FADE_IN);
+        }
+        match grade {
+            Grade::SS if mods.intersects(silver_mods) => Grade::SilverSS,
+            Grade::S if mods.intersects(silver_mods) => Grade::SilverS,
+            grade => grade,
+        }
+    }
+
+    // Accuracy with osu!mania's 320s worth `perfect` instead of 300.
+    pub(crate) fn weighted_accuracy(&self, mode: Mode, perfect: f64) -> f64 {
+        let total = self.total(mode);
+        if total == 0 {
+            return 1.0;
+        }
+        let total = total as f64;
+        let (c300, c100, c50) = (
+            self.count_300 as f64,
+            self.count_100 as f64,
+            self.count_50 as f64,
+        );
+        match mode {
+            Mode::Osu => (c300 * 6.0 + c100 * 2.0 + c50) / (total * 6.0),
+            Mode::Taiko => (c300 + c100 / 2.0) / total,
+            Mode::Catch => (c300 + c100 + c50) / total,
+            Mode::Mania => {
+                let (geki, katu) = (self.count_geki as f64, self.count_katu as f64);
+                (geki * perfect + c300 * 300.0 + katu * 200.0 + c100 * 100.0 + c50 * 50.0)
+                    / (total * perfect)
+            }
+        }
+    }
+
+    // What each judgement that builds combo is worth, best first, without the misses.
+    fn combo_hits(&self, mode: Mode) -> Vec<i64> {
+        let counts: &[(i64, i64)] = match mode {
+            Mode::Osu => &[
+                (self.count_300, 300),
+                (self.count_100, 100),
+                (self.count_50, 50),
+            ],
+            Mode::Taiko => &[(self.count_300, 300), (self.count_100, 150)],
+            // Tiny droplets don't build combo.
+            Mode::Catch => &[(self.count_300, 300), (self.count_100, 100)],
+            Mode::Mania => &[
+                (self.count_geki, 320),
+                (self.count_300, 300),
+                (self.count_katu, 200),
+                (self.count_100, 100),
+                (self.count_50, 50),
+            ],
+        };
+        counts
+            .iter()
+            .flat_map(|&(count, value)| std::iter::repeat_n(value, count.max(0) as usize))
+            .collect()
+    }
+
+    // Counts don't say in which order the judgements came, so the best hits are assumed to
+    // make up the highest combo, and the rest to come in runs no longer than it between the
+    // misses. Misses are 0.
+    fn assumed_order(&self, mode: Mode, combo: i64) -> Vec<i64> {
+        let hits = self.combo_hits(mode);
+        let run_length = combo.clamp(1, hits.len().max(1) as i64) as usize;
+        let mut misses = self.count_miss.max(0);
+        let mut order = Vec::with_capacity(hits.len() + misses as usize);
+        for run in hits.chunks(run_length) {
+            if !order.is_empty() && misses > 0 {
+                order.push(0);
+                misses -= 1;
+            }
+            order.extend_from_slice(run);
+        }
+        order.extend(std::iter::repeat_n(0, misses as usize));
+        order
+    }
+}
+
+/// A play's letter grade, from best to worst.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Grade {
+    SilverSS,
+    SS,
+    SilverS,
+    S,
+    A,
+    B,
+    C,
+    D,
+}
+
+// Grades for accuracies above each of the thresholds for S, A, B and C.
+fn grade_by_accuracy(accuracy: f64, thresholds: [f64; 4]) -> Grade {
+    let grades = [Grade::S, Grade::A, Grade::B, Grade::C];
+    thresholds
+        .iter()
+        .zip(grades)
+        .find(|(threshold, _)| accuracy > **threshold)
+        .map_or(Grade::D, |(_, grade)| grade)
+}
+
+/// Stable's difficulty multiplier for ScoreV1, from 0 to 10 and usually 2 to 6.
+///
+/// It comes from the beatmap's own difficulty settings, before any mods,
+/// and how many objects there are for how long the chart drains health.
+pub fn difficulty_multiplier(chart: &Chart, difficulty: &Difficulty, breaks: &[Break]) -> i64 {
+    let first = chart
+        .hit_objects
+        .first()
+        .map_or(0, |object| object.start_time() as i64);
+    let last = chart
+        .hit_objects
+        .last()
+        .map_or(0, |object| object.start_time() as i64);
+    let break_time: i64 = breaks.iter().map(|b| b.end_time - b.start_time).sum();
+    // In whole seconds, and so are the objects per second.
+    let drain_time = (last - first - break_time) / 1000;
+    let object_density = match drain_time {
+        0 => 16,
+        _ => (chart.hit_objects.len() as i64 / drain_time * 8).clamp(0, 16),
+    };
+    let settings = ratio_to_f64(difficulty.hpdrain_rate)
+        + ratio_to_f64(difficulty.overall_difficulty)
+        + ratio_to_f64(difficulty.circle_size);
+    ((settings + object_density as f64) / 38.0 * 5.0).round_ties_even() as i64
+}
+
+/// Calculates stable's ScoreV1 for a chart.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ScoreV1 {
+    pub mode: Mode,
+    /// See [`difficulty_multiplier`].
+    pub difficulty_multiplier: i64,
+    pub mods: Mods,
+}
+
+impl ScoreV1 {
+    pub fn new(
+        mode: Mode,
+        chart: &Chart,
+        difficulty: &Difficulty,
+        breaks: &[Break],
+        mods: Mods,
+    ) -> ScoreV1 {
+        ScoreV1 {
+            mode,
+            difficulty_multiplier: difficulty_multiplier(chart, difficulty, breaks),
+            mods,
+        }
+    }
+
+    /// The score of a play that reached `combo` at most.
+    ///
+    /// Only the judgements are scored. Slider ticks, spinners, drumrolls, swells and
+    /// bananas add points that judgement counts can't tell, so they're left out,
+    /// and the order of the judgements is guessed from `combo`.
+    pub fn total(&self, judgements: &Judgements, combo: i64) -> i64 {
+        let order = judgements.assumed_order(self.mode, combo);
+        let mod_multiplier = self.mods.score_multiplier(self.mode);
+        if self.mode == Mode::Mania {
+            return mania_score_v1(&order, mod_multiplier);
+        }
+        let multiplier = self.difficulty_multiplier as f64 * mod_multiplier;
+        let mut score = 0;
+        let mut combo = 0;
+        for value in order {
+            if value == 0 {
+                combo = 0;
+                continue;
+            }
+            let combo_bonus = match self.mode {
+                // Grows every 10 combo until 100, with stable's integer divisions.
+                Mode::Taiko => {
+                    let step = (value / 35 * 2) as f64 * (self.difficulty_multiplier + 1) as f64;
+                    (step * mod_multiplier) as i64 * (combo.min(100) / 10)
+                }
+                _ => ((combo - 1).max(0) as f64 * (value / 25) as f64 * multiplier) as i64,
+            };
+            score += value + combo_bonus;
+            combo += 1;
+        }
+        if self.mode == Mode::Catch {
+            score += judgements.count_50 * 10;
+        }
+        score
+    }
+}
+
+// osu!mania scores out of a million, shared between the judgements and a bonus for
+// keeping them good, which Good and worse take away from.
+fn mania_score_v1(order: &[i64], mod_multiplier: f64) -> i64 {
+    if order.is_empty() {
+        return 0;
+    }
+    let note_score = 1_000_000.0 * mod_multiplier * 0.5 / order.len() as f64;
+    let mut bonus: f64 = 100.0;
+    let mut score = 0.0;
+    for &value in order {
+        let (bonus_value, gain, punishment) = match value {
+            320 => (32.0, 2.0, 0.0),
+            300 => (32.0, 1.0, 0.0),
+            200 => (16.0, 0.0, 8.0),
+            100 => (8.0, 0.0, 24.0),
+            50 => (4.0, 0.0, 44.0),
+            _ => (0.0, 0.0, 100.0),
+        };
+        bonus = (bonus + gain - punishment).clamp(0.0, 100.0);
+        score += note_score * value as f64 / 320.0;
+        score += note_score * bonus_value * bonus.sqrt() / 320.0;
+    }
+    score.round() as i64
+}
+
+// What lazer's standardised scoring gives osu!mania's 320s.
+const MANIA_PERFECT_VALUE: i64 = 305;
+
+/// Calculates osu!lazer's standardised scoring, out of a million.
+///
+/// Part of the score comes from combo and the rest from accuracy, shared differently
+/// in each mode. osu!mania's 320s are worth 305, a little more than 300s, like in lazer.
+/// Stable's ScoreV2 mod is [`ScoreV2`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct StandardisedScore {
+    pub mode: Mode,
+    pub mods: Mods,
+}
+
+impl StandardisedScore {
+    pub fn new(mode: Mode, mods: Mods) -> StandardisedScore {
+        StandardisedScore { mode, mods }
+    }
+
+    /// The score of a play that reached `combo` at most.
+    ///
+    /// Bonus objects are left out and the order of the judgements is guessed from `combo`,
+    /// like in [`ScoreV1::total`].
+    pub fn total(&self, judgements: &Judgements, combo: i64) -> i64 {
+        let mode = self.mode;
+        let order = judgements.assumed_order(mode, combo);
+        let best = match mode {
+            Mode::Mania => MANIA_PERFECT_VALUE,
+            _ => 300,
+        };
+        let mut combo = 0;
+        let mut combo_score = 0.0;
+        let mut max_combo_score = 0.0;
+        for (i, &value) in order.iter().enumerate() {
+            let value = match value {
+                320 => MANIA_PERFECT_VALUE,
+                value => value,
+            };
+            combo = match value {
+                0 => 0,
+                _ => combo + 1,
+            };
+            combo_score += value as f64 * self.combo_weight(combo);
+            max_combo_score += best as f64 * self.combo_weight(i as i64 + 1);
+        }
+        let combo_progress = match max_combo_score > 0.0 {
+            true => combo_score / max_combo_score,
+            false => 1.0,
+        };
+        let accuracy = match mode {
+            Mode::Mania => judgements.weighted_accuracy(mode, MANIA_PERFECT_VALUE as f64),
+            _ => judgements.accuracy(mode),
+        };
+        let (combo_portion, accuracy_portion) = match mode {
+            Mode::Osu => (700_000.0, 300_000.0 * accuracy.powi(10)),
+            Mode::Taiko => (250_000.0, 750_000.0 * accuracy.powf(3.6)),
+            Mode::Catch => (600_000.0, 400_000.0 * accuracy.powi(7)),
+            Mode::Mania => (150_000.0, 850_000.0 * accuracy.powf(2.0 + 2.0 * accuracy)),
+        };
+        let score = combo_portion * combo_progress + accuracy_portion;
+        (score * self.mods.score_multiplier(mode)).round() as i64
+    }
+
+    // How much a hit at `combo` is worth compared to its judgement.
+    fn combo_weight(&self, combo: i64) -> f64 {
+        let combo = combo as f64;
+        let log_combo = |cap: f64| combo.log(4.0).max(0.5).min(cap.log(4.0));
+        match self.mode {
+            Mode::Osu => combo.sqrt(),
+            Mode::Taiko | Mode::Mania => log_combo(400.0),
+            Mode::Catch => log_combo(200.0),
+        }
+    }
+}
+
+/// Calculates the score of stable's ScoreV2 mod in osu!standard, out of a million.
+///
+/// 700,000 comes from combo, each hit weighted by the combo it reaches,
+/// and 300,000 from accuracy to the power of 10. Bonus points from spinners
+/// are added on top, before the mod multiplier.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ScoreV2 {
+    pub mods: Mods,
+}
+
+impl ScoreV2 {
+    pub fn new(mods: Mods) -> ScoreV2 {
+        ScoreV2 { mods }
+    }
+
+    /// The score of a finished play that reached `combo` at most, with `bonus` points from spinners.
+    ///
+    /// The order of the judgements is guessed from `combo`, like in [`ScoreV1::total`].
+    pub fn total(&self, judgements: &Judgements, combo: i64, bonus: i64) -> i64 {
+        let order = judgements.assumed_order(Mode::Osu, combo);
+        let weight = |combo: usize| 1.0 + combo as f64 / 10.0;
+        let mut combo = 0;
+        let mut combo_score = 0.0;
+        let mut max_combo_score = 0.0;
+        for (i, &value) in order.iter().enumerate() {
+            combo = match value {
+                0 => 0,
+                _ => combo + 1,
+            };
+            combo_score += value as f64 * weight(combo);
+            max_combo_score += 300.0 * weight(i + 1);
+        }
+        let combo_progress = match max_combo_score > 0.0 {
+            true => combo_score / max_combo_score,
+            false => 1.0,
+        };
+        let accuracy = judgements.accuracy(Mode::Osu);
+        let score = 700_000.0 * combo_progress + 300_000.0 * accuracy.powi(10) + bonus as f64;
+        (score * self.mod_multiplier()).round() as i64
+    }
+
+    /// How much the mods multiply the score by, which differs from ScoreV1
+    /// for No Fail, Hard Rock and Double Time.
+    pub fn mod_multiplier(&self) -> f64 {
+        [
+            (Mods::EASY, 0.5),
+            (Mods::HALF_TIME, 0.3),
+            (Mods::HARD_ROCK, 1.1),
+            (Mods::DOUBLE_TIME, 1.2),
+            (Mods::HIDDEN, 1.06),
+            (Mods::FLASHLIGHT, 1.12),
+            (Mods::SPUN_OUT, 0.9),
+        ]
+        .iter()
+        .filter(|(mods, _)| self.mods.contains(*mods))
+        .map(|(_, multiplier)| multiplier)
+        .product()
+    }
+}
+
+/// The result of judging one object or part of one.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Judgement {
+    /// osu!mania's 320.
+    Perfect,
+    Great,
+    /// osu!mania's 200.
+    Good,
+    Ok,
+    Meh,
+    Miss,
+    /// Slider heads, ticks, repeats and ends, droplets and hold ends.
+    LargeTickHit,
+    LargeTickMiss,
+    /// Tiny droplets and drumroll ticks.
+    SmallTickHit,
+    SmallTickMiss,
+    /// Spinner spins and swell hits.
+    SmallBonus,
+    /// Spinner bonus spins and bananas.
+    LargeBonus,
+}
+
+impl Judgement {
+    /// The best judgement the object this was given to could have gotten.
+    pub fn max(&self, mode: Mode) -> Judgement {
+        match self {
+            Judgement::Perfect
+            | Judgement::Great
+            | Judgement::Good
+            | Judgement::Ok
+            | Judgement::Meh
+            | Judgement::Miss => match mode {
+                Mode::Mania => Judgement::Perfect,
+                _ => Judgement::Great,
+            },
+            Judgement::LargeTickHit | Judgement::LargeTickMiss => Judgement::LargeTickHit,
+            Judgement::SmallTickHit | Judgement::SmallTickMiss => Judgement::SmallTickHit,
+            bonus => *bonus,
+        }
+    }
+
+    pub fn is_miss(&self) -> bool {
+        matches!(
+            self,
+            Judgement::Miss | Judgement::LargeTickMiss | Judgement::SmallTickMiss
+        )
+    }
+}
+
+/// A judgement and when in milliseconds it was given.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TimedJudgement {
+    pub time: f64,
+    pub judgement: Judgement,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mania_katu_is_200_and_100_is_100() {
+        let judgements = Judgements {
+            count_katu: 1,
+            count_100: 1,
+            ..Default::default()
+        };
+        assert_eq!(judgements.accuracy(Mode::Mania), 300.0 / 600.0);
+        assert_eq!(
+            judgements.weighted_accuracy(Mode::Mania, 320.0),
+            300.0 / 640.0
+        );
+    }
+
+    #[test]
+    fn mania_standardised_score_scores_perfects_as_305() {
+        let score = StandardisedScore::new(Mode::Mania, Mods::NONE);
+        let perfects = Judgements {
+            count_geki: 100,
+            ..Default::default()
+        };
+        assert_eq!(score.total(&perfects, 100), 1_000_000);
+        let greats = Judgements {
+            count_300: 100,
+            ..Default::default()
+        };
+        let accuracy: f64 = 300.0 / 305.0;
+        let expected = 150_000.0 * accuracy + 850_000.0 * accuracy.powf(2.0 + 2.0 * accuracy);
+        assert_eq!(score.total(&greats, 100), expected.round() as i64);
+    }
+
+    fn score_v1(mode: Mode, mods: Mods) -> ScoreV1 {
+        ScoreV1 {
+            mode,
+            difficulty_multiplier: 4,
+            mods,
+        }
+    }
+
+    fn greats(count_300: i64) -> Judgements {
+        Judgements {
+            count_300,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn osu_score_v1_bonus_starts_from_the_third_hit() {
+        // 300 / 25 * 4 for the third hit.
+        assert_eq!(score_v1(Mode::Osu, Mods::NONE).total(&greats(3), 3), 948);
+        // The bonus is truncated, so 48 * 1.06 is 50.
+        assert_eq!(score_v1(Mode::Osu, Mods::HIDDEN).total(&greats(3), 3), 950);
+        // A miss in the middle starts the combo over.
+        let judgements = Judgements {
+            count_300: 4,
+            count_miss: 1,
+            ..Default::default()
+        };
+        assert_eq!(score_v1(Mode::Osu, Mods::NONE).total(&judgements, 2), 1200);
+    }
+
+    #[test]
+    fn taiko_score_v1_bonus_grows_every_10_combo_until_100() {
+        let score = score_v1(Mode::Taiko, Mods::NONE);
+        // Each step is 300 / 35 * 2 * (4 + 1) = 80, from the 11th great.
+        assert_eq!(score.total(&greats(10), 10), 3000);
+        assert_eq!(score.total(&greats(12), 12), 3600 + 2 * 80);
+        // 10 greats at each step up to 9, then 20 at the cap of 10.
+        assert_eq!(score.total(&greats(120), 120), 36_000 + (450 + 200) * 80);
+        let oks = Judgements {
+            count_100: 11,
+            ..Default::default()
+        };
+        assert_eq!(score.total(&oks, 11), 11 * 150 + 150 / 35 * 2 * 5);
+    }
+
+    #[test]
+    fn catch_score_v1_adds_tiny_droplets_without_combo() {
+        let judgements = Judgements {
+            count_300: 3,
+            count_50: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            score_v1(Mode::Catch, Mods::NONE).total(&judgements, 3),
+            948 + 20
+        );
+    }
+
+    #[test]
+    fn mania_score_v1_shares_a_million() {
+        let score = score_v1(Mode::Mania, Mods::NONE);
+        let perfects = Judgements {
+            count_geki: 1,
+            ..Default::default()
+        };
+        assert_eq!(score.total(&perfects, 1), 1_000_000);
+        // 300s are worth a little less, but keep the whole bonus.
+        assert_eq!(score.total(&greats(1), 1), 500_000 * 300 / 320 + 500_000);
+        let easy = score_v1(Mode::Mania, Mods::EASY);
+        assert_eq!(easy.total(&perfects, 1), 500_000);
+    }
+
+    #[test]
+    fn score_v2_shares_a_million_between_combo_and_accuracy() {
+        let score = ScoreV2::new(Mods::NONE);
+        assert_eq!(score.total(&greats(100), 100, 0), 1_000_000);
+        assert_eq!(score.total(&greats(100), 100, 1000), 1_001_000);
+        assert_eq!(score.total(&Judgements::default(), 0, 0), 1_000_000);
+
+        let judgements = Judgements {
+            count_300: 2,
+            count_100: 1,
+            ..Default::default()
+        };
+        // Hits at combo 1, 2 and 3 are weighted 1.1, 1.2 and 1.3.
+        let combo_progress = (300.0 * 1.1 + 300.0 * 1.2 + 100.0 * 1.3) / (300.0 * 3.6);
+        let accuracy: f64 = 14.0 / 18.0;
+        let expected = 700_000.0 * combo_progress + 300_000.0 * accuracy.powi(10);
+        assert_eq!(score.total(&judgements, 3, 0), expected.round() as i64);
+
+        let mods = ScoreV2::new(Mods::HARD_ROCK | Mods::DOUBLE_TIME);
+        assert_eq!(mods.total(&greats(100), 100, 0), 1_320_000);
+        assert_eq!(ScoreV2::new(Mods::NO_FAIL).mod_multiplier(), 1.0);
+    }
+}
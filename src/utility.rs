@@ -0,0 +1,405 @@
+use num::rational::Ratio;
+use std::cmp::Ordering;
+
+pub fn ratio_to_f64(ratio: Ratio<i64>) -> f64 {
+    *ratio.numer() as f64 / *ratio.denom() as f64
+}
+
+/// Maps a difficulty value from 0 to 10 onto a range with `mid` at 5,
+/// like osu! does for approach rate and hit windows.
+pub fn difficulty_range(difficulty: f64, min: f64, mid: f64, max: f64) -> f64 {
+    if difficulty > 5.0 {
+        mid + (max - mid) * (difficulty - 5.0) / 5.0
+    } else if difficulty < 5.0 {
+        mid + (mid - min) * (difficulty - 5.0) / 5.0
+    } else {
+        mid
+    }
+}
+
+/// Writes a ratio as a decimal like it appears in .osu files.
+/// Ratios without an exact decimal form are written through `f64`.
+pub fn ratio_to_decimal(ratio: Ratio<i64>) -> String {
+    let (numer, denom) = (*ratio.numer(), *ratio.denom());
+    let mut places = 0;
+    let mut scale: i64 = 1;
+    while scale % denom != 0 {
+        if places == 15 {
+            return ratio_to_f64(ratio).to_string();
+        }
+        places += 1;
+        scale *= 10;
+    }
+    let value = numer * (scale / denom);
+    let sign = if value < 0 { "-" } else { "" };
+    let (whole, fraction) = (value.abs() / scale, value.abs() % scale);
+    match places {
+        0 => format!("{sign}{whole}"),
+        _ => {
+            let fraction = format!("{fraction:0places$}");
+            format!("{sign}{whole}.{}", fraction.trim_end_matches('0'))
+        }
+    }
+}
+
+/// The xorshift generator osu!stable uses wherever beatmap conversion is random,
+/// so the same seed gives the same results as in game.
+pub struct LegacyRandom {
+    x: u32,
+    y: u32,
+    z: u32,
+    w: u32,
+    bit_buffer: u32,
+    bit_index: u32,
+}
+
+impl LegacyRandom {
+    pub fn new(seed: i32) -> Self {
+        Self {
+            x: seed as u32,
+            y: 842502087,
+            z: 3579807591,
+            w: 273326509,
+            bit_buffer: 0,
+            bit_index: 32,
+        }
+    }
+
+    pub fn next_uint(&mut self) -> u32 {
+        let t = self.x ^ (self.x << 11);
+        self.x = self.y;
+        self.y = self.z;
+        self.z = self.w;
+        self.w = self.w ^ (self.w >> 19) ^ t ^ (t >> 8);
+        self.w
+    }
+
+    /// A non-negative `i32`.
+    pub fn next(&mut self) -> i32 {
+        (self.next_uint() & 0x7FFFFFFF) as i32
+    }
+
+    /// A value from 0 up to but not including 1.
+    pub fn next_double(&mut self) -> f64 {
+        self.next() as f64 / (i32::MAX as f64 + 1.0)
+    }
+
+    /// A value from `lower` up to but not including `upper`, truncated towards zero.
+    pub fn next_range(&mut self, lower: f64, upper: f64) -> i32 {
+        (lower + self.next_double() * (upper - lower)) as i32
+    }
+
+    /// Booleans are taken a bit at a time from a single draw.
+    pub fn next_bool(&mut self) -> bool {
+        if self.bit_index == 32 {
+            self.bit_buffer = self.next_uint();
+            self.bit_index = 1;
+            return self.bit_buffer & 1 == 1;
+        }
+        self.bit_index += 1;
+        self.bit_buffer >>= 1;
+        self.bit_buffer & 1 == 1
+    }
+}
+
+/// The unstable introsort .NET Framework used for `Array.Sort`.
+///
+/// Some of osu!'s calculations depend on the order it leaves equal elements in,
+/// so a stable sort doesn't give the same results.
+pub fn legacy_sort<T: Clone>(items: &mut [T], compare: impl Fn(&T, &T) -> Ordering) {
+    if items.len() < 2 {
+        return;
+    }
+    // One more than the floor of log2, as .NET calculated it.
+    let mut depth_limit = 0;
+    let mut n = items.len();
+    while n >= 1 {
+        depth_limit += 1;
+        n /= 2;
+    }
+    intro_sort(items, 0, items.len() - 1, 2 * depth_limit, &compare);
+}
+
+fn intro_sort<T: Clone>(
+    items: &mut [T],
+    lo: usize,
+    mut hi: usize,
+    mut depth_limit: usize,
+    compare: &impl Fn(&T, &T) -> Ordering,
+) {
+    while hi > lo {
+        let size = hi - lo + 1;
+        if size <= 16 {
+            match size {
+                2 => swap_if_greater(items, lo, hi, compare),
+                3 => {
+                    swap_if_greater(items, lo, hi - 1, compare);
+                    swap_if_greater(items, lo, hi, compare);
+                    swap_if_greater(items, hi - 1, hi, compare);
+                }
+                _ => insertion_sort(items, lo, hi, compare),
+            }
+            return;
+        }
+        if depth_limit == 0 {
+            heap_sort(items, lo, hi, compare);
+            return;
+        }
+        depth_limit -= 1;
+        let pivot = pick_pivot_and_partition(items, lo, hi, compare);
+        intro_sort(items, pivot + 1, hi, depth_limit, compare);
+        hi = pivot - 1;
+    }
+}
+
+fn swap_if_greater<T>(items: &mut [T], a: usize, b: usize, compare: &impl Fn(&T, &T) -> Ordering) {
+    if a != b && compare(&items[a], &items[b]) == Ordering::Greater {
+        items.swap(a, b);
+    }
+}
+
+fn pick_pivot_and_partition<T: Clone>(
+    items: &mut [T],
+    lo: usize,
+    hi: usize,
+    compare: &impl Fn(&T, &T) -> Ordering,
+) -> usize {
+    // Sorts the low, middle and high elements, then pivots on the middle one.
+    let middle = lo + ((hi - lo) >> 1);
+    swap_if_greater(items, lo, middle, compare);
+    swap_if_greater(items, lo, hi, compare);
+    swap_if_greater(items, middle, hi, compare);
+    let pivot = items[middle].clone();
+    items.swap(middle, hi - 1);
+    let mut left = lo;
+    let mut right = hi - 1;
+    while left < right {
+        left += 1;
+        while compare(&items[left], &pivot) == Ordering::Less {
+            left += 1;
+        }
+        right -= 1;
+        while compare(&pivot, &items[right]) == Ordering::Less {
+            right -= 1;
+        }
+        if left >= right {
+            break;
+        }
+        items.swap(left, right);
+    }
+    items.swap(left, hi - 1);
+    left
+}
+
+fn heap_sort<T: Clone>(
+    items: &mut [T],
+    lo: usize,
+    hi: usize,
+    compare: &impl Fn(&T, &T) -> Ordering,
+) {
+    let n = hi - lo + 1;
+    for i in (1..=n >> 1).rev() {
+        down_heap(items, i, n, lo, compare);
+    }
+    for i in (2..=n).rev() {
+        items.swap(lo, lo + i - 1);
+        down_heap(items, 1, i - 1, lo, compare);
+    }
+}
+
+fn down_heap<T: Clone>(
+    items: &mut [T],
+    mut i: usize,
+    n: usize,
+    lo: usize,
+    compare: &impl Fn(&T, &T) -> Ordering,
+) {
+    let d = items[lo + i - 1].clone();
+    while i <= n >> 1 {
+        let mut child = 2 * i;
+        if child < n && compare(&items[lo + child - 1], &items[lo + child]) == Ordering::Less {
+            child += 1;
+        }
+        if compare(&d, &items[lo + child - 1]) != Ordering::Less {
+            break;
+        }
+        items[lo + i - 1] = items[lo + child - 1].clone();
+        i = child;
+    }
+    items[lo + i - 1] = d;
+}
+
+fn insertion_sort<T: Clone>(
+    items: &mut [T],
+    lo: usize,
+    hi: usize,
+    compare: &impl Fn(&T, &T) -> Ordering,
+) {
+    for i in lo..hi {
+        let t = items[i + 1].clone();
+        let mut j = i as isize;
+        while j >= lo as isize && compare(&t, &items[j as usize]) == Ordering::Less {
+            items[j as usize + 1] = items[j as usize].clone();
+            j -= 1;
+        }
+        items[(j + 1) as usize] = t;
+    }
+}
+
+/// The error function, accurate to about double precision.
+pub fn erf(x: f64) -> f64 {
+    if x.is_nan() {
+        return f64::NAN;
+    }
+    if x.abs() > 2.5 {
+        return x.signum() * (1.0 - erfc_continued_fraction(x.abs()));
+    }
+    // The Maclaurin series converges quickly for small values.
+    let mut sum = x;
+    let mut term = x;
+    let x2 = x * x;
+    let mut n = 0.0;
+    while term.abs() > 1e-17 * sum.abs() {
+        n += 1.0;
+        term *= -x2 / n;
+        sum += term / (2.0 * n + 1.0);
+    }
+    sum * 2.0 / std::f64::consts::PI.sqrt()
+}
+
+// The complementary error function for large positive values, by Lentz's method.
+fn erfc_continued_fraction(x: f64) -> f64 {
+    let tiny = 1e-300;
+    let mut f = x;
+    let mut c = x;
+    let mut d = 0.0;
+    for n in 1..200 {
+        let a = n as f64 / 2.0;
+        d = x + a * d;
+        d = if d == 0.0 { tiny } else { 1.0 / d };
+        c = x + a / c;
+        if c == 0.0 {
+            c = tiny;
+        }
+        let delta = c * d;
+        f *= delta;
+        if (delta - 1.0).abs() < 1e-16 {
+            break;
+        }
+    }
+    (-x * x).exp() / std::f64::consts::PI.sqrt() / f
+}
+
+/// The inverse of [`erf`], infinite at -1 and 1.
+pub fn erf_inv(y: f64) -> f64 {
+    if y <= -1.0 {
+        return f64::NEG_INFINITY;
+    }
+    if y >= 1.0 {
+        return f64::INFINITY;
+    }
+    if y == 0.0 {
+        return 0.0;
+    }
+    // Giles' approximation, refined with Newton's method.
+    let w = -((1.0 - y) * (1.0 + y)).ln();
+    let mut x = if w < 5.0 {
+        let w = w - 2.5;
+        let mut p = 2.810_226_36e-08;
+        for c in [
+            3.432_739_39e-07,
+            -3.523_387_7e-06,
+            -4.391_506_54e-06,
+            0.000_218_580_87,
+            -0.001_253_725_03,
+            -0.004_177_681_64,
+            0.246_640_727,
+            1.501_409_41,
+        ] {
+            p = c + p * w;
+        }
+        p * y
+    } else {
+        let w = w.sqrt() - 3.0;
+        let mut p = -0.000_200_214_257;
+        for c in [
+            0.000_100_950_558,
+            0.001_349_343_22,
+            -0.003_673_428_44,
+            0.005_739_507_73,
+            -0.007_622_461_3,
+            0.009_438_870_47,
+            1.001_674_06,
+            2.832_976_82,
+        ] {
+            p = c + p * w;
+        }
+        p * y
+    };
+    for _ in 0..3 {
+        let error = erf(x) - y;
+        let slope = 2.0 / std::f64::consts::PI.sqrt() * (-x * x).exp();
+        if slope == 0.0 {
+            break;
+        }
+        x -= error / slope;
+    }
+    x
+}
+
+/// A beatmap from `tests/fixtures`, parsed into the collections the tests use.
+///
+/// Star ratings and performance pinned for the fixtures come from this crate,
+/// not osu!lazer, so they catch regressions rather than prove the ports exact.
+#[cfg(test)]
+pub struct Fixture {
+    pub chart: crate::chart::Chart,
+    pub difficulty: crate::difficulty::Difficulty,
+    pub filedata: crate::filedata::Filedata,
+}
+
+#[cfg(test)]
+pub fn fixture(name: &str) -> Fixture {
+    parse_fixture(&fixture_text(name))
+}
+
+/// The contents of a file in `tests/fixtures`.
+#[cfg(test)]
+pub fn fixture_text(name: &str) -> String {
+    let path = format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"));
+    std::fs::read_to_string(path).expect("fixture exists")
+}
+
+/// The [Difficulty] of [`beatmap`] when a test doesn't care about it.
+#[cfg(test)]
+pub const DIFFICULTY: &str = "HPDrainRate:5
+CircleSize:4
+OverallDifficulty:8
+ApproachRate:9
+SliderMultiplier:1.4
+SliderTickRate:1";
+
+/// A small beatmap written out in the test, for checking exact objects.
+///
+/// `difficulty`, `timing_points` and `hit_objects` are the lines of those sections.
+#[cfg(test)]
+pub fn beatmap(mode: u8, difficulty: &str, timing_points: &str, hit_objects: &str) -> Fixture {
+    let text = format!(
+        "osu file format v14\n\n[General]\nAudioFilename: audio.mp3\nMode: {mode}\n\n\
+         [Difficulty]\n{difficulty}\n\n[TimingPoints]\n{timing_points}\n\n\
+         [HitObjects]\n{hit_objects}\n"
+    );
+    parse_fixture(&text)
+}
+
+#[cfg(test)]
+fn parse_fixture(text: &str) -> Fixture {
+    let (chart, _, difficulty, _, filedata, _) =
+        crate::parse::parse_str(text, true, false, true, false, true, false)
+            .expect("fixture parses");
+    Fixture {
+        chart: chart.unwrap(),
+        difficulty: difficulty.unwrap(),
+        filedata: filedata.unwrap(),
+    }
+}
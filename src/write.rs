@@ -0,0 +1,259 @@
+use crate::chart::Chart;
+use crate::chart::Mode;
+use crate::customization::Countdown;
+use crate::customization::Customization;
+use crate::customization::OverlayPosition;
+use crate::difficulty::Difficulty;
+use crate::editor::Editor;
+use crate::filedata::Filedata;
+use crate::metadata::Metadata;
+use crate::utility::ratio_to_decimal;
+use std::fmt::Write;
+
+/// Writes a complete .osu file, the reverse of [`parse_str`](crate::parse::parse_str).
+///
+/// Videos and storyboard events aren't modelled, so they aren't written.
+pub fn write_str(
+    chart: &Chart,
+    customization: &Customization,
+    difficulty: &Difficulty,
+    editor: &Editor,
+    filedata: &Filedata,
+    metadata: &Metadata,
+) -> String {
+    let mut s = String::new();
+    // Writing to a String can't fail.
+    write_sections(
+        &mut s,
+        chart,
+        customization,
+        difficulty,
+        editor,
+        filedata,
+        metadata,
+    )
+    .unwrap();
+    s
+}
+
+fn write_sections(
+    s: &mut String,
+    chart: &Chart,
+    customization: &Customization,
+    difficulty: &Difficulty,
+    editor: &Editor,
+    filedata: &Filedata,
+    metadata: &Metadata,
+) -> std::fmt::Result {
+    writeln!(s, "osu file format v{}", filedata.file_format)?;
+
+    writeln!(s, "\n[General]")?;
+    writeln!(s, "AudioFilename: {}", filedata.audio_filename)?;
+    writeln!(s, "AudioLeadIn: {}", filedata.audio_lead_in)?;
+    if let Some(audio_hash) = &filedata.audio_hash {
+        writeln!(s, "AudioHash: {audio_hash}")?;
+    }
+    writeln!(s, "PreviewTime: {}", filedata.preview_time)?;
+    let countdown = match customization.countdown {
+        Countdown::None => 0,
+        Countdown::Normal => 1,
+        Countdown::Half => 2,
+        Countdown::Double => 3,
+    };
+    writeln!(s, "Countdown: {countdown}")?;
+    writeln!(s, "SampleSet: {}", customization.sample_set)?;
+    writeln!(
+        s,
+        "StackLeniency: {}",
+        ratio_to_decimal(chart.stack_leniency)
+    )?;
+    let mode = match chart.mode {
+        Mode::Osu => 0,
+        Mode::Taiko => 1,
+        Mode::Catch => 2,
+        Mode::Mania => 3,
+    };
+    writeln!(s, "Mode: {mode}")?;
+    writeln!(
+        s,
+        "LetterboxInBreaks: {}",
+        customization.letterbox_in_breaks as u8
+    )?;
+    // Deprecated settings are only written when they aren't the default.
+    if !customization.story_fire_in_front {
+        writeln!(s, "StoryFireInFront: 0")?;
+    }
+    if customization.use_skin_sprites {
+        writeln!(s, "UseSkinSprites: 1")?;
+    }
+    if customization.always_show_play_field {
+        writeln!(s, "AlwaysShowPlayField: 1")?;
+    }
+    match customization.overlay_position {
+        OverlayPosition::NoChange => {}
+        OverlayPosition::Below => writeln!(s, "OverlayPosition: Below")?,
+        OverlayPosition::Above => writeln!(s, "OverlayPosition: Above")?,
+    }
+    if let Some(skin_preference) = &customization.skin_preference {
+        writeln!(s, "SkinPreference: {skin_preference}")?;
+    }
+    if customization.epilepsy_warning {
+        writeln!(s, "EpilepsyWarning: 1")?;
+    }
+    if filedata.countdown_offset != 0 {
+        writeln!(s, "CountdownOffset: {}", filedata.countdown_offset)?;
+    }
+    if chart.mode == Mode::Mania {
+        writeln!(s, "SpecialStyle: {}", customization.special_style as u8)?;
+    }
+    writeln!(
+        s,
+        "WidescreenStoryboard: {}",
+        customization.widescreen_storyboard as u8
+    )?;
+    if customization.samples_match_playback_rate {
+        writeln!(s, "SamplesMatchPlaybackRate: 1")?;
+    }
+
+    writeln!(s, "\n[Editor]")?;
+    if !editor.bookmarks.is_empty() {
+        let bookmarks: Vec<String> = editor.bookmarks.iter().map(|b| b.to_string()).collect();
+        writeln!(s, "Bookmarks: {}", bookmarks.join(","))?;
+    }
+    if let Some(distance_spacing) = editor.distance_spacing {
+        writeln!(s, "DistanceSpacing: {}", ratio_to_decimal(distance_spacing))?;
+    }
+    if let Some(beat_divisor) = editor.beat_divisor {
+        writeln!(s, "BeatDivisor: {beat_divisor}")?;
+    }
+    if let Some(grid_size) = editor.grid_size {
+        writeln!(s, "GridSize: {grid_size}")?;
+    }
+    if let Some(timeline_zoom) = editor.timeline_zoom {
+        writeln!(s, "TimelineZoom: {}", ratio_to_decimal(timeline_zoom))?;
+    }
+
+    writeln!(s, "\n[Metadata]")?;
+    let fields = [
+        ("Title", &metadata.title),
+        ("TitleUnicode", &metadata.title_unicode),
+        ("Artist", &metadata.artist),
+        ("ArtistUnicode", &metadata.artist_unicode),
+        ("Creator", &metadata.creator),
+        ("Version", &metadata.version),
+        ("Source", &metadata.source),
+    ];
+    for (key, value) in fields {
+        if let Some(value) = value {
+            writeln!(s, "{key}:{value}")?;
+        }
+    }
+    writeln!(s, "Tags:{}", metadata.tags.join(" "))?;
+    if let Some(beatmap_id) = metadata.beatmap_id {
+        writeln!(s, "BeatmapID:{beatmap_id}")?;
+    }
+    if let Some(beatmap_set_id) = metadata.beatmap_set_id {
+        writeln!(s, "BeatmapSetID:{beatmap_set_id}")?;
+    }
+
+    writeln!(s, "\n[Difficulty]")?;
+    writeln!(
+        s,
+        "HPDrainRate:{}",
+        ratio_to_decimal(difficulty.hpdrain_rate)
+    )?;
+    writeln!(s, "CircleSize:{}", ratio_to_decimal(difficulty.circle_size))?;
+    writeln!(
+        s,
+        "OverallDifficulty:{}",
+        ratio_to_decimal(difficulty.overall_difficulty)
+    )?;
+    writeln!(
+        s,
+        "ApproachRate:{}",
+        ratio_to_decimal(difficulty.approach_rate)
+    )?;
+    writeln!(
+        s,
+        "SliderMultiplier:{}",
+        ratio_to_decimal(chart.slider_multiplier)
+    )?;
+    writeln!(
+        s,
+        "SliderTickRate:{}",
+        ratio_to_decimal(chart.slider_tick_rate)
+    )?;
+
+    writeln!(s, "\n[Events]")?;
+    writeln!(s, "//Background and Video events")?;
+    for background in customization.backgrounds.iter() {
+        writeln!(s, "{background}")?;
+    }
+    writeln!(s, "//Break Periods")?;
+    for event in customization.breaks.iter() {
+        writeln!(s, "{event}")?;
+    }
+    for event in customization.other_events.iter() {
+        writeln!(s, "{event}")?;
+    }
+
+    writeln!(s, "\n[TimingPoints]")?;
+    for timing_point in chart.timing_points.iter() {
+        writeln!(s, "{timing_point}")?;
+    }
+
+    if !customization.colours.entries.is_empty() {
+        write!(s, "\n{}", customization.colours)?;
+    }
+
+    writeln!(s, "\n[HitObjects]")?;
+    for object in chart.hit_objects.iter() {
+        writeln!(s, "{object}")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse_str;
+    use crate::utility::fixture_text;
+
+    fn round_trip(name: &str) {
+        let text = fixture_text(name);
+        let (chart, customization, difficulty, editor, filedata, metadata) =
+            parse_str(&text, true, true, true, true, true, true).expect("fixture parses");
+        let written = write_str(
+            &chart.clone().unwrap(),
+            &customization.clone().unwrap(),
+            &difficulty.unwrap(),
+            &editor.clone().unwrap(),
+            &filedata.clone().unwrap(),
+            &metadata.clone().unwrap(),
+        );
+        let parsed =
+            parse_str(&written, true, true, true, true, true, true).expect("written file parses");
+        assert_eq!(
+            parsed,
+            (chart, customization, difficulty, editor, filedata, metadata),
+            "{name} changed after a round trip"
+        );
+        let (chart, customization, difficulty, editor, filedata, metadata) = parsed;
+        let rewritten = write_str(
+            &chart.unwrap(),
+            &customization.unwrap(),
+            &difficulty.unwrap(),
+            &editor.unwrap(),
+            &filedata.unwrap(),
+            &metadata.unwrap(),
+        );
+        assert_eq!(rewritten, written);
+    }
+
+    #[test]
+    fn fixtures_round_trip() {
+        for name in ["osu.osu", "taiko.osu", "catch.osu", "mania.osu"] {
+            round_trip(name);
+        }
+    }
+}